@@ -1,51 +1,1203 @@
+mod error;
+mod measure;
+mod plot;
+mod report;
+mod sys;
+
+use std::alloc::Layout;
 use std::env;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
-use std::os::unix::io::RawFd;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-const DEFAULT_SIZES_MB: &[usize] = &[64, 96, 128];
-const PIPE_READ: usize = 0;
-const PIPE_WRITE: usize = 1;
-const _SC_PAGESIZE: i32 = 30;
+use error::Error;
+use measure::{
+    buffer_usage, memory_source, page_size, read_anon_huge_kb, read_buffer_nodes,
+    read_buffer_usage, read_hugetlb_kb, read_lazy_free_kb, read_locked_kb, read_mapped_kb,
+    read_private_dirty_kb, read_pss_kb, read_rss_kb, read_shared_clean_kb, read_shared_dirty_kb,
+    read_stat_faults, read_swap_kb, read_vm_faults, rss_source, BufferUsage, MemorySource,
+    NodeUsage, Procfs, RssSource, StatFaults, VmFaults,
+};
+use report::Record;
 
-unsafe extern "C" {
-    fn fork() -> i32;
-    fn waitpid(pid: i32, status: *mut i32, options: i32) -> i32;
-    fn pipe(fds: *mut i32) -> i32;
-    fn close(fd: i32) -> i32;
-    fn read(fd: i32, buf: *mut u8, count: usize) -> isize;
-    fn write(fd: i32, buf: *const u8, count: usize) -> isize;
-    fn sysconf(name: i32) -> isize;
-    fn _exit(status: i32) -> !;
-}
+use sys::{
+    Fork, ENOMEM, MADV_DONTFORK, MADV_DONTNEED, MADV_FREE, MADV_HUGEPAGE, MADV_MERGEABLE,
+    MADV_NOHUGEPAGE, MADV_WIPEONFORK, MAP_ANONYMOUS, MAP_HUGETLB, MAP_PRIVATE, MAP_SHARED,
+    RLIMIT_AS, RLIMIT_DATA, RLIMIT_MEMLOCK, RLIM_INFINITY,
+};
 
-#[derive(Debug)]
+const DEFAULT_SIZES_MB: &[usize] = &[64, 96, 128];
+const THP_ENABLED_PATH: &str = "/sys/kernel/mm/transparent_hugepage/enabled";
+const DEFAULT_HUGE_PAGE_KB: u64 = 2048;
+const KSM_PATH: &str = "/sys/kernel/mm/ksm";
+const KSM_TIMEOUT: Duration = Duration::from_secs(60);
+/// Buffers from this size up report their fill and touch progress on stderr.
+const PROGRESS_MIN_BYTES: usize = 1 << 30;
+/// Progress is reported every this many percent.
+const PROGRESS_STEP_PERCENT: usize = 10;
+/// How long a `--concurrent` child waits for the others at a start gate
+/// before touching on its own.
+const GATE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Progress reports a `--dirty-series` child sends over its touch phase,
+/// besides one before it starts.
+const DIRTY_SERIES_SAMPLES: usize = 64;
+/// How often the `--tui` gauges are sampled and redrawn.
+const TUI_INTERVAL: Duration = Duration::from_millis(100);
+/// Timed samples of each `--bench-touch` variant, after `BENCH_WARMUP`
+/// untimed ones.
+const BENCH_SAMPLES: usize = 10;
+const BENCH_WARMUP: usize = 2;
+
+#[derive(Clone, Debug)]
 struct Config {
     sizes_mb: Vec<usize>,
     output: Option<PathBuf>,
+    plot: Option<PathBuf>,
+    report: Option<PathBuf>,
+    /// SQLite database the CSV rows are appended to, through `sqlite3`.
+    sqlite: Option<PathBuf>,
+    /// JSON-lines file, or `-` for stdout, each result is written to as it
+    /// completes.
+    stream: Option<PathBuf>,
+    /// Also stream the parent's and children's figures at every checkpoint.
+    stream_samples: bool,
+    /// Send the narration to /dev/null; warnings and errors still go to
+    /// stderr.
+    quiet: bool,
+    /// Have each forked child pause at regular steps of its touch phase
+    /// while the parent reads its Private_Dirty.
+    dirty_series: bool,
+    format: OutputFormat,
+    /// Add rows to an existing `--output` CSV instead of replacing it.
+    append: bool,
+    alloc: AllocKind,
+    touch_order: TouchOrder,
+    /// Touch only every `stride`-th page.
+    stride: usize,
+    /// Split the touch phase into this many measured slices.
+    rounds: usize,
+    /// Time every page write of the touch phase.
+    latency: bool,
+    /// Count the pages the child wrote from their soft-dirty bits.
+    soft_dirty: bool,
+    seed: u64,
+    write_size: WriteSize,
+    thp: Option<ThpMode>,
+    children: usize,
+    /// Generations in each child's fork chain: every child but the last
+    /// forks the next before touching.
+    depth: usize,
+    /// Release every child's touch stages at the same instant.
+    concurrent: bool,
+    /// Step the children through `Checkpoint::PostRead` before they write.
+    read_stage: bool,
+    /// Checksum the buffer around the fork on both sides.
+    verify: bool,
+    spawn: SpawnKind,
+    thread_baseline: bool,
+    /// Also fork a child that copies the whole buffer right away.
+    eager_copy: bool,
+    parent_write: Option<ParentWrite>,
+    fork_advice: Option<ForkAdvice>,
+    /// Step the children through `Checkpoint::PostDiscard` after they write.
+    discard: Option<DiscardAdvice>,
+    mlock: bool,
+    ksm: bool,
+    numa: Option<NumaNodes>,
+    /// MB of address space and data the experiments may add to what the
+    /// program uses at startup, enforced with `RLIMIT_AS`/`RLIMIT_DATA`.
+    max_memory_mb: Option<usize>,
+    /// Run inside a cgroup v2 group with `memory.max` set to this many MB.
+    cgroup_limit_mb: Option<usize>,
+    /// Run every size once per entry of `PAGE_VARIANTS` instead of with
+    /// `alloc` and `thp`.
+    compare_pages: bool,
+    /// Directory to clone a file in with `FICLONE` after the experiments.
+    fs_cow: Option<PathBuf>,
+    /// Print a readiness report for this configuration and exit.
+    check: bool,
+    /// Draw live memory gauges at the top of the terminal during each run.
+    tui: bool,
+    /// Time the touch loop's variants in this process and exit.
+    bench_touch: bool,
+    /// Map and fork a buffer sized against the commit limit and exit.
+    overcommit: bool,
+    repeat: usize,
+    warmup: usize,
+    /// Kill the children and fail the experiment if they take longer.
+    child_timeout: Option<Duration>,
+    /// Set only in a helper started by `--spawn posix-spawn`: the pipes to
+    /// read checkpoints from and report on.
+    spawn_helper: Option<(RawFd, RawFd)>,
+    /// Fork one more child that execs this program after the others (`--exec`).
+    exec: bool,
+    /// Set only in the program an `--exec` child became: the pipes to wait
+    /// on and to announce itself through.
+    exec_helper: Option<(RawFd, RawFd)>,
 }
 
-#[derive(Debug)]
+/// Outcome of one `--check` item. Warnings degrade the measurements but do not
+/// stop a run; failures do.
+#[derive(Clone, Copy, PartialEq)]
+enum Readiness {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl Readiness {
+    fn label(self) -> &'static str {
+        match self {
+            Readiness::Ok => "ok",
+            Readiness::Warn => "warn",
+            Readiness::Fail => "FAIL",
+        }
+    }
+}
+
+struct CheckItem {
+    readiness: Readiness,
+    name: &'static str,
+    detail: String,
+}
+
+/// A child measured just before and just after it execs (`--exec`), with the
+/// parent's view of the buffer while the child shares it and once it is gone.
+#[derive(Debug, Clone, Copy)]
+struct ExecReport {
+    fork_us: f64,
+    /// From releasing the child to hearing from the program it became.
+    exec_us: f64,
+    before_rss_kb: u64,
+    before_private_dirty_kb: u64,
+    before_shared_dirty_kb: u64,
+    /// The child's buffer mapping before exec; exec unmaps it.
+    before_buffer: BufferUsage,
+    after_rss_kb: u64,
+    after_private_dirty_kb: u64,
+    after_shared_dirty_kb: u64,
+    parent_buffer_before: BufferUsage,
+    parent_buffer_after: BufferUsage,
+}
+
+/// Buffer checksums around the fork (`--verify`): the parent's before forking
+/// and after every child has finished, and how many children saw the
+/// pre-fork contents before writing.
+#[derive(Debug, Clone, Copy)]
+struct Verification {
+    parent_before: u64,
+    parent_after: u64,
+    /// The parent's view may legitimately change: the mapping is shared with
+    /// the children, or the parent wrote to it itself (`--parent-write`).
+    parent_may_change: bool,
+    children_matched: usize,
+    /// Children that reported a checksum; spawned helpers have no inherited
+    /// buffer to check.
+    children_checked: usize,
+}
+
+impl Verification {
+    /// Whether the parent's view came through as it should.
+    fn parent_ok(self) -> bool {
+        self.parent_before == self.parent_after || self.parent_may_change
+    }
+
+    fn status(self) -> &'static str {
+        match (
+            self.parent_before == self.parent_after,
+            self.parent_may_change,
+        ) {
+            (true, _) => "preserved",
+            (false, true) => "changed as expected",
+            (false, false) => "CORRUPTED",
+        }
+    }
+}
+
+/// Block-level comparison of a file and its `--fs-cow` clone.
+struct BlockSharing {
+    /// Blocks of the clone at the same place on disk as the source's.
+    shared: u64,
+    /// Blocks the clone has written elsewhere.
+    diverged: u64,
+    /// Blocks of the clone the filesystem flags as shared
+    /// (`FIEMAP_EXTENT_SHARED`), with the source or anything else.
+    flagged_shared: u64,
+}
+
+/// Advice applied to the buffer just before forking: `DontFork` leaves it out
+/// of the child entirely, `WipeOnFork` hands the child zero-filled pages.
+#[derive(Clone, Copy, Debug)]
+enum ForkAdvice {
+    DontFork,
+    WipeOnFork,
+}
+
+/// Advice a child applies to the pages it wrote once the touch is over
+/// (`--discard`): `DontNeed` drops them at once, `Free` lets the kernel
+/// reclaim them lazily, under memory pressure.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DiscardAdvice {
+    DontNeed,
+    Free,
+}
+
+/// NUMA nodes under `--numa-node`: the buffer is bound to `buffer` and the
+/// parent runs on its CPUs; each child runs on `child`'s CPUs and takes its
+/// copies there.
+#[derive(Clone, Copy, Debug)]
+struct NumaNodes {
+    buffer: u32,
+    child: u32,
+}
+
+/// Which pages the parent writes while the children touch theirs.
+/// `Disjoint` gives the children the first half and the parent the second.
+#[derive(Clone, Copy, Debug)]
+enum ParentWrite {
+    Disjoint,
+    Same,
+}
+
+/// How each child is created. `PosixSpawn` starts a fresh copy of this
+/// program, so nothing of the parent's buffer is shared.
+#[derive(Clone, Copy, Debug, Default)]
+enum SpawnKind {
+    #[default]
+    Fork,
+    PosixSpawn,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum OutputFormat {
+    Csv,
+    Json,
+}
+
+/// How the buffer is advised about transparent huge pages. `AlwaysHint`
+/// issues no madvise and relies on the system-wide `always` policy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ThpMode {
+    Never,
+    Madvise,
+    AlwaysHint,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum WriteSize {
+    Byte,
+    FullPage,
+    Bytes(usize),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+enum TouchOrder {
+    #[default]
+    Sequential,
+    Random,
+    Reverse,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum AllocKind {
+    #[default]
+    Heap,
+    Mmap,
+    Hugetlb,
+    /// `MAP_SHARED | MAP_ANONYMOUS`: children write to the parent's pages.
+    Shared,
+    /// A `memfd_create` file mapped `MAP_SHARED`.
+    Memfd,
+    /// A temporary file mapped `MAP_PRIVATE`: the pages start out as the
+    /// file's page cache and are copied on write.
+    File,
+}
+
+/// One buffer setup of `--compare-pages`: the same mapping backed by base
+/// pages, transparent huge pages, or hugetlb pages.
+struct PageVariant {
+    label: &'static str,
+    alloc: AllocKind,
+    thp: Option<ThpMode>,
+}
+
+const PAGE_VARIANTS: [PageVariant; 3] = [
+    PageVariant {
+        label: "base",
+        alloc: AllocKind::Mmap,
+        thp: Some(ThpMode::Never),
+    },
+    PageVariant {
+        label: "thp",
+        alloc: AllocKind::Mmap,
+        thp: Some(ThpMode::Madvise),
+    },
+    PageVariant {
+        label: "hugetlb",
+        alloc: AllocKind::Hugetlb,
+        thp: None,
+    },
+];
+
+/// The children of every run of one size and page variant, averaged.
+struct PageComparison {
+    size_mb: usize,
+    variant: &'static str,
+    page_kb: usize,
+    runs: usize,
+    fork_us: f64,
+    touch_ms: Stats,
+    faults: f64,
+    /// Touch time per fault taken while touching.
+    us_per_fault: f64,
+    private_dirty_kb: f64,
+    /// The child's `AnonHugePages` plus `HugetlbPages` after touching.
+    huge_kb: f64,
+    /// The parent's `AnonHugePages` before forking: whether THP backed the
+    /// buffer at all.
+    parent_anon_huge_kb: f64,
+}
+
+/// The experiment buffer: either a heap `Vec` or an anonymous mapping obtained
+/// directly from the kernel.
+enum Buffer {
+    Heap(Vec<u8>),
+    Mapped { ptr: *mut u8, len: usize },
+}
+
+#[derive(Debug, Default)]
 struct ChildStage {
     stage: String,
     rss_kb: u64,
     private_dirty_kb: u64,
+    shared_dirty_kb: u64,
+    shared_clean_kb: u64,
+    /// Proportional set size: a page shared with the parent counts half, so
+    /// the two processes' Pss add up to the memory they really use.
+    pss_kb: u64,
+    /// Pages of the buffer the child did not inherit (`MADV_DONTFORK`) or
+    /// found zero-filled (`MADV_WIPEONFORK`); only checked under
+    /// `--fork-advice`.
+    missing_pages: u64,
+    zeroed_pages: u64,
+    /// The parent's Private_Dirty, Shared_Dirty, Shared_Clean and Pss, read
+    /// by the child at the same moment, while both processes still map the
+    /// buffer.
+    parent_private_dirty_kb: u64,
+    parent_shared_dirty_kb: u64,
+    parent_shared_clean_kb: u64,
+    parent_pss_kb: u64,
+    anon_huge_kb: u64,
+    hugetlb_kb: u64,
+    locked_kb: u64,
+    /// Anonymous memory swapped out (`VmSwap`).
+    swap_kb: u64,
+    buffer: BufferUsage,
+    /// Physical frames of the buffer compared with the parent's through
+    /// pagemap; `None` when the frames could not be read or compared.
+    frames: Option<FrameSharing>,
+    /// Base pages of the buffer whose soft-dirty bit was set since
+    /// `post_fork` cleared them all (`--soft-dirty`); `None` otherwise.
+    soft_dirty_pages: Option<u64>,
+    minor_faults: u64,
+    major_faults: u64,
+    stat_min_flt: u64,
+    stat_maj_flt: u64,
     touch_ms: f64,
+    /// Whole pages the stage dirtied, in bytes: touched pages times the page
+    /// size, however few bytes of each page were written.
+    touched_bytes: u64,
+    /// The first page write of the touch phase, timed on its own; `None`
+    /// before the touch phase. Its time is part of `touch_ms`.
+    first_write: Option<FirstWrite>,
+    /// Where the buffer's pages were, per NUMA node (`--numa-node`).
+    nodes: NodeUsage,
+    /// When the child started reading this stage's figures.
+    measured_at: Timestamp,
+    /// When the first and the latest page of the touch phase so far were
+    /// written; `None` before the touch phase.
+    touch_started_at: Option<Timestamp>,
+    touch_ended_at: Option<Timestamp>,
+    /// When the parent received the reply ending with this stage; stamped by
+    /// the parent, so never sent, and `None` for runs inside the parent.
+    collected_at: Option<Timestamp>,
 }
 
-#[derive(Debug)]
+/// The very first page write after the fork: the first copy-on-write fault,
+/// which finds nothing of the copy path warm in the caches, against the
+/// steady rate of the writes after it.
+#[derive(Debug, Clone, Copy, Default)]
+struct FirstWrite {
+    ns: u64,
+    minor_faults: u64,
+    major_faults: u64,
+}
+
+/// One moment on both clocks: the monotonic one, which tracers such as perf
+/// and bpftrace stamp their events with, and the wall clock, for logs and
+/// dashboards.
+#[derive(Debug, Clone, Copy, Default)]
+struct Timestamp {
+    monotonic_ns: u64,
+    unix_ns: u64,
+}
+
+/// Buffer pages present in both processes, split by whether they map the same
+/// physical frame.
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameSharing {
+    shared: u64,
+    copied: u64,
+}
+
+/// The process whose figures are read alongside the measured one's.
+#[derive(Clone, Copy)]
+struct Peer {
+    pid: u32,
+    /// Whether the peer maps the buffer at the same addresses, as a fork
+    /// parent or the measuring process itself does, so frames can be compared.
+    same_layout: bool,
+}
+
+#[derive(Debug, Default)]
 struct ExperimentResult {
     size_mb: usize,
+    /// Which of the `--repeat` runs of this size produced the result.
+    run: usize,
+    alloc: AllocKind,
+    touch_order: TouchOrder,
+    stride: usize,
+    write_bytes: usize,
+    thp: Option<ThpMode>,
+    spawn: SpawnKind,
+    /// Generations per fork chain (`--depth`).
+    depth: usize,
+    /// Whether the children's touch stages started together (`--concurrent`).
+    concurrent: bool,
     parent_rss_kb: u64,
-    child_post_fork: ChildStage,
-    child_post_write: ChildStage,
+    parent_private_dirty_kb: u64,
+    /// The parent's RSS and Private_Dirty read again once every child had
+    /// reported writing its pages, before any was waited for: the children's
+    /// copies are theirs, so these match the figures before the fork unless
+    /// the parent wrote too (`--parent-write`).
+    parent_rss_after_kb: u64,
+    parent_private_dirty_after_kb: u64,
+    parent_anon_huge_kb: u64,
+    parent_buffer: BufferUsage,
+    /// Parent faults while allocating and filling the buffer.
+    parent_fill_faults: StatFaults,
+    /// Parent faults from the first fork until every child was reaped; the
+    /// `c*` counters hold the children's totals once they have been waited for.
+    parent_wait_faults: StatFaults,
+    /// System-wide faults over the same span as the two above, from the
+    /// allocation until every child was reaped; `None` without `/proc/vmstat`.
+    vm_faults: Option<VmFaults>,
+    /// Pages whose contents changed in the parent's mapping after the
+    /// children ran: zero unless the buffer is shared.
+    parent_visible_pages: usize,
+    children: Vec<ChildRun>,
+    /// The same workload run by a thread of the parent (`--baseline thread`).
+    thread_baseline: Option<ChildRun>,
+    /// A child that copied the whole buffer into a fresh allocation right
+    /// after its fork (`--baseline eager-copy`); its `touch_ms` is the copy.
+    eager_copy: Option<ChildRun>,
+    /// The `--exec` child, measured around its exec.
+    exec: Option<ExecReport>,
+    verification: Option<Verification>,
+    /// The parent's own writes while the children ran (`--parent-write`);
+    /// its `fork_us` is zero.
+    parent_write: Option<ChildRun>,
+    /// The parent's figures at each checkpoint the children stopped at.
+    parent_checkpoints: Vec<ParentCheckpoint>,
+    fork_advice: Option<ForkAdvice>,
+    discard: Option<DiscardAdvice>,
+    mlock: bool,
+    parent_locked_kb: u64,
+    /// The parent's `VmSwap` before forking.
+    parent_swap_kb: u64,
+    /// What KSM merged in the parent's buffer before forking (`--ksm`).
+    ksm: Option<KsmReport>,
+    /// Memory pressure events under `--cgroup-limit`.
+    cgroup: Option<CgroupReport>,
+    numa: Option<NumaNodes>,
+    /// The parent's buffer per NUMA node before forking (`--numa-node`).
+    parent_nodes: NodeUsage,
+}
+
+/// One pass of `run_experiment`: a discarded warmup or a measured run, each
+/// with its zero-based index.
+#[derive(Clone, Copy, Debug)]
+enum Iteration {
+    Warmup(usize),
+    Measured(usize),
+}
+
+/// Mean, sample standard deviation and range of one metric over repeated
+/// runs.
+#[derive(Debug, Clone, Copy)]
+struct Stats {
+    mean: f64,
+    stddev: f64,
+    min: f64,
+    max: f64,
+}
+
+/// The `--repeat` runs of one size, aggregated over every child of every run.
+#[derive(Debug)]
+struct SizeSummary {
+    size_mb: usize,
+    runs: usize,
+    metrics: Vec<(&'static str, Stats)>,
+}
+
+/// A least-squares line `y = slope * x + intercept` and how much of the
+/// variance of `y` it explains.
+#[derive(Debug, Clone, Copy)]
+struct LinearFit {
+    slope: f64,
+    intercept: f64,
+    r_squared: f64,
+    points: usize,
+}
+
+/// A figure of every child fitted against the buffer size, so the cost of a
+/// copy can be stated per MB instead of read off the rows.
+#[derive(Debug)]
+struct ScalingFit {
+    metric: &'static str,
+    /// Unit of the metric, and so of the intercept; the slope is per MB.
+    unit: &'static str,
+    fit: LinearFit,
+}
+
+/// The kernel's overcommit policy (`vm.overcommit_*`) and how much of the
+/// commit limit is in use, from `/proc/sys/vm` and `/proc/meminfo`.
+#[derive(Debug, Clone, Copy)]
+struct Overcommit {
+    /// 0 heuristic, 1 always, 2 never (strict accounting).
+    mode: u8,
+    /// Percent of RAM counted toward `CommitLimit` under mode 2, unless
+    /// `kbytes` is set instead.
+    ratio: u64,
+    kbytes: u64,
+    commit_limit_kb: u64,
+    committed_kb: u64,
+}
+
+/// KSM counters once ksmd has scanned the registered buffer.
+#[derive(Debug, Clone, Copy)]
+struct KsmReport {
+    wait_ms: f64,
+    /// Pages of this process backed by a KSM page
+    /// (`/proc/<pid>/ksm_merging_pages`).
+    merging_pages: u64,
+    /// System-wide: KSM pages in use, and the extra mappings of them, which is
+    /// the number of pages saved.
+    pages_shared: u64,
+    pages_sharing: u64,
+}
+
+/// How often the `--cgroup-limit` group hit its limits during one
+/// experiment: the increase of each `memory.events` counter.
+#[derive(Debug, Clone, Copy, Default)]
+struct CgroupReport {
+    limit_mb: usize,
+    /// Reclaimed despite `memory.low` protection.
+    low: u64,
+    /// Throttled and reclaimed for exceeding `memory.high`.
+    high: u64,
+    /// Reached `memory.max`, forcing reclaim (or swap-out) to continue.
+    max: u64,
+    /// Reclaim at the limit failed and the OOM killer was invoked.
+    oom: u64,
+    /// Processes the OOM killer killed.
+    oom_kill: u64,
+}
+
+/// Both reports from one forked child.
+#[derive(Debug, Default)]
+struct ChildRun {
+    /// Time spent inside the parent's `fork()` call, in microseconds.
+    fork_us: f64,
+    /// Just before the parent forked (or spawned) it; `None` for later
+    /// generations and the parent's own writes.
+    forked_at: Option<Timestamp>,
+    /// Position in its fork chain: 1 for a child of the parent, 2 for the
+    /// child that child forked under `--depth`, and so on; 0 for runs inside
+    /// the parent.
+    generation: usize,
+    post_fork: ChildStage,
+    /// After reading every page of the plan but writing none
+    /// (`--read-stage`); its `touch_ms` is the time spent reading.
+    post_read: Option<ChildStage>,
+    /// Halfway through the touch phase; only children stepped through the
+    /// checkpoints report it.
+    mid_touch: Option<ChildStage>,
+    post_write: ChildStage,
+    /// Right after the child applied `--discard` to the pages it wrote.
+    post_discard: Option<ChildStage>,
+    /// What the discard did and what reading the pages back showed.
+    discard: Option<DiscardReport>,
+    /// Measurements between the slices of the touch phase (`--rounds`);
+    /// empty when it ran in one go.
+    rounds: Vec<Round>,
+    /// Per-page write times of the touch phase (`--latency`).
+    latency: Option<WriteLatency>,
+    /// The parent's readings of a first-generation child's Private_Dirty as
+    /// it reported its progress (`--dirty-series`).
+    dirty_series: Vec<DirtySample>,
+    /// Checksum of the buffer before the child wrote to it (`--verify`).
+    checksum: Option<u64>,
+    /// How the child process ended; `None` for runs inside the parent.
+    exit: Option<ExitStatus>,
+    /// Measurements that failed and were reported as zero, each prefixed
+    /// with the stage it belongs to.
+    errors: Vec<String>,
+}
+
+/// A child's written pages after `--discard`, as read back once the stage's
+/// figures were taken: still holding the child's write, reading as zero
+/// (the private copy and the data with it are gone), or showing anything
+/// else, such as the file's contents under a private file mapping.
+#[derive(Debug, Clone, Copy)]
+struct DiscardReport {
+    advice: DiscardAdvice,
+    /// Bytes the advice covered: every whole page a write landed in.
+    discarded_bytes: u64,
+    /// LazyFree right after the advice.
+    lazy_free_kb: u64,
+    kept_pages: u64,
+    zeroed_pages: u64,
+    other_pages: u64,
+    /// Faults reading the pages back took, and the figures after it.
+    reread_faults: u64,
+    reread_rss_kb: u64,
+    reread_private_dirty_kb: u64,
+}
+
+/// What one pass over the plan's pages cost: the faults it took, how much
+/// the buffer's Private_Dirty grew, which is what it copied, and its time.
+#[derive(Debug, Clone, Copy)]
+struct PassCost {
+    faults: u64,
+    copied_kb: u64,
+    ms: f64,
+}
+
+/// Memory use after one slice of the touch phase. Counts are cumulative, so
+/// a run of rounds forms a staircase from the post-fork to the post-write
+/// figures.
+#[derive(Debug)]
+struct Round {
+    pages_touched: usize,
+    rss_kb: u64,
+    private_dirty_kb: u64,
+    buffer_private_dirty_kb: u64,
+    /// Time spent touching so far, excluding the measurements in between.
+    touch_ms: f64,
+}
+
+/// One reading of `--dirty-series`: the child's Private_Dirty, taken by the
+/// parent while the child waited after writing `pages_touched` pages.
+#[derive(Debug, Clone, Copy)]
+struct DirtySample {
+    pages_touched: usize,
+    private_dirty_kb: u64,
+    buffer_private_dirty_kb: u64,
+    /// Since the child's first report, pauses included.
+    elapsed_ms: f64,
+}
+
+/// The child's ends of its `--dirty-series` pipes: it writes the pages
+/// touched so far to `report`, then waits for a byte on `ack`.
+#[derive(Clone, Copy)]
+struct SeriesPipe<'a> {
+    report: BorrowedFd<'a>,
+    ack: BorrowedFd<'a>,
+}
+
+/// Sent instead of a page count once every page is written.
+const SERIES_DONE: u64 = u64::MAX;
+
+impl SeriesPipe<'_> {
+    /// Reports `pages_touched` and waits until the parent has measured.
+    fn report(self, pages_touched: usize) -> io::Result<()> {
+        self.send(pages_touched as u64)?;
+        match sys::read_fd(self.ack, &mut [0u8; 1])? {
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tells the parent the series is over. Closing the pipe would not:
+    /// later generations hold copies of it.
+    fn finish(self) {
+        let _ = self.send(SERIES_DONE);
+    }
+
+    fn send(self, value: u64) -> io::Result<()> {
+        let message = value.to_ne_bytes();
+        if sys::write_fd(self.report, &message)? != message.len() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "short write"));
+        }
+        Ok(())
+    }
+}
+
+/// The parent's side of a child's `--dirty-series`: a thread that answers
+/// each progress report with a reading of the child's memory, until the
+/// child says it is done or exits.
+struct DirtySampler {
+    handle: JoinHandle<Vec<DirtySample>>,
+}
+
+impl DirtySampler {
+    /// Samples `pid`, whose copy of the buffer spans `buffer`, reading its
+    /// reports from `report` and acknowledging on `ack`; takes both.
+    fn start(pid: u32, buffer: &[u8], report: OwnedFd, ack: OwnedFd) -> DirtySampler {
+        let start = buffer.as_ptr() as usize;
+        let end = start + buffer.len();
+        let handle = std::thread::spawn(move || {
+            let mut samples = Vec::new();
+            let mut first = None;
+            loop {
+                let mut message = [0u8; 8];
+                let mut filled = 0;
+                while filled < message.len() {
+                    match sys::read_fd(report.as_fd(), &mut message[filled..]) {
+                        Ok(0) | Err(_) => break,
+                        Ok(count) => filled += count,
+                    }
+                }
+                if filled < message.len() || u64::from_ne_bytes(message) == SERIES_DONE {
+                    break;
+                }
+                let received = Instant::now();
+                let first = *first.get_or_insert(received);
+                samples.push(DirtySample {
+                    pages_touched: u64::from_ne_bytes(message) as usize,
+                    private_dirty_kb: read_private_dirty_kb(pid).unwrap_or_default(),
+                    buffer_private_dirty_kb: buffer_usage(&Procfs, pid, start, end)
+                        .unwrap_or_default()
+                        .private_dirty_kb,
+                    elapsed_ms: (received - first).as_secs_f64() * 1000.0,
+                });
+                if sys::write_fd(ack.as_fd(), &[1]).is_err() {
+                    break;
+                }
+            }
+            samples
+        });
+        DirtySampler { handle }
+    }
+
+    /// Waits for the child to finish its series, which it does once it has
+    /// written every page, or to exit.
+    fn finish(self) -> Vec<DirtySample> {
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// Reports on stderr how far a long fill or touch has got, so multi-gigabyte
+/// runs do not look hung. Silent for buffers under `PROGRESS_MIN_BYTES`.
+struct Progress {
+    label: String,
+    /// Units (bytes or pages) in the whole job.
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(label: String, total: usize, buffer_bytes: usize) -> Self {
+        Progress {
+            label,
+            total,
+            enabled: buffer_bytes >= PROGRESS_MIN_BYTES && total > 0,
+        }
+    }
+
+    /// Units between two reports; the whole job when disabled, so callers
+    /// can always work in steps of this size.
+    fn step(&self) -> usize {
+        if self.enabled {
+            self.total.div_ceil(100 / PROGRESS_STEP_PERCENT).max(1)
+        } else {
+            self.total.max(1)
+        }
+    }
+
+    /// Reports `done` units if that ends a step or the job.
+    fn update(&self, done: usize) {
+        if self.enabled && (done.is_multiple_of(self.step()) || done == self.total) {
+            // One write per line, so children reporting at once do not
+            // interleave mid-line.
+            let line = format!("{}: {}%\n", self.label, done * 100 / self.total);
+            let _ = io::stderr().write_all(line.as_bytes());
+        }
+    }
+}
+
+/// The `--stream` output: one JSON object per line, flushed as soon as it is
+/// written so a run that dies part-way keeps everything before it. Every
+/// line has a `type`: `metadata` first, then `experiment` or `error` per
+/// measured run, and `checkpoint` under `--stream-samples`.
+struct JsonLines {
+    /// `None` once a write has failed; the rest of the run goes on without.
+    out: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+/// The `--tui` panel: RSS and Private_Dirty gauges for the parent and each
+/// first-generation child, redrawn by a sampling thread in the top rows of
+/// the terminal while the narration scrolls in the rows below. Dropping it
+/// draws a last frame and gives the whole terminal back to the narration.
+struct Tui {
+    /// Label and pid of every process shown, parent first.
+    watched: Arc<Mutex<Vec<(String, u32)>>>,
+    stop: Arc<AtomicBool>,
+    sampler: Option<JoinHandle<()>>,
+    rows: u16,
+}
+
+impl JsonLines {
+    /// `-` is `stdout`, the real standard output even under `--quiet`.
+    fn open(path: &Path, stdout: Option<&File>) -> io::Result<JsonLines> {
+        let out: Box<dyn Write + Send> = if path != Path::new("-") {
+            Box::new(File::create(path)?)
+        } else if let Some(stdout) = stdout {
+            Box::new(stdout.try_clone()?)
+        } else {
+            Box::new(io::stdout())
+        };
+        Ok(JsonLines {
+            out: Mutex::new(Some(out)),
+        })
+    }
+
+    /// Writes `kind` with `fields`, the rest of a JSON object after its
+    /// opening brace.
+    fn emit(&self, kind: &str, fields: &str) {
+        let mut out = self
+            .out
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(writer) = out.as_mut() else {
+            return;
+        };
+        let line = format!("{{\"type\":{},{fields}\n", json_string(kind));
+        if let Err(err) = writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.flush())
+        {
+            eprintln!("Warning: writing the JSON-lines stream failed, so it stops here: {err}");
+            *out = None;
+        }
+    }
+}
+
+impl Tui {
+    /// Reserves the top rows for `title` and the parent plus `children`
+    /// gauges; `None` if stdout's terminal size is unknown.
+    fn start(title: String, size_kb: u64, parent_pid: u32, children: usize) -> Option<Tui> {
+        let (rows, cols) = sys::terminal_size(io::stdout().as_fd()).ok()?;
+        // Leave at least half the terminal to the narration.
+        let shown = (1 + children).min((rows as usize / 2).saturating_sub(2).max(1));
+        let height = shown + 2;
+        let watched = Arc::new(Mutex::new(vec![("Parent".to_string(), parent_pid)]));
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut out = io::stdout().lock();
+        // Scroll only below the panel, and write from the bottom row on.
+        let _ = write!(out, "\x1b[{};{rows}r\x1b[{rows};1H", height + 1);
+        let _ = out.flush();
+        drop(out);
+        let sampler = {
+            let (watched, stop) = (Arc::clone(&watched), Arc::clone(&stop));
+            std::thread::spawn(move || loop {
+                let done = stop.load(Ordering::Acquire);
+                let frame = {
+                    let watched = watched.lock().unwrap_or_else(|e| e.into_inner());
+                    tui_frame(&title, size_kb, &watched, shown, cols as usize)
+                };
+                // Under the stdout lock, so a frame never lands in the middle
+                // of a narration line. Children never print to stdout, so
+                // forking while this thread holds the lock is harmless.
+                let mut out = io::stdout().lock();
+                let _ = out.write_all(frame.as_bytes());
+                let _ = out.flush();
+                drop(out);
+                if done {
+                    break;
+                }
+                std::thread::sleep(TUI_INTERVAL);
+            })
+        };
+        Some(Tui {
+            watched,
+            stop,
+            sampler: Some(sampler),
+            rows,
+        })
+    }
+
+    fn watch(&self, label: String, pid: u32) {
+        self.watched
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((label, pid));
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(sampler) = self.sampler.take() {
+            let _ = sampler.join();
+        }
+        // Resetting the scroll region homes the cursor; put it back below.
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "\x1b[r\x1b[{};1H", self.rows);
+        let _ = out.flush();
+    }
+}
+
+/// One redraw of the panel: the title, `shown` rows of gauges (the last
+/// counting the processes left out if they do not all fit), and a rule. Bars
+/// are scaled to the buffer size, or to the largest figure if a process has
+/// grown past it.
+fn tui_frame(
+    title: &str,
+    size_kb: u64,
+    watched: &[(String, u32)],
+    shown: usize,
+    cols: usize,
+) -> String {
+    let fits = if watched.len() > shown {
+        shown - 1
+    } else {
+        watched.len()
+    };
+    let figures: Vec<Option<(u64, u64)>> = watched
+        .iter()
+        .take(fits)
+        .map(|&(_, pid)| {
+            let rss = read_rss_kb(pid).ok()?;
+            Some((rss, read_private_dirty_kb(pid).unwrap_or(0)))
+        })
+        .collect();
+    let full = figures
+        .iter()
+        .flatten()
+        .map(|&(rss, _)| rss)
+        .fold(size_kb, u64::max)
+        .max(1);
+    // Everything on a gauge line but its two bars.
+    let width = cols.saturating_sub(62) / 2;
+    let bar = |kb: u64| {
+        let filled = (kb.min(full) * width as u64 / full) as usize;
+        format!("[{}{}]", "#".repeat(filled), ".".repeat(width - filled))
+    };
+    let mut lines = vec![title.to_string()];
+    for ((label, pid), figures) in watched.iter().zip(&figures) {
+        lines.push(match figures {
+            Some((rss, dirty)) => format!(
+                "{label:<10} RSS {} {rss:>9} kB   Private_Dirty {} {dirty:>9} kB",
+                bar(*rss),
+                bar(*dirty)
+            ),
+            None => format!("{label:<10} (pid {pid} has exited)"),
+        });
+    }
+    if fits < watched.len() {
+        lines.push(format!("... and {} more", watched.len() - fits));
+    }
+    lines.resize(shown + 1, String::new());
+    lines.push("-".repeat(cols));
+    // Save the cursor, redraw each row, and restore it.
+    let mut frame = String::from("\x1b7");
+    for (row, line) in lines.iter().enumerate() {
+        let line: String = line.chars().take(cols).collect();
+        frame.push_str(&format!("\x1b[{};1H\x1b[2K{line}", row + 1));
+    }
+    frame.push_str("\x1b8");
+    frame
+}
+
+/// Log2 buckets of a latency histogram: bucket `i` counts writes that took
+/// from `2^i` up to `2^(i+1)` nanoseconds (bucket 0 also those under 1 ns).
+const LATENCY_BUCKETS: usize = 32;
+
+/// The distribution of one kind of page write's duration.
+#[derive(Debug, Clone, Copy, Default)]
+struct LatencyStats {
+    buckets: [u64; LATENCY_BUCKETS],
+    p50_ns: u64,
+    p90_ns: u64,
+    p99_ns: u64,
+    max_ns: u64,
+}
+
+/// How long each page write of a touch stage took: the first write to a page,
+/// which takes the copy-on-write fault, against a second write of the same
+/// bytes once the page is private.
+#[derive(Debug, Clone, Copy, Default)]
+struct WriteLatency {
+    first: LatencyStats,
+    rewrite: LatencyStats,
+}
+
+/// The moments the parent asks each child to measure at, in order. A child
+/// waits at each one until asked to go on, so the parent can read its own
+/// figures while every child is stopped at the same point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Checkpoint {
+    PostFork,
+    /// After reading the plan's pages without writing any (`--read-stage`).
+    PostRead,
+    MidTouch,
+    PostWrite,
+    /// After discarding the written pages (`--discard`).
+    PostDiscard,
+}
+
+/// The parent's own figures at one checkpoint.
+#[derive(Debug, Clone, Copy)]
+struct ParentCheckpoint {
+    checkpoint: Checkpoint,
+    rss_kb: u64,
+    private_dirty_kb: u64,
+    shared_dirty_kb: u64,
+    swap_kb: u64,
+    buffer: BufferUsage,
+}
+
+/// What a touch stage writes: which pages, in what order, how many bytes of
+/// each, in how many measured slices, whether each write is timed, and
+/// whether written pages are tracked through their soft-dirty bits.
+#[derive(Clone, Copy)]
+struct TouchPlan<'a> {
+    page: usize,
+    order: &'a [usize],
+    write_bytes: usize,
+    rounds: usize,
+    latency: bool,
+    soft_dirty: bool,
+    /// Under `--concurrent`, where the touch stages wait for every child.
+    gate: Option<StartGate<'a>>,
+    /// Checksum the buffer after the post-fork measurements (`--verify`).
+    verify: bool,
+    /// Under `--numa-node`, the node a forked child moves to and takes its
+    /// copies on; every stage then counts the buffer's pages per node.
+    numa: Option<u32>,
+    /// Under `--dirty-series`, where a first-generation child reports its
+    /// progress while touching.
+    series: Option<SeriesPipe<'a>>,
+    /// Under `--discard`, the advice applied to the written pages at
+    /// `Checkpoint::PostDiscard`.
+    discard: Option<DiscardAdvice>,
+}
+
+/// A barrier in memory shared with every child (`--concurrent`). Each touch
+/// stage starts once all of them have arrived, so they write at once instead
+/// of in the order the parent asked them.
+#[derive(Clone, Copy)]
+struct StartGate<'a> {
+    arrivals: &'a AtomicUsize,
+    /// Processes that pass the gate.
+    total: usize,
+}
+
+impl<'a> StartGate<'a> {
+    /// A gate for `total` processes in `memory`, which must be a page-aligned
+    /// `MAP_SHARED` mapping created before they were forked.
+    fn new(memory: &'a mut [u8], total: usize) -> Self {
+        assert!(memory.len() >= std::mem::size_of::<AtomicUsize>());
+        memory.fill(0);
+        StartGate {
+            // SAFETY: the mapping is page-aligned and outlives the borrow,
+            // and it is only ever accessed through this atomic.
+            arrivals: unsafe { AtomicUsize::from_ptr(memory.as_mut_ptr().cast()) },
+            total,
+        }
+    }
+
+    /// Arrives at the gate for the `pass`-th time (from 1) and spins until
+    /// every process has, or until `GATE_TIMEOUT`.
+    fn wait(self, pass: usize) -> Result<(), Error> {
+        self.arrivals.fetch_add(1, Ordering::AcqRel);
+        let start = Instant::now();
+        while self.arrivals.load(Ordering::Acquire) < self.total * pass {
+            if start.elapsed() > GATE_TIMEOUT {
+                return Err(Error::Child(format!(
+                    "only {} of {} processes reached the start gate within {} s",
+                    self.arrivals.load(Ordering::Acquire) - self.total * (pass - 1),
+                    self.total,
+                    GATE_TIMEOUT.as_secs()
+                )));
+            }
+            std::hint::spin_loop();
+        }
+        Ok(())
+    }
 }
 
-fn parse_args() -> Result<Config, String> {
+fn parse_args() -> Result<Config, Error> {
     let mut sizes: Option<Vec<usize>> = None;
     let mut output: Option<PathBuf> = None;
+    let mut plot: Option<PathBuf> = None;
+    let mut report: Option<PathBuf> = None;
+    let mut sqlite: Option<PathBuf> = None;
+    let mut stream: Option<PathBuf> = None;
+    let mut stream_samples = false;
+    let mut quiet = false;
+    let mut dirty_series = false;
+    let mut machine = false;
+    let mut format = OutputFormat::Csv;
+    let mut append = false;
+    let mut latency = false;
+    let mut soft_dirty = false;
+    let mut alloc = AllocKind::Heap;
+    let mut touch_order = TouchOrder::Sequential;
+    let mut seed = 42;
+    let mut write_size = WriteSize::Byte;
+    let mut thp = None;
+    let mut children = 1;
+    let mut depth = 1;
+    let mut concurrent = false;
+    let mut read_stage = false;
+    let mut verify = false;
+    let mut repeat = 1;
+    let mut stride = 1;
+    let mut rounds = 1;
+    let mut warmup = 0;
+    let mut child_timeout = None;
+    let mut spawn = SpawnKind::Fork;
+    let mut spawn_helper = None;
+    let mut exec = false;
+    let mut exec_helper = None;
+    let mut thread_baseline = false;
+    let mut eager_copy = false;
+    let mut parent_write = None;
+    let mut fork_advice = None;
+    let mut discard = None;
+    let mut mlock = false;
+    let mut ksm = false;
+    let mut numa = None;
+    let mut max_memory_mb = None;
+    let mut cgroup_limit_mb = None;
+    let mut compare_pages = false;
+    let mut fs_cow = None;
+    let mut check = false;
+    let mut tui = false;
+    let mut bench_touch = false;
+    let mut overcommit = false;
 
     let mut it = env::args().skip(1);
     while let Some(arg) = it.next() {
@@ -53,329 +1205,7140 @@ fn parse_args() -> Result<Config, String> {
             "--sizes" => {
                 let value = it
                     .next()
-                    .ok_or_else(|| "--sizes requires a value".to_string())?;
+                    .ok_or_else(|| Error::Usage("--sizes requires a value".into()))?;
                 let mut parsed = Vec::new();
                 for chunk in value.split(',') {
                     if chunk.trim().is_empty() {
                         continue;
                     }
-                    let mb: usize = chunk
-                        .trim()
-                        .parse()
-                        .map_err(|_| format!("invalid size: {}", chunk))?;
-                    if mb < 16 {
-                        return Err("each size must be at least 16 MB".into());
-                    }
-                    parsed.push(mb);
+                    parsed.push(parse_size_mb(chunk.trim())?);
                 }
                 if parsed.is_empty() {
-                    return Err("no valid sizes provided".into());
+                    return Err(Error::Usage("no valid sizes provided".into()));
                 }
                 sizes = Some(parsed);
             }
             "--output" => {
                 let value = it
                     .next()
-                    .ok_or_else(|| "--output requires a path".to_string())?;
+                    .ok_or_else(|| Error::Usage("--output requires a path".into()))?;
                 output = Some(PathBuf::from(value));
             }
-            "--help" | "-h" => {
-                print_usage();
-                std::process::exit(0);
+            "--plot" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--plot requires a path".into()))?;
+                plot = Some(PathBuf::from(value));
             }
-            other => return Err(format!("unknown argument: {}", other)),
-        }
-    }
+            "--fs-cow" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--fs-cow requires a directory".into()))?;
+                fs_cow = Some(PathBuf::from(value));
+            }
+            "--report" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--report requires a path".into()))?;
+                report = Some(PathBuf::from(value));
+            }
+            "--sqlite" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--sqlite requires a path".into()))?;
+                sqlite = Some(PathBuf::from(value));
+            }
+            "--stream" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--stream requires a path or -".into()))?;
+                stream = Some(PathBuf::from(value));
+            }
+            "--stream-samples" => stream_samples = true,
+            "--quiet" => quiet = true,
+            "--dirty-series" => dirty_series = true,
+            "--machine" => machine = true,
+            "--format" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--format requires a value".into()))?;
+                format = match value.as_str() {
+                    "csv" => OutputFormat::Csv,
+                    "json" => OutputFormat::Json,
+                    other => return Err(Error::Usage(format!("unknown output format: {other}"))),
+                };
+            }
+            "--alloc" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--alloc requires a value".into()))?;
+                alloc = match value.as_str() {
+                    "heap" => AllocKind::Heap,
+                    "mmap" => AllocKind::Mmap,
+                    "hugetlb" => AllocKind::Hugetlb,
+                    "shared" => AllocKind::Shared,
+                    "memfd" => AllocKind::Memfd,
+                    "file" => AllocKind::File,
+                    other => return Err(Error::Usage(format!("unknown allocator: {other}"))),
+                };
+            }
+            "--touch-order" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--touch-order requires a value".into()))?;
+                touch_order = match value.as_str() {
+                    "sequential" => TouchOrder::Sequential,
+                    "random" => TouchOrder::Random,
+                    "reverse" => TouchOrder::Reverse,
+                    other => return Err(Error::Usage(format!("unknown touch order: {other}"))),
+                };
+            }
+            "--stride" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--stride requires a value".into()))?;
+                stride = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid stride: {value}")))?;
+                if stride == 0 {
+                    return Err(Error::Usage("--stride must be at least 1".into()));
+                }
+            }
+            "--rounds" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--rounds requires a value".into()))?;
+                rounds = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid round count: {value}")))?;
+                if rounds == 0 {
+                    return Err(Error::Usage("--rounds must be at least 1".into()));
+                }
+            }
+            "--seed" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--seed requires a value".into()))?;
+                seed = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid seed: {value}")))?;
+            }
+            "--write-size" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--write-size requires a value".into()))?;
+                write_size = match value.as_str() {
+                    "byte" => WriteSize::Byte,
+                    "full-page" => WriteSize::FullPage,
+                    other => {
+                        let bytes: usize = other
+                            .parse()
+                            .map_err(|_| Error::Usage(format!("invalid write size: {other}")))?;
+                        if bytes == 0 {
+                            return Err(Error::Usage("write size must be at least 1 byte".into()));
+                        }
+                        WriteSize::Bytes(bytes)
+                    }
+                };
+            }
+            "--thp" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--thp requires a value".into()))?;
+                thp = Some(match value.as_str() {
+                    "never" => ThpMode::Never,
+                    "madvise" => ThpMode::Madvise,
+                    "always-hint" => ThpMode::AlwaysHint,
+                    other => return Err(Error::Usage(format!("unknown THP mode: {other}"))),
+                });
+            }
+            "--hugetlb" => alloc = AllocKind::Hugetlb,
+            "--children" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--children requires a value".into()))?;
+                children = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid child count: {value}")))?;
+                if children == 0 {
+                    return Err(Error::Usage("--children must be at least 1".into()));
+                }
+            }
+            "--depth" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--depth requires a value".into()))?;
+                depth = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid depth: {value}")))?;
+                if depth == 0 {
+                    return Err(Error::Usage("--depth must be at least 1".into()));
+                }
+            }
+            "--max-memory" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--max-memory requires a size".into()))?;
+                max_memory_mb = Some(parse_size_mb(&value)?);
+            }
+            "--numa-node" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--numa-node requires a node".into()))?;
+                let node = |text: &str| {
+                    text.parse::<u32>()
+                        .map_err(|_| Error::Usage(format!("invalid NUMA node: {text}")))
+                };
+                let (buffer, child) = match value.split_once(',') {
+                    Some((buffer, child)) => (node(buffer)?, node(child)?),
+                    None => (node(&value)?, node(&value)?),
+                };
+                numa = Some(NumaNodes { buffer, child });
+            }
+            "--cgroup-limit" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--cgroup-limit requires a size in MB".into()))?;
+                let mb: usize = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid cgroup limit: {value}")))?;
+                if mb == 0 {
+                    return Err(Error::Usage("--cgroup-limit must be at least 1 MB".into()));
+                }
+                cgroup_limit_mb = Some(mb);
+            }
+            "--repeat" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--repeat requires a value".into()))?;
+                repeat = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid repeat count: {value}")))?;
+                if repeat == 0 {
+                    return Err(Error::Usage("--repeat must be at least 1".into()));
+                }
+            }
+            "--warmup" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--warmup requires a value".into()))?;
+                warmup = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid warmup count: {value}")))?;
+            }
+            "--child-timeout" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--child-timeout requires a value".into()))?;
+                let seconds: f64 = value
+                    .parse()
+                    .map_err(|_| Error::Usage(format!("invalid child timeout: {value}")))?;
+                if !(seconds > 0.0 && seconds.is_finite()) {
+                    return Err(Error::Usage(
+                        "--child-timeout must be a positive number of seconds".into(),
+                    ));
+                }
+                child_timeout = Some(Duration::from_secs_f64(seconds));
+            }
+            "--spawn" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--spawn requires a value".into()))?;
+                spawn = match value.as_str() {
+                    "fork" => SpawnKind::Fork,
+                    "posix-spawn" => SpawnKind::PosixSpawn,
+                    other => return Err(Error::Usage(format!("unknown spawn method: {other}"))),
+                };
+            }
+            "--baseline" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--baseline requires a value".into()))?;
+                match value.as_str() {
+                    "thread" => thread_baseline = true,
+                    "eager-copy" => eager_copy = true,
+                    other => return Err(Error::Usage(format!("unknown baseline: {other}"))),
+                }
+            }
+            "--parent-write" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--parent-write requires a value".into()))?;
+                parent_write = Some(match value.as_str() {
+                    "disjoint" => ParentWrite::Disjoint,
+                    "same" => ParentWrite::Same,
+                    other => {
+                        return Err(Error::Usage(format!("unknown parent write mode: {other}")))
+                    }
+                });
+            }
+            "--fork-advice" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--fork-advice requires a value".into()))?;
+                fork_advice = Some(match value.as_str() {
+                    "dontfork" => ForkAdvice::DontFork,
+                    "wipeonfork" => ForkAdvice::WipeOnFork,
+                    other => return Err(Error::Usage(format!("unknown fork advice: {other}"))),
+                });
+            }
+            "--discard" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--discard requires a value".into()))?;
+                discard = Some(match value.as_str() {
+                    "dontneed" => DiscardAdvice::DontNeed,
+                    "free" => DiscardAdvice::Free,
+                    other => return Err(Error::Usage(format!("unknown discard advice: {other}"))),
+                });
+            }
+            "--append" => append = true,
+            "--latency" => latency = true,
+            "--soft-dirty" => soft_dirty = true,
+            "--mlock" => mlock = true,
+            "--ksm" => ksm = true,
+            "--concurrent" => concurrent = true,
+            "--read-stage" => read_stage = true,
+            "--verify" => verify = true,
+            "--check" => check = true,
+            "--tui" => tui = true,
+            "--bench-touch" => bench_touch = true,
+            "--overcommit" => overcommit = true,
+            "--compare-pages" => compare_pages = true,
+            "--spawn-helper" => {
+                let value = it.next().ok_or_else(|| {
+                    Error::Usage("--spawn-helper requires two descriptors".into())
+                })?;
+                spawn_helper = Some(parse_descriptors(&value)?);
+            }
+            "--exec" => exec = true,
+            "--exec-helper" => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| Error::Usage("--exec-helper requires two descriptors".into()))?;
+                exec_helper = Some(parse_descriptors(&value)?);
+            }
+            "--help" | "-h" => {
+                print_usage();
+                std::process::exit(0);
+            }
+            other => return Err(Error::Usage(format!("unknown argument: {}", other))),
+        }
+    }
+
+    if append && (output.is_none() || matches!(format, OutputFormat::Json)) {
+        return Err(Error::Usage(
+            "--append needs --output with CSV format".into(),
+        ));
+    }
+    if parent_write.is_some() && matches!(spawn, SpawnKind::PosixSpawn) {
+        return Err(Error::Usage(
+            "--parent-write needs forked children that share the buffer".into(),
+        ));
+    }
+    if dirty_series && matches!(spawn, SpawnKind::PosixSpawn) {
+        return Err(Error::Usage(
+            "--dirty-series needs forked children that inherit its pipes".into(),
+        ));
+    }
+    if depth > 1 && matches!(spawn, SpawnKind::PosixSpawn) {
+        return Err(Error::Usage("--depth needs forked children".into()));
+    }
+    if concurrent && matches!(spawn, SpawnKind::PosixSpawn) {
+        return Err(Error::Usage(
+            "--concurrent needs forked children that share its start gate".into(),
+        ));
+    }
+    if concurrent && children * depth < 2 {
+        return Err(Error::Usage(
+            "--concurrent needs --children or --depth of 2 or more".into(),
+        ));
+    }
+    if verify && fork_advice.is_some() {
+        return Err(Error::Usage(
+            "--verify needs the whole buffer inherited, without --fork-advice".into(),
+        ));
+    }
+    if eager_copy && fork_advice.is_some() {
+        return Err(Error::Usage(
+            "--baseline eager-copy needs the whole buffer inherited, without --fork-advice".into(),
+        ));
+    }
+    if fork_advice.is_some() && matches!(spawn, SpawnKind::PosixSpawn) {
+        return Err(Error::Usage(
+            "--fork-advice only affects forked children".into(),
+        ));
+    }
+    if matches!(fork_advice, Some(ForkAdvice::WipeOnFork))
+        && !matches!(alloc, AllocKind::Heap | AllocKind::Mmap)
+    {
+        return Err(Error::Usage(
+            "MADV_WIPEONFORK needs a private anonymous buffer (heap or mmap)".into(),
+        ));
+    }
+    if discard == Some(DiscardAdvice::Free) && !matches!(alloc, AllocKind::Heap | AllocKind::Mmap) {
+        return Err(Error::Usage(
+            "MADV_FREE needs a private anonymous buffer (heap or mmap)".into(),
+        ));
+    }
+    if ksm && !matches!(alloc, AllocKind::Heap | AllocKind::Mmap) {
+        return Err(Error::Usage(
+            "KSM only merges private anonymous buffers (heap or mmap)".into(),
+        ));
+    }
+    if cfg!(not(target_os = "linux"))
+        && (matches!(alloc, AllocKind::Hugetlb | AllocKind::Memfd)
+            || thp.is_some()
+            || fork_advice.is_some()
+            || ksm
+            || numa.is_some()
+            || overcommit
+            || cgroup_limit_mb.is_some()
+            || soft_dirty
+            || compare_pages
+            || fs_cow.is_some())
+    {
+        return Err(Error::Usage(
+            "--alloc hugetlb|memfd, --thp, --fork-advice, --ksm, --numa-node, --overcommit, \
+--cgroup-limit, --soft-dirty, --compare-pages and --fs-cow need Linux"
+                .into(),
+        ));
+    }
+    if let Some(nodes) = numa {
+        if alloc.is_shared() {
+            return Err(Error::Usage(
+                "--numa-node places copies, which a shared buffer never makes".into(),
+            ));
+        }
+        if matches!(spawn, SpawnKind::PosixSpawn) {
+            return Err(Error::Usage(
+                "--numa-node needs forked children that inherit the buffer".into(),
+            ));
+        }
+        for node in [nodes.buffer, nodes.child] {
+            if cfg!(target_os = "linux") && node_cpus(node).is_err() {
+                return Err(Error::Usage(format!(
+                    "NUMA node {node} does not exist or has no CPUs (see /sys/devices/system/node)"
+                )));
+            }
+        }
+    }
+    if compare_pages && (!matches!(alloc, AllocKind::Heap) || thp.is_some()) {
+        return Err(Error::Usage(
+            "--compare-pages chooses the allocator and THP mode itself".into(),
+        ));
+    }
+    if compare_pages && (ksm || matches!(fork_advice, Some(ForkAdvice::WipeOnFork))) {
+        return Err(Error::Usage(
+            "--compare-pages cannot be combined with --ksm or --fork-advice wipeonfork, \
+which need base pages"
+                .into(),
+        ));
+    }
+    if matches!(alloc, AllocKind::Hugetlb) && thp.is_some() {
+        return Err(Error::Usage(
+            "--thp does not apply to hugetlb buffers".into(),
+        ));
+    }
+    if machine {
+        // The structured records are the JSON-lines stream, on stdout.
+        if stream.as_deref().is_some_and(|path| path != Path::new("-")) {
+            return Err(Error::Usage(
+                "--machine writes the JSON lines to stdout; drop --stream".into(),
+            ));
+        }
+        stream = Some(PathBuf::from("-"));
+        quiet = true;
+    }
+    if stream_samples && stream.is_none() {
+        return Err(Error::Usage("--stream-samples needs --stream".into()));
+    }
+    if quiet && tui {
+        return Err(Error::Usage(
+            "--tui draws on stdout, which --quiet and --machine silence".into(),
+        ));
+    }
+    if tui && !io::stdout().is_terminal() {
+        eprintln!("Warning: --tui needs stdout to be a terminal; no gauges will be drawn");
+        tui = false;
+    }
+    if let (Some(budget), None) = (max_memory_mb, spawn_helper) {
+        // The eager copy child holds the buffer and its copy at once.
+        let per_process = 1 + eager_copy as usize;
+        let largest = sizes
+            .as_deref()
+            .unwrap_or(DEFAULT_SIZES_MB)
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0);
+        if largest * per_process > budget {
+            let needs = if eager_copy {
+                format!("{largest} MB twice over for --baseline eager-copy")
+            } else {
+                format!("size {largest} MB")
+            };
+            return Err(Error::Usage(format!(
+                "{needs} exceeds --max-memory ({budget} MB)"
+            )));
+        }
+    }
+    // A spawned helper was given a size the parent already checked, and the
+    // parent's own buffer now takes part of what was available.
+    if let (Some(sizes), None, Some(available_kb)) =
+        (&sizes, spawn_helper, read_meminfo("MemAvailable"))
+    {
+        let available_mb = available_kb / 1024;
+        if let Some(&too_big) = sizes.iter().find(|&&mb| mb as u64 > available_mb) {
+            return Err(Error::Usage(format!(
+                "size {too_big} MB exceeds MemAvailable ({available_mb} MB)"
+            )));
+        }
+        let largest = sizes.iter().copied().max().unwrap_or(0) as u64;
+        let copies = if alloc.is_shared() {
+            0
+        } else {
+            (children * depth) as u64
+        };
+        if largest * (1 + copies) > available_mb {
+            eprintln!(
+                "Warning: {largest} MB copied by {copies} child(ren) needs up to {} MB, more \
+than MemAvailable ({available_mb} MB)",
+                largest * (1 + copies)
+            );
+        }
+    }
+
+    Ok(Config {
+        sizes_mb: sizes.unwrap_or_else(|| DEFAULT_SIZES_MB.to_vec()),
+        output,
+        plot,
+        report,
+        sqlite,
+        stream,
+        stream_samples,
+        quiet,
+        dirty_series,
+        format,
+        append,
+        alloc,
+        touch_order,
+        stride,
+        rounds,
+        latency,
+        soft_dirty,
+        seed,
+        write_size,
+        thp,
+        children,
+        depth,
+        concurrent,
+        read_stage,
+        verify,
+        spawn,
+        thread_baseline,
+        eager_copy,
+        parent_write,
+        fork_advice,
+        discard,
+        mlock,
+        ksm,
+        numa,
+        max_memory_mb,
+        cgroup_limit_mb,
+        compare_pages,
+        fs_cow,
+        check,
+        tui,
+        bench_touch,
+        overcommit,
+        repeat,
+        warmup,
+        child_timeout,
+        spawn_helper,
+        exec,
+        exec_helper,
+    })
+}
+
+/// Parses one `--sizes` entry into megabytes: a bare number of MB, or a
+/// number with a unit of `K`/`KB`, `M`/`MB`, `G`/`GB` (binary multiples,
+/// case-insensitive) or `pages` (base pages). The result must be a whole,
+/// non-zero number of megabytes.
+fn parse_size_mb(text: &str) -> Result<usize, Error> {
+    let split = text
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| Error::Usage(format!("invalid size: {text}")))?;
+    let bytes_per_unit = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "m" | "mb" | "mib" => 1 << 20,
+        "k" | "kb" | "kib" => 1 << 10,
+        "g" | "gb" | "gib" => 1 << 30,
+        "p" | "page" | "pages" => page_size() as u64,
+        other => {
+            return Err(Error::Usage(format!(
+                "unknown size unit {other:?} in {text} (use K, M, G or pages)"
+            )))
+        }
+    };
+    let bytes = number
+        .checked_mul(bytes_per_unit)
+        .ok_or_else(|| Error::Usage(format!("size too large: {text}")))?;
+    if bytes == 0 {
+        return Err(Error::Usage(format!("size must be positive: {text}")));
+    }
+    if !bytes.is_multiple_of(1 << 20) {
+        return Err(Error::Usage(format!(
+            "{text} is {:.2} MB; sizes must be whole megabytes",
+            bytes as f64 / (1 << 20) as f64
+        )));
+    }
+    usize::try_from(bytes >> 20).map_err(|_| Error::Usage(format!("size too large: {text}")))
+}
+
+/// Parses the `input,output` descriptor pair passed to a helper process.
+fn parse_descriptors(value: &str) -> Result<(RawFd, RawFd), Error> {
+    let descriptor = |fd: &str| {
+        fd.parse()
+            .map_err(|_| Error::Usage(format!("invalid descriptor: {fd}")))
+    };
+    let (input, output) = value
+        .split_once(',')
+        .ok_or_else(|| Error::Usage(format!("expected input,output descriptors: {value}")))?;
+    Ok((descriptor(input)?, descriptor(output)?))
+}
+
+fn print_usage() {
+    eprintln!("Usage: cow [--sizes 64,96,128] [--output path] [--append]");
+    eprintln!("           [--alloc heap|mmap|hugetlb|shared|memfd|file]");
+    eprintln!("           [--touch-order sequential|random|reverse] [--seed S] [--stride K]");
+    eprintln!("           [--rounds R] [--latency] [--soft-dirty]");
+    eprintln!("           [--write-size byte|full-page|N] [--thp never|madvise|always-hint]");
+    eprintln!("           [--hugetlb] [--format csv|json] [--children N] [--depth N]");
+    eprintln!("           [--concurrent] [--read-stage] [--exec]");
+    eprintln!("           [--verify]");
+    eprintln!("           [--spawn fork|posix-spawn] [--baseline thread|eager-copy]");
+    eprintln!("           [--parent-write disjoint|same] [--fork-advice dontfork|wipeonfork]");
+    eprintln!("           [--discard dontneed|free]");
+    eprintln!("           [--mlock] [--ksm] [--numa-node N[,M]] [--cgroup-limit MB]");
+    eprintln!("           [--compare-pages] [--max-memory SIZE] [--dirty-series]");
+    eprintln!("           [--repeat N] [--warmup N]");
+    eprintln!("           [--plot out.svg] [--report out.md] [--sqlite results.db]");
+    eprintln!("           [--stream out.jsonl|-] [--stream-samples] [--quiet] [--machine]");
+    eprintln!("           [--child-timeout SECS]");
+    eprintln!("           [--fs-cow DIR] [--check] [--tui] [--bench-touch] [--overcommit]");
+    eprintln!("Demonstrates copy-on-write behaviour via RSS measurements.");
+}
+
+/// The raw `/proc/<pid>/pagemap` entry of each of `buffer`'s base pages.
+fn read_pagemap(pid: u32, buffer: &[u8]) -> io::Result<Vec<u64>> {
+    let base = page_size();
+    let first = buffer.as_ptr() as usize / base;
+    let last = (buffer.as_ptr() as usize + buffer.len()).div_ceil(base);
+    let mut raw = vec![0u8; (last - first) * 8];
+    File::open(format!("/proc/{pid}/pagemap"))?.read_exact_at(&mut raw, (first * 8) as u64)?;
+    Ok(raw
+        .chunks_exact(8)
+        .map(|entry| u64::from_ne_bytes(entry.try_into().expect("chunks are 8 bytes")))
+        .collect())
+}
+
+/// Base pages of `buffer` with the soft-dirty bit (55) set: written since
+/// the last `clear_soft_dirty`.
+fn count_soft_dirty(pid: u32, buffer: &[u8]) -> io::Result<u64> {
+    Ok(read_pagemap(pid, buffer)?
+        .into_iter()
+        .filter(|entry| entry >> 55 & 1 == 1)
+        .count() as u64)
+}
+
+/// Clears the soft-dirty bit of every page of process `pid`; the kernel sets
+/// it again on the next write to each page.
+fn clear_soft_dirty(pid: u32) -> io::Result<()> {
+    std::fs::write(format!("/proc/{pid}/clear_refs"), "4")
+}
+
+/// Whether the kernel tracks soft-dirty bits at all (`CONFIG_MEM_SOFT_DIRTY`).
+/// Nothing in this process has cleared them yet, so a page just written must
+/// show the bit if it is tracked.
+fn soft_dirty_supported() -> bool {
+    let mut probe = vec![0u8; page_size()];
+    probe[0] = 1;
+    let probe = std::hint::black_box(probe);
+    count_soft_dirty(std::process::id(), &probe).is_ok_and(|pages| pages > 0)
+}
+
+/// Physical frame numbers of `buffer`'s base pages in process `pid`, from
+/// `/proc/<pid>/pagemap`; `None` for pages that are not present.
+fn read_frames(pid: u32, buffer: &[u8]) -> io::Result<Vec<Option<u64>>> {
+    let frames: Vec<Option<u64>> = read_pagemap(pid, buffer)?
+        .into_iter()
+        // Bit 63 is "present"; bits 0-54 hold the frame number.
+        .map(|entry| (entry >> 63 == 1).then_some(entry & ((1 << 55) - 1)))
+        .collect();
+    // Without CAP_SYS_ADMIN the kernel reports every frame number as 0.
+    if frames.iter().flatten().all(|&pfn| pfn == 0) && frames.iter().any(Option::is_some) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "pagemap frame numbers need CAP_SYS_ADMIN",
+        ));
+    }
+    Ok(frames)
+}
+
+fn compare_frames(pid: u32, peer: Peer, buffer: &[u8]) -> io::Result<FrameSharing> {
+    if !peer.same_layout {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the peer maps a different buffer",
+        ));
+    }
+    let ours = read_frames(pid, buffer)?;
+    let theirs = read_frames(peer.pid, buffer)?;
+    let mut sharing = FrameSharing::default();
+    for (a, b) in ours.iter().zip(&theirs) {
+        match (a, b) {
+            (Some(a), Some(b)) if a == b => sharing.shared += 1,
+            (Some(_), Some(_)) => sharing.copied += 1,
+            _ => {}
+        }
+    }
+    Ok(sharing)
+}
+
+impl Checkpoint {
+    /// In protocol order: a checkpoint is sent as its index here.
+    const ALL: [Checkpoint; 5] = [
+        Checkpoint::PostFork,
+        Checkpoint::PostRead,
+        Checkpoint::MidTouch,
+        Checkpoint::PostWrite,
+        Checkpoint::PostDiscard,
+    ];
+
+    /// The name sent to the child, which is also the label of the stage it
+    /// reports back.
+    fn label(self) -> &'static str {
+        match self {
+            Checkpoint::PostFork => "post_fork",
+            Checkpoint::PostRead => "post_read",
+            Checkpoint::MidTouch => "mid_touch",
+            Checkpoint::PostWrite => "post_write",
+            Checkpoint::PostDiscard => "post_discard",
+        }
+    }
+}
+
+impl Timestamp {
+    fn now() -> Timestamp {
+        Timestamp {
+            monotonic_ns: sys::monotonic_ns().unwrap_or_default(),
+            unix_ns: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos() as u64),
+        }
+    }
+
+    /// Milliseconds from `start` to this moment, on the monotonic clock.
+    fn ms_since(self, start: Timestamp) -> f64 {
+        (self.monotonic_ns as f64 - start.monotonic_ns as f64) / 1_000_000.0
+    }
+
+    /// The wall-clock time as an RFC 3339 UTC timestamp, to the microsecond.
+    fn utc(self) -> String {
+        let stamp = utc_timestamp(UNIX_EPOCH + Duration::from_nanos(self.unix_ns));
+        format!(
+            "{}.{:06}Z",
+            stamp.trim_end_matches('Z'),
+            self.unix_ns % 1_000_000_000 / 1_000
+        )
+    }
+}
+
+impl NodeUsage {
+    /// E.g. `node 0 8192 kB, node 1 8192 kB`.
+    fn summary(&self) -> String {
+        if self.0.is_empty() {
+            return "unknown".into();
+        }
+        self.0
+            .iter()
+            .map(|(node, kb)| format!("node {node} {kb} kB"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// E.g. `N0=8192;N1=8192`, after the counts of `numa_maps`.
+    fn csv_cell(&self) -> String {
+        self.0
+            .iter()
+            .map(|(node, kb)| format!("N{node}={kb}"))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .0
+            .iter()
+            .map(|(node, kb)| format!("\"{node}\":{kb}"))
+            .collect();
+        format!("{{{}}}", nodes.join(","))
+    }
+}
+
+impl ParentCheckpoint {
+    fn read(checkpoint: Checkpoint, pid: u32, data: &[u8]) -> ParentCheckpoint {
+        ParentCheckpoint {
+            checkpoint,
+            rss_kb: read_rss_kb(pid).unwrap_or_default(),
+            private_dirty_kb: read_private_dirty_kb(pid).unwrap_or_default(),
+            shared_dirty_kb: read_shared_dirty_kb(pid).unwrap_or_default(),
+            swap_kb: read_swap_kb(pid).unwrap_or_default(),
+            buffer: read_buffer_usage(pid, data).unwrap_or_default(),
+        }
+    }
+}
+
+fn system_thp_policy() -> String {
+    // The active choice is bracketed, e.g. "always [madvise] never".
+    std::fs::read_to_string(THP_ENABLED_PATH)
+        .ok()
+        .and_then(|text| {
+            let start = text.find('[')?;
+            let end = text[start..].find(']')?;
+            Some(text[start + 1..start + end].to_string())
+        })
+        .unwrap_or_else(|| "unknown".into())
+}
+
+impl Overcommit {
+    /// `None` without `/proc` (macOS).
+    fn read() -> Option<Overcommit> {
+        let setting = |name: &str| -> Option<u64> {
+            std::fs::read_to_string(format!("/proc/sys/vm/{name}"))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        Some(Overcommit {
+            mode: setting("overcommit_memory")? as u8,
+            ratio: setting("overcommit_ratio")?,
+            kbytes: setting("overcommit_kbytes").unwrap_or(0),
+            commit_limit_kb: read_meminfo("CommitLimit")?,
+            committed_kb: read_meminfo("Committed_AS")?,
+        })
+    }
+
+    fn label(self) -> &'static str {
+        match self.mode {
+            0 => "heuristic",
+            1 => "always",
+            2 => "never",
+            _ => "unknown",
+        }
+    }
+
+    /// E.g. `heuristic (0), ratio 50%`.
+    fn summary(self) -> String {
+        let share = if self.kbytes > 0 {
+            format!("kbytes {}", self.kbytes)
+        } else {
+            format!("ratio {}%", self.ratio)
+        };
+        format!("{} ({}), {share}", self.label(), self.mode)
+    }
+
+    /// Commit still available before `CommitLimit`, which only mode 2
+    /// enforces; negative when more has been promised already.
+    fn headroom_kb(self) -> i64 {
+        self.commit_limit_kb as i64 - self.committed_kb as i64
+    }
+}
+
+impl AllocKind {
+    /// Whether children write to the parent's pages instead of copies.
+    fn is_shared(self) -> bool {
+        matches!(self, AllocKind::Shared | AllocKind::Memfd)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AllocKind::Heap => "heap",
+            AllocKind::Mmap => "mmap",
+            AllocKind::Hugetlb => "hugetlb",
+            AllocKind::Shared => "shared",
+            AllocKind::Memfd => "memfd",
+            AllocKind::File => "file",
+        }
+    }
+}
+
+impl TouchOrder {
+    fn label(self) -> &'static str {
+        match self {
+            TouchOrder::Sequential => "sequential",
+            TouchOrder::Random => "random",
+            TouchOrder::Reverse => "reverse",
+        }
+    }
+}
+
+impl WriteSize {
+    fn arg(self) -> String {
+        match self {
+            WriteSize::Byte => "byte".into(),
+            WriteSize::FullPage => "full-page".into(),
+            WriteSize::Bytes(bytes) => bytes.to_string(),
+        }
+    }
+
+    /// Bytes written per page, capped at the page size.
+    fn bytes(self, page: usize) -> usize {
+        match self {
+            WriteSize::Byte => 1,
+            WriteSize::FullPage => page,
+            WriteSize::Bytes(bytes) => bytes.min(page),
+        }
+    }
+}
+
+impl ParentWrite {
+    fn label(self) -> &'static str {
+        match self {
+            ParentWrite::Disjoint => "disjoint",
+            ParentWrite::Same => "same",
+        }
+    }
+}
+
+impl SpawnKind {
+    fn label(self) -> &'static str {
+        match self {
+            SpawnKind::Fork => "fork",
+            SpawnKind::PosixSpawn => "posix_spawn",
+        }
+    }
+}
+
+impl ThpMode {
+    fn label(self) -> &'static str {
+        match self {
+            ThpMode::Never => "never",
+            ThpMode::Madvise => "madvise",
+            ThpMode::AlwaysHint => "always-hint",
+        }
+    }
+
+    /// Advises the page-aligned interior of `data`; heap buffers are not
+    /// necessarily aligned, so their ragged ends are left alone.
+    fn apply(self, data: &mut [u8], page: usize) -> io::Result<()> {
+        let advice = match self {
+            ThpMode::Never => MADV_NOHUGEPAGE,
+            ThpMode::Madvise => MADV_HUGEPAGE,
+            ThpMode::AlwaysHint => return Ok(()),
+        };
+        advise_interior(data, page, advice)
+    }
+}
+
+impl DiscardAdvice {
+    fn label(self) -> &'static str {
+        match self {
+            DiscardAdvice::DontNeed => "MADV_DONTNEED",
+            DiscardAdvice::Free => "MADV_FREE",
+        }
+    }
+
+    /// The `--discard` value naming this advice.
+    fn arg(self) -> &'static str {
+        match self {
+            DiscardAdvice::DontNeed => "dontneed",
+            DiscardAdvice::Free => "free",
+        }
+    }
+
+    /// Applies the advice to `run`, which must be page-aligned.
+    fn apply(self, run: &mut [u8]) -> io::Result<()> {
+        let advice = match self {
+            DiscardAdvice::DontNeed => MADV_DONTNEED,
+            DiscardAdvice::Free => MADV_FREE,
+        };
+        sys::advise(run, advice)
+    }
+}
+
+impl ForkAdvice {
+    fn label(self) -> &'static str {
+        match self {
+            ForkAdvice::DontFork => "MADV_DONTFORK",
+            ForkAdvice::WipeOnFork => "MADV_WIPEONFORK",
+        }
+    }
+
+    fn apply(self, data: &mut [u8], page: usize) -> io::Result<()> {
+        let advice = match self {
+            ForkAdvice::DontFork => MADV_DONTFORK,
+            ForkAdvice::WipeOnFork => MADV_WIPEONFORK,
+        };
+        advise_interior(data, page, advice)
+    }
+}
+
+/// Applies `advice` to the page-aligned interior of `data`.
+fn advise_interior(data: &mut [u8], page: usize, advice: i32) -> io::Result<()> {
+    match page_interior(data, page) {
+        [] => Ok(()),
+        interior => sys::advise(interior, advice),
+    }
+}
+
+/// The part of `data` from its first to its last whole `page`, if any.
+fn page_interior(data: &mut [u8], page: usize) -> &mut [u8] {
+    let addr = data.as_mut_ptr() as usize;
+    let start = addr.next_multiple_of(page);
+    let end = (addr + data.len()) / page * page;
+    if end <= start {
+        return &mut [];
+    }
+    &mut data[start - addr..end - addr]
+}
+
+/// The CPUs of NUMA `node`, from its `cpulist` in sysfs (e.g. `0-3,8-11`).
+/// Fails for a node that does not exist or has no CPUs.
+fn node_cpus(node: u32) -> io::Result<Vec<usize>> {
+    let text = std::fs::read_to_string(format!("/sys/devices/system/node/node{node}/cpulist"))?;
+    let mut cpus = Vec::new();
+    for range in text.trim().split(',').filter(|range| !range.is_empty()) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let parse = |cpu: &str| {
+            cpu.parse::<usize>()
+                .map_err(|_| io::Error::other(format!("malformed cpulist: {}", text.trim())))
+        };
+        cpus.extend(parse(first)?..=parse(last)?);
+    }
+    if cpus.is_empty() {
+        return Err(io::Error::other(format!("node {node} has no CPUs")));
+    }
+    Ok(cpus)
+}
+
+/// Binds the page-aligned interior of `data` to NUMA `node` and moves the
+/// calling process onto that node's CPUs, so the writes that follow run
+/// there and their new pages are allocated there.
+fn move_to_node(data: &mut [u8], page: usize, node: u32) -> io::Result<()> {
+    sys::bind_memory(page_interior(data, page), node)?;
+    sys::set_cpu_affinity(&node_cpus(node)?)
+}
+
+/// Address ranges currently mapped in this process, from `/proc/self/maps`.
+fn mapped_ranges() -> io::Result<Vec<(usize, usize)>> {
+    let text = std::fs::read_to_string("/proc/self/maps")?;
+    let mut ranges = Vec::new();
+    for line in text.lines() {
+        let Some((start, end)) = line
+            .split_whitespace()
+            .next()
+            .and_then(|range| range.split_once('-'))
+        else {
+            continue;
+        };
+        if let (Ok(start), Ok(end)) = (
+            usize::from_str_radix(start, 16),
+            usize::from_str_radix(end, 16),
+        ) {
+            ranges.push((start, end));
+        }
+    }
+    Ok(ranges)
+}
+
+impl Buffer {
+    fn allocate(kind: AllocKind, len: usize) -> io::Result<Buffer> {
+        match kind {
+            AllocKind::Heap => Buffer::heap(len),
+            AllocKind::Mmap => Buffer::map(len, MAP_PRIVATE),
+            AllocKind::Shared => Buffer::map(len, MAP_SHARED),
+            AllocKind::Memfd => Buffer::map_memfd(len),
+            AllocKind::File => Buffer::map_file(len),
+            AllocKind::Hugetlb => {
+                let huge = huge_page_size();
+                let len = len.next_multiple_of(huge);
+                Buffer::map(len, MAP_PRIVATE | MAP_HUGETLB).map_err(|err| {
+                    if err.raw_os_error() == Some(ENOMEM) {
+                        io::Error::other(format!(
+                            "not enough huge pages reserved: need {} of {} kB, {} free \
+(reserve more via /proc/sys/vm/nr_hugepages)",
+                            len / huge,
+                            huge / 1024,
+                            read_meminfo("HugePages_Free").unwrap_or(0)
+                        ))
+                    } else {
+                        err
+                    }
+                })
+            }
+        }
+    }
+
+    /// A zeroed `Vec` from the global allocator, as `vec![0; len]` would
+    /// give, but failing with ENOMEM instead of aborting, which under
+    /// `--max-memory` is the expected way for an oversized buffer to fail.
+    fn heap(len: usize) -> io::Result<Buffer> {
+        let layout = Layout::array::<u8>(len).map_err(io::Error::other)?;
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            return Err(io::Error::from_raw_os_error(ENOMEM));
+        }
+        // Allocated by the global allocator with the layout a Vec<u8> of
+        // this capacity uses.
+        Ok(Buffer::Heap(unsafe { Vec::from_raw_parts(ptr, len, len) }))
+    }
+
+    fn map(len: usize, flags: i32) -> io::Result<Buffer> {
+        let ptr = sys::map_memory(len, MAP_ANONYMOUS | flags, None)?;
+        Ok(Buffer::Mapped { ptr, len })
+    }
+
+    fn map_memfd(len: usize) -> io::Result<Buffer> {
+        let fd = sys::create_memfd("cow-buffer", len)?;
+        // The mapping keeps the file alive; the descriptor closes on return.
+        let ptr = sys::map_memory(len, MAP_SHARED, Some(fd.as_fd()))?;
+        Ok(Buffer::Mapped { ptr, len })
+    }
+
+    /// Writes the fill pattern to a temporary file in the temp directory
+    /// and maps it `MAP_PRIVATE`. The file is unlinked straight away; the
+    /// mapping keeps it alive.
+    fn map_file(len: usize) -> io::Result<Buffer> {
+        let path = env::temp_dir().join(format!("cow-buffer-{}", std::process::id()));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let _ = std::fs::remove_file(&path);
+        write_pattern(&mut file, len)?;
+        // Written back, so the page cache starts out clean like any file's.
+        file.sync_all()?;
+        let ptr = sys::map_memory(len, MAP_PRIVATE, Some(file.as_fd()))?;
+        Ok(Buffer::Mapped { ptr, len })
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            Buffer::Heap(data) => data,
+            Buffer::Mapped { ptr, len } => unsafe { std::slice::from_raw_parts_mut(*ptr, *len) },
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if let Buffer::Mapped { ptr, len } = *self {
+            // The slice handed out by `as_mut_slice` borrows `self`, so
+            // nothing can still refer to the mapping here.
+            let _ = unsafe { sys::unmap_memory(ptr, len) };
+        }
+    }
+}
+
+fn read_ksm_counter(name: &str) -> Option<u64> {
+    std::fs::read_to_string(format!("{KSM_PATH}/{name}"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Registers `data` with KSM and waits for ksmd to merge it. A page is only
+/// merged once its checksum has held steady across two scans, and the scan
+/// running at the time of the `madvise` may already be past the buffer, so
+/// three full scans have to complete.
+fn merge_with_ksm(data: &mut [u8], page: usize) -> Result<KsmReport, Error> {
+    if read_ksm_counter("run") != Some(1) {
+        return Err(Error::Unsupported(format!(
+            "ksmd is not running (echo 1 > {KSM_PATH}/run to start it)"
+        )));
+    }
+    let scans = read_ksm_counter("full_scans")
+        .ok_or_else(|| Error::Unsupported(format!("cannot read {KSM_PATH}/full_scans")))?;
+    advise_interior(data, page, MADV_MERGEABLE).map_err(Error::sys("madvise(MADV_MERGEABLE)"))?;
+    println!("Waiting for ksmd to scan the buffer...");
+    let started = Instant::now();
+    while read_ksm_counter("full_scans").unwrap_or(scans) < scans + 3 {
+        if started.elapsed() > KSM_TIMEOUT {
+            return Err(Error::Unsupported(format!(
+                "ksmd did not finish three full scans within {} s",
+                KSM_TIMEOUT.as_secs()
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let merging_pages = std::fs::read_to_string("/proc/self/ksm_merging_pages")
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(0);
+    Ok(KsmReport {
+        wait_ms: started.elapsed().as_secs_f64() * 1000.0,
+        merging_pages,
+        pages_shared: read_ksm_counter("pages_shared").unwrap_or(0),
+        pages_sharing: read_ksm_counter("pages_sharing").unwrap_or(0),
+    })
+}
+
+/// A cgroup v2 group limited by `memory.max` that this process moved into
+/// for `--cgroup-limit`, so the buffer and every child are charged to it.
+/// Dropping it moves the process back and removes the group.
+struct MemoryCgroup {
+    path: PathBuf,
+    /// The group the process came from.
+    original: PathBuf,
+    limit_mb: usize,
+}
+
+impl MemoryCgroup {
+    fn enter(limit_mb: usize) -> Result<MemoryCgroup, Error> {
+        let mounts = std::fs::read_to_string("/proc/self/mounts")
+            .map_err(Error::sys("reading /proc/self/mounts"))?;
+        let root = mounts
+            .lines()
+            .find_map(|line| {
+                let mut fields = line.split_whitespace();
+                let (_, dir, kind) = (fields.next()?, fields.next()?, fields.next()?);
+                (kind == "cgroup2").then(|| PathBuf::from(dir))
+            })
+            .ok_or_else(|| Error::Unsupported("no cgroup v2 hierarchy is mounted".into()))?;
+        let own = std::fs::read_to_string("/proc/self/cgroup")
+            .map_err(Error::sys("reading /proc/self/cgroup"))?;
+        let relative = own
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_else(|| Error::Unsupported("this process is not in a cgroup v2 group".into()))?;
+        let original = root.join(relative.trim_start_matches('/'));
+
+        // The new group goes directly under the root, which is exempt from
+        // the rule that a group handing controllers to its children holds no
+        // processes itself.
+        let has_memory = |file: &str| {
+            std::fs::read_to_string(root.join(file))
+                .unwrap_or_default()
+                .split_whitespace()
+                .any(|controller| controller == "memory")
+        };
+        if !has_memory("cgroup.controllers") {
+            return Err(Error::Unsupported(format!(
+                "the memory controller is not available in {}",
+                root.display()
+            )));
+        }
+        if !has_memory("cgroup.subtree_control") {
+            std::fs::write(root.join("cgroup.subtree_control"), "+memory")
+                .map_err(Error::sys("enabling the memory controller"))?;
+        }
+        let path = root.join(format!("cow-{}", std::process::id()));
+        std::fs::create_dir(&path).map_err(Error::sys(format!("creating {}", path.display())))?;
+        // From here on an error drops the group, which removes it again.
+        let cgroup = MemoryCgroup {
+            path,
+            original,
+            limit_mb,
+        };
+        std::fs::write(
+            cgroup.path.join("memory.max"),
+            (limit_mb * 1024 * 1024).to_string(),
+        )
+        .map_err(Error::sys("setting memory.max"))?;
+        std::fs::write(
+            cgroup.path.join("cgroup.procs"),
+            std::process::id().to_string(),
+        )
+        .map_err(Error::sys(format!("moving into {}", cgroup.path.display())))?;
+        Ok(cgroup)
+    }
+
+    /// The group's `memory.events` counters so far; unreadable ones read as 0.
+    fn events(&self) -> CgroupReport {
+        let mut report = CgroupReport {
+            limit_mb: self.limit_mb,
+            ..CgroupReport::default()
+        };
+        let text = std::fs::read_to_string(self.path.join("memory.events")).unwrap_or_default();
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let value = value.trim().parse().unwrap_or(0);
+            match name {
+                "low" => report.low = value,
+                "high" => report.high = value,
+                "max" => report.max = value,
+                "oom" => report.oom = value,
+                "oom_kill" => report.oom_kill = value,
+                _ => {}
+            }
+        }
+        report
+    }
+}
+
+impl Drop for MemoryCgroup {
+    fn drop(&mut self) {
+        // A group can only be removed once no process is left in it.
+        let _ = std::fs::write(
+            self.original.join("cgroup.procs"),
+            std::process::id().to_string(),
+        );
+        if let Err(err) = std::fs::remove_dir(&self.path) {
+            eprintln!(
+                "Warning: could not remove cgroup {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl CgroupReport {
+    fn since(self, earlier: CgroupReport) -> CgroupReport {
+        CgroupReport {
+            limit_mb: self.limit_mb,
+            low: self.low.saturating_sub(earlier.low),
+            high: self.high.saturating_sub(earlier.high),
+            max: self.max.saturating_sub(earlier.max),
+            oom: self.oom.saturating_sub(earlier.oom),
+            oom_kill: self.oom_kill.saturating_sub(earlier.oom_kill),
+        }
+    }
+
+    fn summary(self) -> String {
+        format!(
+            "low {}, high {}, max {}, oom {}, oom_kill {}",
+            self.low, self.high, self.max, self.oom, self.oom_kill
+        )
+    }
+}
+
+fn read_meminfo(field: &str) -> Option<u64> {
+    let text = std::fs::read_to_string("/proc/meminfo").ok()?;
+    text.lines().find_map(|line| {
+        let rest = line.strip_prefix(field)?.strip_prefix(':')?;
+        rest.split_whitespace().next()?.parse().ok()
+    })
+}
+
+fn huge_page_size() -> usize {
+    read_meminfo("Hugepagesize").unwrap_or(DEFAULT_HUGE_PAGE_KB) as usize * 1024
+}
+
+/// Minor and major page faults of the calling process so far.
+fn fault_counts() -> (u64, u64) {
+    sys::fault_counts().unwrap_or_default()
+}
+
+/// The unit the child touches: one huge page for hugetlb buffers.
+fn touch_page_size(alloc: AllocKind) -> usize {
+    match alloc {
+        AllocKind::Hugetlb => huge_page_size(),
+        AllocKind::Heap
+        | AllocKind::Mmap
+        | AllocKind::Shared
+        | AllocKind::Memfd
+        | AllocKind::File => page_size(),
+    }
+}
+
+/// Locks `data` into memory, explaining the usual causes when that fails.
+fn lock_buffer(data: &[u8]) -> Result<(), Error> {
+    let Err(err) = sys::lock_memory(data) else {
+        return Ok(());
+    };
+    let soft = match sys::resource_limit(RLIMIT_MEMLOCK) {
+        Err(_) => "unknown".to_string(),
+        Ok((RLIM_INFINITY, _)) => "unlimited".to_string(),
+        Ok((soft, _)) => format!("{} kB", soft / 1024),
+    };
+    let call = format!("mlock of {} kB", data.len() / 1024);
+    Err(Error::sys(call)(err).explained(format!(
+        "RLIMIT_MEMLOCK is {soft}; raise it with `ulimit -l` or run with CAP_IPC_LOCK"
+    )))
+}
+
+/// Caps this process, and through inheritance every child and helper, at
+/// `budget_mb` more address space and private memory than it maps now
+/// (`--max-memory`), so an allocation past the budget fails with ENOMEM
+/// instead of waking the OOM killer. Returns the two limits in bytes.
+fn limit_memory(budget_mb: usize) -> Result<(u64, u64), Error> {
+    let budget = budget_mb as u64 * 1024 * 1024;
+    // Without /proc (macOS) the budget is the whole limit.
+    let (size_kb, data_kb) = read_mapped_kb(std::process::id()).unwrap_or_default();
+    let limits = (size_kb * 1024 + budget, data_kb * 1024 + budget);
+    for (resource, name, limit) in [
+        (RLIMIT_AS, "RLIMIT_AS", limits.0),
+        (RLIMIT_DATA, "RLIMIT_DATA", limits.1),
+    ] {
+        let (_, hard) =
+            sys::resource_limit(resource).map_err(Error::sys(format!("getrlimit({name})")))?;
+        sys::set_resource_limit(resource, limit.min(hard), hard)
+            .map_err(Error::sys(format!("setrlimit({name})")))?;
+    }
+    Ok(limits)
+}
+
+/// Page indices in the order the child should touch them. Random order is a
+/// Fisher-Yates shuffle driven by SplitMix64, so a seed reproduces it exactly.
+/// The indices of every `stride`-th page out of `pages`, in the order they
+/// are touched.
+fn page_order(pages: usize, order: TouchOrder, seed: u64, stride: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..pages).step_by(stride).collect();
+    match order {
+        TouchOrder::Sequential => {}
+        TouchOrder::Reverse => indices.reverse(),
+        TouchOrder::Random => {
+            let mut state = seed;
+            for i in (1..indices.len()).rev() {
+                state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+                indices.swap(i, (z % (i as u64 + 1)) as usize);
+            }
+        }
+    }
+    indices
+}
+
+fn touch_pages(data: &mut [u8], page: usize, order: &[usize], write_bytes: usize) {
+    if page == 0 {
+        return;
+    }
+    for &index in order {
+        let start = index * page;
+        let end = (start + write_bytes).min(data.len());
+        if let Some(bytes) = data.get_mut(start..end) {
+            for byte in bytes {
+                *byte = byte.wrapping_add(1);
+            }
+        }
+    }
+}
+
+/// The runs of `data` the writes to `written` landed in, as `(offset, len)`:
+/// every whole `page` of the address space a write touched, coalesced.
+/// Pages only partly inside `data` are left out, since a heap buffer shares
+/// them with allocator metadata.
+fn written_runs(
+    data: &[u8],
+    page: usize,
+    written: &[usize],
+    write_bytes: usize,
+) -> Vec<(usize, usize)> {
+    let base = data.as_ptr() as usize;
+    let (first, end) = (
+        base.next_multiple_of(page),
+        (base + data.len()) / page * page,
+    );
+    let mut pages: Vec<usize> = written
+        .iter()
+        .flat_map(|&index| {
+            let start = base + index * page;
+            let stop = (start + write_bytes.max(1)).min(base + data.len());
+            (start / page * page..stop).step_by(page)
+        })
+        .filter(|&addr| first <= addr && addr + page <= end)
+        .collect();
+    pages.sort_unstable();
+    pages.dedup();
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for addr in pages {
+        match runs.last_mut() {
+            Some((offset, len)) if base + *offset + *len == addr => *len += page,
+            _ => runs.push((addr - base, page)),
+        }
+    }
+    runs
+}
+
+/// Applies `write` to the first `write_bytes` bytes of each page in `order`,
+/// timing every page and appending the times to `samples` in nanoseconds.
+fn time_page_writes(
+    data: &mut [u8],
+    page: usize,
+    order: &[usize],
+    write_bytes: usize,
+    write: fn(&mut u8),
+    samples: &mut Vec<u64>,
+) {
+    for &index in order {
+        let start = index * page;
+        let end = (start + write_bytes).min(data.len());
+        if let Some(bytes) = data.get_mut(start..end) {
+            // Letting the page escape keeps its writes between the two clock
+            // reads instead of letting the compiler move them past either.
+            let bytes = std::hint::black_box(bytes);
+            let clock = Instant::now();
+            bytes.iter_mut().for_each(write);
+            samples.push(clock.elapsed().as_nanos() as u64);
+        }
+    }
+}
+
+impl LatencyStats {
+    fn of(samples: &mut [u64]) -> LatencyStats {
+        let mut stats = LatencyStats::default();
+        if samples.is_empty() {
+            return stats;
+        }
+        samples.sort_unstable();
+        for &ns in samples.iter() {
+            let bucket = (u64::BITS - 1 - ns.max(1).leading_zeros()) as usize;
+            stats.buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        }
+        // Nearest-rank percentiles.
+        let rank = |percent: usize| samples[(samples.len() * percent).div_ceil(100).max(1) - 1];
+        stats.p50_ns = rank(50);
+        stats.p90_ns = rank(90);
+        stats.p99_ns = rank(99);
+        stats.max_ns = samples[samples.len() - 1];
+        stats
+    }
+
+    fn count(self) -> u64 {
+        self.buckets.iter().sum()
+    }
+}
+
+fn write_all(fd: BorrowedFd<'_>, payload: &[u8]) -> io::Result<()> {
+    let mut total = 0;
+    while total < payload.len() {
+        total += sys::write_fd(fd, &payload[total..])?;
+    }
+    Ok(())
+}
+
+/// One side of the checkpoint protocol between the parent and a child: a
+/// pipe each way, carrying the versioned frames of the `report` module. The
+/// parent sends a checkpoint; the child replies with one frame of records,
+/// the last of which is the stage for that checkpoint and doubles as the
+/// acknowledgement.
+struct Channel {
+    input: OwnedFd,
+    output: OwnedFd,
+    /// Bytes read past the last frame returned.
+    pending: Vec<u8>,
+}
+
+impl Channel {
+    /// Creates both pipes and returns the parent's end and the child's. Every
+    /// descriptor is close-on-exec: a helper must not inherit the parent's
+    /// end, or it would never see its command pipe close, and is handed the
+    /// child's end explicitly with [`Channel::inherit`].
+    fn pair() -> io::Result<(Channel, Channel)> {
+        let (command_read, command_write) = sys::create_pipe()?;
+        let (report_read, report_write) = sys::create_pipe()?;
+        Ok((
+            Channel::new(report_read, command_write),
+            Channel::new(command_read, report_write),
+        ))
+    }
+
+    /// The channel a helper process was handed by number on its command
+    /// line.
+    ///
+    /// # Safety
+    ///
+    /// `input` and `output` must be open descriptors owned by nothing else
+    /// in this process.
+    unsafe fn inherited(input: RawFd, output: RawFd) -> Channel {
+        Channel::new(OwnedFd::from_raw_fd(input), OwnedFd::from_raw_fd(output))
+    }
+
+    /// Lets a program this process starts inherit the channel, and returns
+    /// the `input,output` argument that tells it which descriptors it has.
+    fn inherit(&self) -> io::Result<String> {
+        sys::set_inheritable(self.input.as_fd())?;
+        sys::set_inheritable(self.output.as_fd())?;
+        Ok(format!(
+            "{},{}",
+            self.input.as_raw_fd(),
+            self.output.as_raw_fd()
+        ))
+    }
+
+    fn new(input: OwnedFd, output: OwnedFd) -> Channel {
+        Channel {
+            input,
+            output,
+            pending: Vec::new(),
+        }
+    }
+
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        write_all(self.output.as_fd(), frame)
+    }
+
+    /// The payload of the next frame, or `None` once the other side has
+    /// closed its end. Fails with `TimedOut` if no whole frame has arrived by
+    /// `deadline`, and with `UnexpectedEof` if the other side closed its end
+    /// partway through one.
+    fn receive(&mut self, deadline: Option<Instant>) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(payload) = report::take_frame(&mut self.pending) {
+                return Ok(Some(payload));
+            }
+            if let Some(deadline) = deadline {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let left_ms = left.as_millis().min(i32::MAX as u128) as i32;
+                if !sys::wait_readable(self.input.as_fd(), left_ms)? {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+            }
+            let mut temp = [0u8; 1024];
+            let read_bytes = sys::read_fd(self.input.as_fd(), &mut temp)?;
+            if read_bytes == 0 {
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.pending.extend_from_slice(&temp[..read_bytes]);
+        }
+    }
+
+    /// Asks the child to go on to `checkpoint`.
+    fn ask(&self, checkpoint: Checkpoint) -> Result<(), Error> {
+        self.send(&report::encode_request(checkpoint))
+            .map_err(Error::sys(format!("sending {}", checkpoint.label())))
+    }
+
+    /// Waits for the child to reach `checkpoint` and returns its records.
+    fn reply_to(
+        &mut self,
+        checkpoint: Checkpoint,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Record>, Error> {
+        let stage = checkpoint.label();
+        let payload = self
+            .receive(deadline)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::TimedOut => Error::Child(format!(
+                    "child did not reach {stage} within the --child-timeout"
+                )),
+                _ => Error::sys(format!("reading the child report for {stage}"))(e),
+            })?
+            .ok_or_else(|| Error::Child(format!("child exited before reaching {stage}")))?;
+        let collected_at = Timestamp::now();
+        let mut records = report::decode_reply(&payload).map_err(|e| e.at_stage(stage))?;
+        match records.last_mut() {
+            Some(Record::Stage(last)) if last.stage == stage => {
+                last.collected_at = Some(collected_at);
+                Ok(records)
+            }
+            _ => Err(Error::Report {
+                stage: Some(stage),
+                message: "the reply does not end with that stage".into(),
+            }),
+        }
+    }
+}
+
+/// Sorts a child's records into a run with a zero `fork_us`.
+fn collect_run(records: Vec<Record>) -> Result<ChildRun, Error> {
+    let mut stages = Vec::new();
+    let mut rounds = Vec::new();
+    let mut latency = None;
+    let mut discard = None;
+    let mut checksum = None;
+    let mut errors = Vec::new();
+    for record in records {
+        match record {
+            Record::Stage(stage) => stages.push(*stage),
+            Record::Round(round) => rounds.push(round),
+            Record::Latency(report) => latency = Some(*report),
+            Record::Discard(report) => discard = Some(*report),
+            Record::Error(error) => errors.push(error),
+            // The fork of the next generation; the caller credits it to that
+            // generation's run.
+            Record::Forked(_) => {}
+            Record::Checksum(sum) => checksum = Some(sum),
+        }
+    }
+    let mut take = |label: &str| {
+        stages
+            .iter()
+            .position(|stage: &ChildStage| stage.stage == label)
+            .map(|index| stages.remove(index))
+    };
+    let post_fork = take(Checkpoint::PostFork.label());
+    let post_read = take(Checkpoint::PostRead.label());
+    let mid_touch = take(Checkpoint::MidTouch.label());
+    let post_write = take(Checkpoint::PostWrite.label());
+    let post_discard = take(Checkpoint::PostDiscard.label());
+    let (Some(post_fork), Some(post_write)) = (post_fork, post_write) else {
+        return Err(Error::Report {
+            stage: None,
+            message: "expected post_fork and post_write stages from child".into(),
+        });
+    };
+    Ok(ChildRun {
+        fork_us: 0.0,
+        forked_at: None,
+        generation: 0,
+        post_fork,
+        post_read,
+        mid_touch,
+        post_write,
+        post_discard,
+        discard,
+        rounds,
+        latency,
+        dirty_series: Vec::new(),
+        checksum,
+        exit: None,
+        errors,
+    })
+}
+
+/// Runs one child: `chain` holds its own channel, then those of the
+/// generations below it, which it forks first.
+fn child_routine(
+    data: &mut [u8],
+    mut chain: Vec<Channel>,
+    plan: TouchPlan,
+    fork_advice: Option<ForkAdvice>,
+    forked: bool,
+) -> ! {
+    let mut channel = chain.remove(0);
+    let mut errors = Vec::new();
+    // The next generation is forked before anything is measured or touched,
+    // so it shares every page this one inherited.
+    let mut next = None;
+    if !chain.is_empty() {
+        let fork_start = Instant::now();
+        match sys::fork_process() {
+            Ok(Fork::Child) => {
+                drop(channel);
+                // Only the parent's own children report their progress.
+                let plan = TouchPlan {
+                    series: None,
+                    ..plan
+                };
+                child_routine(data, chain, plan, fork_advice, forked);
+            }
+            Ok(Fork::Parent(pid)) => {
+                next = Some((pid, fork_start.elapsed().as_secs_f64() * 1_000_000.0));
+            }
+            // The next generation's channel closes unanswered, which fails
+            // the experiment.
+            Err(err) => errors.push(format!("fork of the next generation: {err}")),
+        }
+        drop(chain);
+    }
+    if let Some(node) = plan.numa {
+        // The buffer's mapping carries the parent's binding, which would
+        // otherwise place the copies too; everything else follows the task
+        // policy.
+        checked(&mut errors, "mbind", move_to_node(data, plan.page, node));
+        checked(&mut errors, "set_mempolicy", sys::set_memory_policy(node));
+    }
+
+    let (page, order) = (plan.page, plan.order);
+    let (mut missing, mut zeroed) = (0, 0);
+    let mut touchable = order;
+    let kept: Vec<usize>;
+    if fork_advice.is_some() {
+        // Touching a page left out by MADV_DONTFORK would segfault, so find
+        // which pages are still mapped before reading or writing any of them.
+        // The maps are read before allocating anything large, since a new
+        // mapping could land in the hole the buffer left behind.
+        let ranges = checked(&mut errors, "maps", mapped_ranges());
+        let base = data.as_ptr() as usize;
+        let mapped = |index: usize| {
+            let addr = base + index * page;
+            ranges
+                .iter()
+                .any(|&(start, end)| start <= addr && addr < end)
+        };
+        kept = order
+            .iter()
+            .copied()
+            .filter(|&index| mapped(index))
+            .collect();
+        missing = (order.len() - kept.len()) as u64;
+        // The parent filled offset 1 of every page with 1; wiped pages read 0.
+        zeroed = kept
+            .iter()
+            .filter(|&&index| data.get(index * page + 1) == Some(&0))
+            .count() as u64;
+        touchable = &kept;
+    }
+
+    // Fault counters restart at zero in a forked child, so no baseline is
+    // needed to count the faults taken since the fork.
+    let mut toucher = Toucher::new(
+        data,
+        TouchPlan {
+            order: touchable,
+            ..plan
+        },
+        FaultBaseline::default(),
+        Peer {
+            pid: std::os::unix::process::parent_id(),
+            same_layout: forked,
+        },
+    );
+    toucher.errors = errors;
+    toucher.missing_pages = missing;
+    toucher.zeroed_pages = zeroed;
+
+    // Answer the checkpoints in the order the parent asks for them. The child
+    // stays put after the last one until the parent closes the channel, so
+    // the parent's own figures for it are read before the child exits.
+    let mut status = loop {
+        let checkpoint = match channel.receive(None) {
+            Ok(Some(payload)) => match report::decode_request(&payload) {
+                Ok(checkpoint) => checkpoint,
+                Err(err) => {
+                    eprintln!("child could not decode its checkpoint: {err}");
+                    break 1;
+                }
+            },
+            Ok(None) => break 0,
+            Err(err) => {
+                eprintln!("child failed to read checkpoint: {err}");
+                break 1;
+            }
+        };
+        let mut records = toucher.reach(checkpoint);
+        if let (Checkpoint::PostFork, Some((_, fork_us))) = (checkpoint, next) {
+            records.insert(0, Record::Forked(fork_us));
+        }
+        if let Err(err) = channel.send(&report::encode_reply(&records)) {
+            eprintln!("child failed to write report: {err}");
+            break 1;
+        }
+    };
+
+    drop(channel);
+    // Only the parent's own children are reaped by it; a failed generation
+    // further down shows in the exit status of the first.
+    if let Some((pid, _)) = next {
+        match wait_child(pid, None) {
+            Ok(exit) if exit.success() => {}
+            Ok(exit) => {
+                eprintln!("child's next generation ended abnormally ({exit})");
+                status = 1;
+            }
+            Err(err) => {
+                eprintln!("child could not reap its next generation: {err}");
+                status = 1;
+            }
+        }
+    }
+    sys::exit_now(status);
+}
+
+/// Fault counters of the calling process at some earlier moment, from both
+/// `getrusage` and `/proc/<pid>/stat`.
+#[derive(Clone, Copy, Default)]
+struct FaultBaseline {
+    rusage: (u64, u64),
+    stat: StatFaults,
+}
+
+impl FaultBaseline {
+    fn now(pid: u32) -> FaultBaseline {
+        FaultBaseline {
+            rusage: fault_counts(),
+            stat: read_stat_faults(pid).unwrap_or_default(),
+        }
+    }
+
+    /// Moves the baseline forward by the faults taken between `from` and
+    /// `to`, leaving them out of every later count.
+    fn skip(&mut self, from: FaultBaseline, to: FaultBaseline) {
+        self.rusage.0 += to.rusage.0.saturating_sub(from.rusage.0);
+        self.rusage.1 += to.rusage.1.saturating_sub(from.rusage.1);
+        self.stat.min_flt += to.stat.min_flt.saturating_sub(from.stat.min_flt);
+        self.stat.maj_flt += to.stat.maj_flt.saturating_sub(from.stat.maj_flt);
+    }
+}
+
+/// A touch workload measured at checkpoints: the figures before any page is
+/// written, then after writing the plan's pages up to each later checkpoint.
+/// Faults taken by the measurements themselves are left out of the counts.
+struct Toucher<'a> {
+    data: &'a mut [u8],
+    plan: TouchPlan<'a>,
+    peer: Peer,
+    /// Fault counters the next stage counts from: the caller's baseline for
+    /// `post_fork`, the end of `post_fork` for every later stage.
+    baseline: FaultBaseline,
+    /// Pages of `plan.order` written so far.
+    touched: usize,
+    touch_ms: f64,
+    touch_started_at: Option<Timestamp>,
+    touch_ended_at: Option<Timestamp>,
+    /// Failed measurements not yet reported.
+    errors: Vec<String>,
+    /// Reported with `post_fork`; see `ChildStage`.
+    missing_pages: u64,
+    zeroed_pages: u64,
+    /// Time of each first write so far, under `plan.latency`.
+    first_write_ns: Vec<u64>,
+    /// The touch phase's first page write, once made.
+    first_write: Option<FirstWrite>,
+    progress: Progress,
+}
+
+impl<'a> Toucher<'a> {
+    fn new(data: &'a mut [u8], plan: TouchPlan<'a>, baseline: FaultBaseline, peer: Peer) -> Self {
+        let progress = Progress::new(
+            format!("Process {} writing", std::process::id()),
+            plan.order.len(),
+            data.len(),
+        );
+        Toucher {
+            data,
+            plan,
+            peer,
+            baseline,
+            touched: 0,
+            touch_ms: 0.0,
+            touch_started_at: None,
+            touch_ended_at: None,
+            errors: Vec::new(),
+            missing_pages: 0,
+            zeroed_pages: 0,
+            // Reserved up front so recording a time never allocates while
+            // the pages are being touched.
+            first_write_ns: Vec::with_capacity(if plan.latency { plan.order.len() } else { 0 }),
+            first_write: None,
+            progress,
+        }
+    }
+
+    /// Touches whatever `checkpoint` needs and returns the records for it:
+    /// any rounds and failed measurements, then its stage.
+    fn reach(&mut self, checkpoint: Checkpoint) -> Vec<Record> {
+        let pages = self.plan.order.len();
+        let (mut records, stage) = match checkpoint {
+            Checkpoint::PostFork => {
+                let stage = self.post_fork();
+                // Summed after the measurements, so its reads are not in them.
+                let verified = self
+                    .plan
+                    .verify
+                    .then(|| Record::Checksum(checksum(self.data)));
+                (verified.into_iter().collect(), stage)
+            }
+            Checkpoint::PostRead => (Vec::new(), self.post_read()),
+            Checkpoint::MidTouch => {
+                self.pass_gate(1);
+                (self.touch_to(pages / 2), self.stage(checkpoint))
+            }
+            Checkpoint::PostWrite => {
+                self.pass_gate(2);
+                let mut records = self.touch_to(pages);
+                if self.plan.latency {
+                    records.push(Record::Latency(Box::new(self.latency())));
+                }
+                (records, self.stage(checkpoint))
+            }
+            Checkpoint::PostDiscard => match self.plan.discard {
+                Some(advice) => {
+                    let (report, stage) = self.post_discard(advice);
+                    (vec![Record::Discard(Box::new(report))], stage)
+                }
+                None => (Vec::new(), self.stage(checkpoint)),
+            },
+        };
+        records.extend(
+            self.errors
+                .drain(..)
+                .map(|error| Record::Error(format!("{}: {error}", checkpoint.label()))),
+        );
+        records.push(Record::Stage(Box::new(stage)));
+        records
+    }
+
+    /// Waits for the other `--concurrent` children before a touch stage.
+    fn pass_gate(&mut self, pass: usize) {
+        if let Some(gate) = self.plan.gate {
+            if let Err(err) = gate.wait(pass) {
+                self.errors.push(err.to_string());
+            }
+        }
+    }
+
+    fn post_fork(&mut self) -> ChildStage {
+        let pid = std::process::id();
+        let mut stage = self.figures(pid, Checkpoint::PostFork);
+        let now = self.faults_now(pid);
+        self.count_faults(&mut stage, now);
+        stage.missing_pages = self.missing_pages;
+        stage.zeroed_pages = self.zeroed_pages;
+        if self.plan.soft_dirty {
+            // Pages written from here on show the bit again.
+            checked(&mut self.errors, "clear_refs", clear_soft_dirty(pid));
+        }
+        self.baseline = now;
+        stage
+    }
+
+    /// Reads the first byte of every page of the plan without writing, the
+    /// control for the touch stages: reads alone copy nothing. Faults taken
+    /// here are left out of the touch stages' counts.
+    fn post_read(&mut self) -> ChildStage {
+        let pid = std::process::id();
+        let start = Instant::now();
+        let sum = self.plan.order.iter().fold(0u8, |sum, &index| {
+            sum.wrapping_add(self.data[index * self.plan.page])
+        });
+        std::hint::black_box(sum);
+        let read_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let now = self.faults_now(pid);
+        let mut stage = self.figures(pid, Checkpoint::PostRead);
+        self.count_faults(&mut stage, now);
+        stage.touch_ms = read_ms;
+        if self.plan.soft_dirty {
+            stage.soft_dirty_pages = count_soft_dirty(pid, self.data)
+                .map_err(|err| self.errors.push(format!("soft_dirty: {err}")))
+                .ok();
+        }
+        self.baseline = self.faults_now(pid);
+        stage
+    }
+
+    /// Applies `advice` to every page written so far and measures, then reads
+    /// the first byte of each written page back and sorts the pages by what
+    /// it holds. The stage's faults are those of the advice alone; the
+    /// read-back is in the report.
+    fn post_discard(&mut self, advice: DiscardAdvice) -> (DiscardReport, ChildStage) {
+        let pid = std::process::id();
+        let (page, order) = (self.plan.page, self.plan.order);
+        let written = &order[..self.touched];
+        let values: Vec<u8> = written
+            .iter()
+            .map(|&index| self.data[index * page])
+            .collect();
+        self.baseline = self.faults_now(pid);
+        let mut discarded_bytes = 0;
+        for (offset, len) in written_runs(self.data, page, written, self.plan.write_bytes) {
+            let run = &mut self.data[offset..offset + len];
+            if let Err(err) = advice.apply(run) {
+                self.errors
+                    .push(format!("madvise({}): {err}", advice.label()));
+                break;
+            }
+            discarded_bytes += len as u64;
+        }
+        let now = self.faults_now(pid);
+        let mut stage = self.figures(pid, Checkpoint::PostDiscard);
+        self.count_faults(&mut stage, now);
+        let lazy_free_kb = checked(&mut self.errors, "lazy_free_kb", read_lazy_free_kb(pid));
+
+        let before = fault_counts();
+        let (mut kept_pages, mut zeroed_pages, mut other_pages) = (0, 0, 0);
+        for (&index, &value) in written.iter().zip(&values) {
+            match std::hint::black_box(self.data[index * page]) {
+                read if read == value => kept_pages += 1,
+                0 => zeroed_pages += 1,
+                _ => other_pages += 1,
+            }
+        }
+        let after = fault_counts();
+        let errors = &mut self.errors;
+        let report = DiscardReport {
+            advice,
+            discarded_bytes,
+            lazy_free_kb,
+            kept_pages,
+            zeroed_pages,
+            other_pages,
+            reread_faults: (after.0 + after.1).saturating_sub(before.0 + before.1),
+            reread_rss_kb: checked(errors, "reread_rss_kb", read_rss_kb(pid)),
+            reread_private_dirty_kb: checked(
+                errors,
+                "reread_private_dirty_kb",
+                read_private_dirty_kb(pid),
+            ),
+        };
+        (report, stage)
+    }
+
+    /// Writes the pages of the plan up to `end`, measuring at every
+    /// `--rounds` slice boundary on the way, and returns the rounds.
+    fn touch_to(&mut self, end: usize) -> Vec<Record> {
+        let pid = std::process::id();
+        let order = self.plan.order;
+        let end = end.min(order.len());
+        let slice = order.len().div_ceil(self.plan.rounds).max(1);
+        let step = self.progress.step();
+        let series_step = match self.plan.series {
+            Some(_) => order.len().div_ceil(DIRTY_SERIES_SAMPLES).max(1),
+            None => order.len().max(1),
+        };
+        if self.touched == 0 && self.touched < end {
+            // The starting point of the series, before any copy.
+            self.report_series(0);
+        }
+        let mut rounds = Vec::new();
+        while self.touched < end {
+            // Stop at whichever comes first: the end of the round, the next
+            // progress report or `end`. The first page is written alone.
+            let stop = if self.touched == 0 {
+                1
+            } else {
+                ((self.touched / slice + 1) * slice)
+                    .min((self.touched / step + 1) * step)
+                    .min((self.touched / series_step + 1) * series_step)
+                    .min(end)
+            };
+            self.touch_started_at.get_or_insert_with(Timestamp::now);
+            let start = Instant::now();
+            if self.touched == 0 {
+                let first = self.write_first(order[0]);
+                self.first_write = Some(first);
+                self.touch_ms += first.ns as f64 / 1_000_000.0;
+            } else if self.plan.latency {
+                time_page_writes(
+                    self.data,
+                    self.plan.page,
+                    &order[self.touched..stop],
+                    self.plan.write_bytes,
+                    |byte| *byte = byte.wrapping_add(1),
+                    &mut self.first_write_ns,
+                );
+            } else {
+                touch_pages(
+                    self.data,
+                    self.plan.page,
+                    &order[self.touched..stop],
+                    self.plan.write_bytes,
+                );
+            }
+            if self.touched > 0 {
+                self.touch_ms += start.elapsed().as_secs_f64() * 1000.0;
+            }
+            self.touch_ended_at = Some(Timestamp::now());
+            self.touched = stop;
+            self.progress.update(stop);
+            if stop.is_multiple_of(series_step) || stop == order.len() {
+                self.report_series(stop);
+            }
+            if self.plan.rounds > 1 && (stop.is_multiple_of(slice) || stop == order.len()) {
+                let before = FaultBaseline::now(pid);
+                let errors = &mut self.errors;
+                rounds.push(Record::Round(Round {
+                    pages_touched: stop,
+                    rss_kb: checked(errors, "rss_kb", read_rss_kb(pid)),
+                    private_dirty_kb: checked(
+                        errors,
+                        "private_dirty_kb",
+                        read_private_dirty_kb(pid),
+                    ),
+                    buffer_private_dirty_kb: checked(
+                        errors,
+                        "buffer",
+                        read_buffer_usage(pid, self.data),
+                    )
+                    .private_dirty_kb,
+                    touch_ms: self.touch_ms,
+                }));
+                self.baseline.skip(before, FaultBaseline::now(pid));
+            }
+        }
+        if self.touched == order.len() {
+            if let Some(series) = self.plan.series.take() {
+                series.finish();
+            }
+        }
+        rounds
+    }
+
+    /// Writes the page at `index` on its own, counting the faults the write
+    /// took. Only the write is timed, not the fault counters around it.
+    fn write_first(&mut self, index: usize) -> FirstWrite {
+        let mut ns = Vec::with_capacity(1);
+        let before = fault_counts();
+        time_page_writes(
+            self.data,
+            self.plan.page,
+            &[index],
+            self.plan.write_bytes,
+            |byte| *byte = byte.wrapping_add(1),
+            &mut ns,
+        );
+        let after = fault_counts();
+        let ns = ns.first().copied().unwrap_or(0);
+        if self.plan.latency {
+            self.first_write_ns.push(ns);
+        }
+        FirstWrite {
+            ns,
+            minor_faults: after.0.saturating_sub(before.0),
+            major_faults: after.1.saturating_sub(before.1),
+        }
+    }
+
+    /// Under `--dirty-series`, reports `pages_touched` and waits while the
+    /// parent measures; stops reporting once that fails.
+    fn report_series(&mut self, pages_touched: usize) {
+        let Some(series) = self.plan.series else {
+            return;
+        };
+        if let Err(err) = series.report(pages_touched) {
+            self.errors.push(format!("dirty series: {err}"));
+            self.plan.series = None;
+        }
+    }
+
+    /// The first-write times so far, against those of writing every touched
+    /// page again with the bytes it already holds. The rewrites are left out
+    /// of the stage's faults and touch time.
+    fn latency(&mut self) -> WriteLatency {
+        let pid = std::process::id();
+        let before = FaultBaseline::now(pid);
+        let mut rewrite_ns = Vec::with_capacity(self.touched);
+        time_page_writes(
+            self.data,
+            self.plan.page,
+            &self.plan.order[..self.touched],
+            self.plan.write_bytes,
+            |byte| *byte = std::hint::black_box(*byte),
+            &mut rewrite_ns,
+        );
+        self.baseline.skip(before, FaultBaseline::now(pid));
+        WriteLatency {
+            first: LatencyStats::of(&mut self.first_write_ns),
+            rewrite: LatencyStats::of(&mut rewrite_ns),
+        }
+    }
+
+    /// The stage for `checkpoint`, with faults and touch time counted since
+    /// `post_fork`.
+    fn stage(&mut self, checkpoint: Checkpoint) -> ChildStage {
+        let pid = std::process::id();
+        let now = self.faults_now(pid);
+        let mut stage = self.figures(pid, checkpoint);
+        self.count_faults(&mut stage, now);
+        stage.touch_ms = self.touch_ms;
+        stage.touched_bytes = (self.touched * self.plan.page) as u64;
+        stage.first_write = self.first_write;
+        stage.touch_started_at = self.touch_started_at;
+        stage.touch_ended_at = self.touch_ended_at;
+        if self.plan.soft_dirty {
+            stage.soft_dirty_pages = count_soft_dirty(pid, self.data)
+                .map_err(|err| self.errors.push(format!("soft_dirty: {err}")))
+                .ok();
+        }
+        self.baseline.skip(now, FaultBaseline::now(pid));
+        stage
+    }
+
+    /// Fills in the faults taken between the baseline and `now`.
+    fn count_faults(&self, stage: &mut ChildStage, now: FaultBaseline) {
+        let stat = now.stat.since(self.baseline.stat);
+        stage.minor_faults = now.rusage.0.saturating_sub(self.baseline.rusage.0);
+        stage.major_faults = now.rusage.1.saturating_sub(self.baseline.rusage.1);
+        stage.stat_min_flt = stat.min_flt;
+        stage.stat_maj_flt = stat.maj_flt;
+    }
+
+    fn faults_now(&mut self, pid: u32) -> FaultBaseline {
+        FaultBaseline {
+            rusage: checked(&mut self.errors, "getrusage", sys::fault_counts()),
+            stat: checked(&mut self.errors, "stat", read_stat_faults(pid)),
+        }
+    }
+
+    /// The stage for `checkpoint` with every figure read except the fault
+    /// counts and timing, which are left at zero.
+    fn figures(&mut self, pid: u32, checkpoint: Checkpoint) -> ChildStage {
+        let peer = self.peer.pid;
+        let measured_at = Timestamp::now();
+        let errors = &mut self.errors;
+        ChildStage {
+            stage: checkpoint.label().to_string(),
+            rss_kb: checked(errors, "rss_kb", read_rss_kb(pid)),
+            private_dirty_kb: checked(errors, "private_dirty_kb", read_private_dirty_kb(pid)),
+            shared_dirty_kb: checked(errors, "shared_dirty_kb", read_shared_dirty_kb(pid)),
+            shared_clean_kb: checked(errors, "shared_clean_kb", read_shared_clean_kb(pid)),
+            pss_kb: checked(errors, "pss_kb", read_pss_kb(pid)),
+            missing_pages: 0,
+            zeroed_pages: 0,
+            parent_private_dirty_kb: checked(
+                errors,
+                "parent_private_dirty_kb",
+                read_private_dirty_kb(peer),
+            ),
+            parent_shared_dirty_kb: checked(
+                errors,
+                "parent_shared_dirty_kb",
+                read_shared_dirty_kb(peer),
+            ),
+            parent_shared_clean_kb: checked(
+                errors,
+                "parent_shared_clean_kb",
+                read_shared_clean_kb(peer),
+            ),
+            parent_pss_kb: checked(errors, "parent_pss_kb", read_pss_kb(peer)),
+            anon_huge_kb: checked(errors, "anon_huge_kb", read_anon_huge_kb(pid)),
+            hugetlb_kb: checked(errors, "hugetlb_kb", read_hugetlb_kb(pid)),
+            locked_kb: checked(errors, "locked_kb", read_locked_kb(pid)),
+            swap_kb: checked(errors, "swap_kb", read_swap_kb(pid)),
+            buffer: checked(errors, "buffer", read_buffer_usage(pid, self.data)),
+            frames: compare_frames(pid, self.peer, self.data).ok(),
+            nodes: if self.plan.numa.is_some() {
+                checked(errors, "numa_maps", read_buffer_nodes(pid, self.data))
+            } else {
+                NodeUsage::default()
+            },
+            soft_dirty_pages: None,
+            minor_faults: 0,
+            major_faults: 0,
+            stat_min_flt: 0,
+            stat_maj_flt: 0,
+            touch_ms: 0.0,
+            touched_bytes: 0,
+            first_write: None,
+            measured_at,
+            touch_started_at: None,
+            touch_ended_at: None,
+            collected_at: None,
+        }
+    }
+}
+
+/// The value of a measurement, or its default with the failure noted in
+/// `errors`. Measurements this system does not support at all (such as
+/// smaps on macOS) were announced at startup and are not noted again.
+fn checked<T: Default>(errors: &mut Vec<String>, what: &str, result: io::Result<T>) -> T {
+    result.unwrap_or_else(|err| {
+        if err.kind() != io::ErrorKind::Unsupported {
+            errors.push(format!("{what}: {err}"));
+        }
+        T::default()
+    })
+}
+
+/// Measures the calling process before and after touching `data` in one go
+/// and returns the records of both stages. Fault counts in the first stage
+/// are taken relative to `baseline`.
+fn measure_touch(
+    data: &mut [u8],
+    plan: TouchPlan,
+    baseline: FaultBaseline,
+    peer: Peer,
+) -> Vec<Record> {
+    let mut toucher = Toucher::new(data, plan, baseline, peer);
+    let mut records = toucher.reach(Checkpoint::PostFork);
+    records.extend(toucher.reach(Checkpoint::PostWrite));
+    records
+}
+
+/// Starts a fresh copy of this program that allocates its own buffer of the
+/// same size and runs the child's touch workload on it, answering checkpoints
+/// on `channel` exactly as a forked child would.
+fn spawn_helper(size_mb: usize, config: &Config, channel: &Channel) -> Result<i32, Error> {
+    let exe = env::current_exe().map_err(Error::sys("locating own executable"))?;
+    let descriptors = channel.inherit().map_err(Error::sys("fcntl"))?;
+    let mut args = vec![
+        exe.to_string_lossy().into_owned(),
+        "--spawn-helper".into(),
+        descriptors,
+        "--sizes".into(),
+        size_mb.to_string(),
+        "--alloc".into(),
+        config.alloc.label().into(),
+        "--touch-order".into(),
+        config.touch_order.label().into(),
+        "--seed".into(),
+        config.seed.to_string(),
+        "--stride".into(),
+        config.stride.to_string(),
+        "--rounds".into(),
+        config.rounds.to_string(),
+        "--write-size".into(),
+        config.write_size.arg(),
+    ];
+    if config.latency {
+        args.push("--latency".into());
+    }
+    if config.soft_dirty {
+        args.push("--soft-dirty".into());
+    }
+    if let Some(discard) = config.discard {
+        args.push("--discard".into());
+        args.push(discard.arg().into());
+    }
+    if let Some(thp) = config.thp {
+        args.push("--thp".into());
+        args.push(thp.label().into());
+    }
+    args.into_iter()
+        .map(|arg| CString::new(arg).map_err(io::Error::from))
+        .collect::<io::Result<Vec<_>>>()
+        .and_then(|args| sys::spawn(&args))
+        .map_err(Error::sys("posix_spawn"))
+}
+
+/// Entry point of a `--spawn-helper` process: the same touch workload as a
+/// forked child, but on a freshly allocated (never filled) buffer, since a
+/// spawned process inherits none of the parent's memory.
+fn run_spawn_helper(config: &Config, channel: Channel) -> ! {
+    let size_bytes = config.sizes_mb[0] * 1024 * 1024;
+    let mut buffer = match Buffer::allocate(config.alloc, size_bytes) {
+        Ok(buffer) => buffer,
+        Err(err) => {
+            eprintln!("spawn helper failed to allocate its buffer: {err}");
+            sys::exit_now(1)
+        }
+    };
+    let data = buffer.as_mut_slice();
+    let page = touch_page_size(config.alloc);
+    if let Some(thp) = config.thp {
+        if let Err(err) = thp.apply(data, page) {
+            eprintln!("spawn helper madvise failed: {err}");
+        }
+    }
+    let order = page_order(
+        data.len().div_ceil(page),
+        config.touch_order,
+        config.seed,
+        config.stride,
+    );
+    let plan = TouchPlan {
+        page,
+        order: &order,
+        write_bytes: config.write_size.bytes(page),
+        rounds: config.rounds,
+        latency: config.latency,
+        soft_dirty: config.soft_dirty,
+        gate: None,
+        verify: false,
+        numa: None,
+        series: None,
+        discard: config.discard,
+    };
+    child_routine(data, vec![channel], plan, None, false);
+}
+
+/// Reaps `pid`, first killing it if it is still running at `deadline`, and
+/// returns how it ended.
+fn wait_child(pid: i32, deadline: Option<Instant>) -> Result<ExitStatus, Error> {
+    let Some(deadline) = deadline else {
+        return sys::wait_pid(pid)
+            .map(ExitStatus::from_raw)
+            .map_err(Error::sys(format!("waitpid for child {pid}")));
+    };
+    loop {
+        let waited = sys::try_wait_pid(pid).map_err(Error::sys(format!("waitpid for child {pid}")));
+        if let Some(status) = waited? {
+            return Ok(ExitStatus::from_raw(status));
+        }
+        if Instant::now() >= deadline {
+            let _ = sys::send_signal(pid, sys::SIGKILL);
+            let _ = sys::wait_pid(pid);
+            return Err(Error::Child(format!(
+                "child {pid} was still running at the --child-timeout and was killed"
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Steps every child through the checkpoints together and returns each
+/// child's replies along with the parent's own figures at every checkpoint.
+/// The children wait after answering, so the parent's figures are read at the
+/// same moment as theirs. `during_touch` runs once the children have been
+/// told to start touching, and `at_checkpoint` with the parent's figures as
+/// each checkpoint is answered. Fails if a child has not answered by
+/// `deadline`.
+fn step_children(
+    channels: &mut [Channel],
+    checkpoints: &[Checkpoint],
+    data: &mut [u8],
+    parent_pid: u32,
+    deadline: Option<Instant>,
+    mut during_touch: impl FnMut(&mut [u8]) -> Result<(), Error>,
+    mut at_checkpoint: impl FnMut(&ParentCheckpoint),
+) -> Result<(Vec<Vec<Record>>, Vec<ParentCheckpoint>), Error> {
+    let mut replies: Vec<Vec<Record>> = channels.iter().map(|_| Vec::new()).collect();
+    let mut parent = Vec::with_capacity(checkpoints.len());
+    for &checkpoint in checkpoints {
+        for channel in channels.iter() {
+            channel.ask(checkpoint)?;
+        }
+        if checkpoint == Checkpoint::MidTouch {
+            during_touch(data)?;
+        }
+        for (channel, reply) in channels.iter_mut().zip(&mut replies) {
+            reply.extend(channel.reply_to(checkpoint, deadline)?);
+        }
+        let figures = ParentCheckpoint::read(checkpoint, parent_pid, data);
+        at_checkpoint(&figures);
+        parent.push(figures);
+    }
+    Ok((replies, parent))
+}
+
+/// The parent's own writes under `--parent-write`, measured like a child's.
+fn run_parent_write(
+    data: &mut [u8],
+    plan: TouchPlan,
+    mode: ParentWrite,
+    parent_pid: u32,
+) -> Result<ChildRun, Error> {
+    let records = measure_touch(
+        data,
+        plan,
+        FaultBaseline::now(parent_pid),
+        // Comparing the parent's frames with its own says nothing.
+        Peer {
+            pid: parent_pid,
+            same_layout: false,
+        },
+    );
+    let run = collect_run(records)?;
+    let (post_fork, post_write) = (&run.post_fork, &run.post_write);
+    println!(
+        "Parent writes ({} pages, {} of them): RSS {} -> {} kB, Private_Dirty {} -> {} kB, \
+Shared_Dirty {} -> {} kB, {} minor faults, touch {:.3} ms",
+        mode.label(),
+        plan.order.len(),
+        post_fork.rss_kb,
+        post_write.rss_kb,
+        post_fork.private_dirty_kb,
+        post_write.private_dirty_kb,
+        post_fork.shared_dirty_kb,
+        post_write.shared_dirty_kb,
+        post_write.minor_faults,
+        post_write.touch_ms
+    );
+    print_rounds("Parent", &run.rounds);
+    if let Some(latency) = run.latency {
+        print_latency("Parent", latency);
+    }
+    Ok(run)
+}
+
+/// Sets the pages a child wrote against the pages it copied, from its
+/// buffer mapping's Private_Dirty, and fits a line through them: a slope of
+/// 1 is one copy per page written.
+fn print_dirty_series(label: &str, series: &[DirtySample], page: usize) {
+    let Some(first) = series.first() else {
+        return;
+    };
+    let page_kb = (page / 1024).max(1) as f64;
+    let points: Vec<(f64, f64)> = series
+        .iter()
+        .map(|sample| {
+            (
+                (sample.pages_touched - first.pages_touched) as f64,
+                (sample.buffer_private_dirty_kb as f64 - first.buffer_private_dirty_kb as f64)
+                    / page_kb,
+            )
+        })
+        .collect();
+    let slope = LinearFit::of(&points).map(|fit| fit.slope);
+    println!(
+        "{label} dirty series: {} samples; pages copied per page written {}",
+        series.len(),
+        slope.map_or_else(|| "n/a".to_string(), |slope| format!("{slope:.3}"))
+    );
+    // A handful of evenly spaced points; the full series is in the output.
+    let shown = 5.min(series.len());
+    for index in 0..shown {
+        let at = if shown > 1 {
+            index * (series.len() - 1) / (shown - 1)
+        } else {
+            0
+        };
+        let (written, copied) = points[at];
+        println!(
+            "  {:>10} pages written -> {:>10.0} pages copied (buffer Private_Dirty {} kB, \
+Private_Dirty {} kB, {:.3} ms)",
+            written,
+            copied,
+            series[at].buffer_private_dirty_kb,
+            series[at].private_dirty_kb,
+            series[at].elapsed_ms
+        );
+    }
+}
+
+fn print_rounds(label: &str, rounds: &[Round]) {
+    for (index, round) in rounds.iter().enumerate() {
+        println!(
+            "{label} round {}/{}: {} pages touched, RSS {} kB, Private_Dirty {} kB \
+(buffer {} kB), touch {:.3} ms",
+            index + 1,
+            rounds.len(),
+            round.pages_touched,
+            round.rss_kb,
+            round.private_dirty_kb,
+            round.buffer_private_dirty_kb,
+            round.touch_ms
+        );
+    }
+}
+
+fn print_latency(label: &str, latency: WriteLatency) {
+    let (first, rewrite) = (latency.first, latency.rewrite);
+    println!(
+        "{label} write latency per page: first write p50 {} / p90 {} / p99 {} / max {} ns; \
+rewrite p50 {} / p90 {} / p99 {} / max {} ns",
+        first.p50_ns,
+        first.p90_ns,
+        first.p99_ns,
+        first.max_ns,
+        rewrite.p50_ns,
+        rewrite.p90_ns,
+        rewrite.p99_ns,
+        rewrite.max_ns
+    );
+    let used = |bucket: &usize| first.buckets[*bucket] + rewrite.buckets[*bucket] > 0;
+    let (Some(low), Some(high)) = (
+        (0..LATENCY_BUCKETS).find(used),
+        (0..LATENCY_BUCKETS).rev().find(used),
+    ) else {
+        return;
+    };
+    let most = first.count().max(rewrite.count()).max(1);
+    let bar = |count: u64| "#".repeat((count * 30).div_ceil(most) as usize);
+    println!("{label} latency histogram (ns): first writes | rewrites");
+    for bucket in low..=high {
+        println!(
+            "  {:>10}-{:<10} {:>8} {:<30} | {:>8} {}",
+            if bucket == 0 { 0 } else { 1u64 << bucket },
+            (1u64 << (bucket + 1)) - 1,
+            first.buckets[bucket],
+            bar(first.buckets[bucket]),
+            rewrite.buckets[bucket],
+            bar(rewrite.buckets[bucket])
+        );
+    }
+}
+
+fn print_run(label: &str, started: &str, run: &ChildRun, write_bytes: usize, alloc: AllocKind) {
+    let (post_fork, post_write) = (&run.post_fork, &run.post_write);
+    println!("{label} {started}(): {:.1} us", run.fork_us);
+    if let Some(exit) = run.exit.filter(|exit| !exit.success()) {
+        eprintln!("Warning: {label} ended abnormally ({exit})");
+    }
+    for error in &run.errors {
+        eprintln!("Warning: {label} measurement failed and reads as 0: {error}");
+    }
+    println!(
+        "{label} after {started}: RSS {} kB, Private_Dirty {} kB, AnonHugePages {} kB",
+        post_fork.rss_kb, post_fork.private_dirty_kb, post_fork.anon_huge_kb
+    );
+    if let Some(read) = &run.post_read {
+        let frames = read.frames.map_or_else(String::new, |frames| {
+            format!(
+                ", page frames {} shared / {} copied",
+                frames.shared, frames.copied
+            )
+        });
+        println!(
+            "{label} after reading every page: RSS {} kB, Private_Dirty {} kB, {} minor / {} major \
+faults, read {:.3} ms{frames}",
+            read.rss_kb, read.private_dirty_kb, read.minor_faults, read.major_faults, read.touch_ms
+        );
+    }
+    if let Some((read, write)) = run.passes() {
+        println!(
+            "{label} read pass vs write pass: reading took {} fault(s) and copied {} kB in {:.3} ms; \
+writing took {} fault(s) and copied {} kB in {:.3} ms",
+            read.faults, read.copied_kb, read.ms, write.faults, write.copied_kb, write.ms
+        );
+    }
+    if let Some(mid) = &run.mid_touch {
+        println!(
+            "{label} halfway through touching: RSS {} kB, Private_Dirty {} kB, {} minor faults, \
+touch {:.3} ms",
+            mid.rss_kb, mid.private_dirty_kb, mid.minor_faults, mid.touch_ms
+        );
+    }
+    println!(
+        "{label} after touching pages: RSS {} kB, Private_Dirty {} kB, AnonHugePages {} kB \
+(touch {:.3} ms, {} byte(s) per page, {:.1} MB/s dirtied)",
+        post_write.rss_kb,
+        post_write.private_dirty_kb,
+        post_write.anon_huge_kb,
+        post_write.touch_ms,
+        write_bytes,
+        post_write.touch_mb_per_s()
+    );
+    if let (Some(stage), Some(discard)) = (&run.post_discard, run.discard) {
+        println!(
+            "{label} after {} on {} kB of written pages: RSS {} kB, Private_Dirty {} kB, \
+LazyFree {} kB (RSS {} kB, Private_Dirty {} kB after touching)",
+            discard.advice.label(),
+            discard.discarded_bytes / 1024,
+            stage.rss_kb,
+            stage.private_dirty_kb,
+            discard.lazy_free_kb,
+            post_write.rss_kb,
+            post_write.private_dirty_kb
+        );
+        println!(
+            "{label} reading the discarded pages back: {} still held the writes, {} read as zero, \
+{} showed other contents; {} fault(s), then RSS {} kB, Private_Dirty {} kB",
+            discard.kept_pages,
+            discard.zeroed_pages,
+            discard.other_pages,
+            discard.reread_faults,
+            discard.reread_rss_kb,
+            discard.reread_private_dirty_kb
+        );
+    }
+    if let Some(first) = post_write.first_write {
+        let later = post_write
+            .later_write_ns(touch_page_size(alloc))
+            .map_or_else(String::new, |later| {
+                format!(
+                    "; the pages after it averaged {:.2} us each (first write {:.1}x that)",
+                    later / 1000.0,
+                    first.ns as f64 / later.max(1.0)
+                )
+            });
+        println!(
+            "{label} first write after {started}: {:.2} us, {} minor / {} major fault(s){later}",
+            first.ns as f64 / 1000.0,
+            first.minor_faults,
+            first.major_faults
+        );
+    }
+    print_rounds(label, &run.rounds);
+    if let Some(latency) = run.latency {
+        print_latency(label, latency);
+    }
+    if post_fork.missing_pages > 0 || post_fork.zeroed_pages > 0 {
+        println!(
+            "{label} inherited the buffer with {} page(s) missing and {} page(s) zero-filled",
+            post_fork.missing_pages, post_fork.zeroed_pages
+        );
+    }
+    println!(
+        "{label} dirty pages: Shared_Dirty {} kB after {started} / {} kB after touching; \
+parent Private_Dirty {} kB / Shared_Dirty {} kB after {started}, {} kB / {} kB after touching",
+        post_fork.shared_dirty_kb,
+        post_write.shared_dirty_kb,
+        post_fork.parent_private_dirty_kb,
+        post_fork.parent_shared_dirty_kb,
+        post_write.parent_private_dirty_kb,
+        post_write.parent_shared_dirty_kb
+    );
+    println!(
+        "{label} proportional share: Pss {} kB (parent {} kB), Shared_Clean {} kB (parent {} kB) \
+after {started}; Pss {} kB (parent {} kB), Shared_Clean {} kB (parent {} kB) after touching",
+        post_fork.pss_kb,
+        post_fork.parent_pss_kb,
+        post_fork.shared_clean_kb,
+        post_fork.parent_shared_clean_kb,
+        post_write.pss_kb,
+        post_write.parent_pss_kb,
+        post_write.shared_clean_kb,
+        post_write.parent_shared_clean_kb
+    );
+    let (before, after) = (post_fork.buffer, post_write.buffer);
+    println!(
+        "{label} buffer mapping: RSS {} kB, Private_Dirty {} kB, Shared_Clean {} kB, \
+Shared_Dirty {} kB after {started}; {} / {} / {} / {} kB after touching",
+        before.rss_kb,
+        before.private_dirty_kb,
+        before.shared_clean_kb,
+        before.shared_dirty_kb,
+        after.rss_kb,
+        after.private_dirty_kb,
+        after.shared_clean_kb,
+        after.shared_dirty_kb
+    );
+    if alloc == AllocKind::File {
+        // Clean pages are still the file's page cache; dirty ones are
+        // anonymous copies.
+        let file_kb = |usage: BufferUsage| usage.shared_clean_kb + usage.private_clean_kb;
+        println!(
+            "{label} file mapping: {} kB file-backed (Shared_Clean {} kB) and {} kB private \
+copies after {started}; {} kB file-backed (Shared_Clean {} kB) and {} kB private copies after \
+touching",
+            file_kb(before),
+            before.shared_clean_kb,
+            before.private_dirty_kb,
+            file_kb(after),
+            after.shared_clean_kb,
+            after.private_dirty_kb
+        );
+    }
+    if let Some(pages) = post_write.soft_dirty_pages {
+        let halfway = run
+            .mid_touch
+            .as_ref()
+            .and_then(|mid| mid.soft_dirty_pages)
+            .map_or_else(String::new, |pages| format!("{pages} halfway, "));
+        println!(
+            "{label} soft-dirty pages written since {started}: {halfway}{pages} after touching \
+({} kB, against buffer Private_Dirty {} kB)",
+            pages * page_size() as u64 / 1024,
+            after.private_dirty_kb
+        );
+    }
+    match (post_fork.frames, post_write.frames) {
+        (Some(before), Some(after)) => println!(
+            "{label} page frames vs parent: {} shared / {} copied after {started}, \
+{} shared / {} copied after touching",
+            before.shared, before.copied, after.shared, after.copied
+        ),
+        _ => println!("{label} page frames vs parent: unavailable"),
+    }
+    let faults = post_write.minor_faults + post_write.major_faults;
+    let per_fault = if faults == 0 {
+        String::new()
+    } else {
+        format!(
+            " ({:.2} us per fault)",
+            post_write.touch_ms * 1000.0 / faults as f64
+        )
+    };
+    println!(
+        "{label} page faults: {} minor / {} major before touching, {} minor / {} major while touching{per_fault}",
+        post_fork.minor_faults, post_fork.major_faults, post_write.minor_faults, post_write.major_faults
+    );
+    println!(
+        "{label} /proc stat faults: {} minor / {} major after {started}, {} minor / {} major while touching",
+        post_fork.stat_min_flt, post_fork.stat_maj_flt, post_write.stat_min_flt, post_write.stat_maj_flt
+    );
+    if post_fork.swap_kb > 0 || post_write.swap_kb > 0 || after.swap_kb > 0 {
+        println!(
+            "{label} swap: VmSwap {} kB after {started}, {} kB after touching; \
+buffer mapping Swap {} kB / {} kB",
+            post_fork.swap_kb, post_write.swap_kb, before.swap_kb, after.swap_kb
+        );
+    }
+    if post_fork.locked_kb > 0 || post_write.locked_kb > 0 {
+        println!(
+            "{label} VmLck: {} kB after {started}, {} kB after touching",
+            post_fork.locked_kb, post_write.locked_kb
+        );
+    }
+    if matches!(alloc, AllocKind::Hugetlb) {
+        println!(
+            "{label} HugetlbPages: {} kB after {started}, {} kB after touching",
+            post_fork.hugetlb_kb, post_write.hugetlb_kb
+        );
+    }
+    if !post_write.nodes.0.is_empty() {
+        println!(
+            "{label} buffer by NUMA node: {} after {started}; {} after touching",
+            post_fork.nodes.summary(),
+            post_write.nodes.summary()
+        );
+    }
+    print_timeline(label, started, run);
+}
+
+/// When each stage of `run` happened, in milliseconds after the fork (or the
+/// first measurement, for runs that were not forked), anchored on both clocks
+/// so the stages can be found in a tracer's or a dashboard's timeline.
+fn print_timeline(label: &str, started: &str, run: &ChildRun) {
+    let (post_fork, post_write) = (&run.post_fork, &run.post_write);
+    let (origin, from) = match run.forked_at {
+        Some(forked_at) => (forked_at, format!("{started}()")),
+        None => (post_fork.measured_at, "the first measurement".to_string()),
+    };
+    let touch = match (post_write.touch_started_at, post_write.touch_ended_at) {
+        (Some(start), Some(end)) => format!(
+            ", touch +{:.3}..+{:.3} ms",
+            start.ms_since(origin),
+            end.ms_since(origin)
+        ),
+        _ => String::new(),
+    };
+    let collected = post_write.collected_at.map_or_else(String::new, |at| {
+        format!(", report collected +{:.3} ms", at.ms_since(origin))
+    });
+    println!(
+        "{label} timeline from {from} at {} (monotonic {} ns): post_fork measured +{:.3} ms{touch}, post_write measured +{:.3} ms{collected}",
+        origin.utc(),
+        origin.monotonic_ns,
+        post_fork.measured_at.ms_since(origin),
+        post_write.measured_at.ms_since(origin)
+    );
+}
+
+/// Runs the child's touch workload on a thread of this process instead: the
+/// thread shares the buffer outright, so its writes copy nothing.
+/// How the `--concurrent` children fared writing at the same time: the spread
+/// of their touch times, and their combined dirtying rate against each one's.
+fn print_concurrent(children: &[ChildRun]) {
+    let times: Vec<f64> = children
+        .iter()
+        .map(|child| child.post_write.touch_ms)
+        .collect();
+    let listed: Vec<String> = times.iter().map(|ms| format!("{ms:.3}")).collect();
+    println!(
+        "Concurrent touch: {} processes released together at each touch stage; touch times (ms): {}",
+        children.len(),
+        listed.join(", ")
+    );
+    let fastest = times.iter().copied().fold(f64::INFINITY, f64::min);
+    let slowest = times.iter().copied().fold(0.0, f64::max);
+    let mean = times.iter().sum::<f64>() / times.len() as f64;
+    let rate = |bytes: u64, ms: f64| {
+        if ms > 0.0 {
+            bytes as f64 / (1024.0 * 1024.0) / (ms / 1000.0)
+        } else {
+            0.0
+        }
+    };
+    let bytes: u64 = children
+        .iter()
+        .map(|child| child.post_write.touched_bytes)
+        .sum();
+    let mean_rate = children
+        .iter()
+        .map(|child| rate(child.post_write.touched_bytes, child.post_write.touch_ms))
+        .sum::<f64>()
+        / children.len() as f64;
+    println!(
+        "Concurrent touch: min {fastest:.3} / mean {mean:.3} / max {slowest:.3} ms, slowest \
+{:.2}x the fastest; {:.1} MB/s dirtied together, {mean_rate:.1} MB/s per process",
+        if fastest > 0.0 {
+            slowest / fastest
+        } else {
+            0.0
+        },
+        rate(bytes, slowest)
+    );
+}
+
+/// Each generation of the `--depth` fork chains, averaged over the chains:
+/// what it shared right after its fork, and what it copied by touching.
+fn print_generations(children: &[ChildRun], depth: usize) {
+    println!(
+        "== Fork chain of depth {depth} ({} chain(s), mean per generation) ==",
+        children.len() / depth
+    );
+    println!(
+        "{:>10} {:>10} {:>14} {:>22} {:>26} {:>10} {:>10} {:>26}",
+        "generation",
+        "fork_us",
+        "fork RSS kB",
+        "fork Shared_Dirty kB",
+        "touched Private_Dirty kB",
+        "faults",
+        "touch_ms",
+        "frames shared/copied"
+    );
+    for generation in 1..=depth {
+        let runs: Vec<&ChildRun> = children
+            .iter()
+            .filter(|run| run.generation == generation)
+            .collect();
+        let mean = |metric: &dyn Fn(&ChildRun) -> f64| {
+            runs.iter().map(|run| metric(run)).sum::<f64>() / runs.len() as f64
+        };
+        // Against the generation before: the parent for the first.
+        let frames: Vec<FrameSharing> = runs
+            .iter()
+            .filter_map(|run| run.post_write.frames)
+            .collect();
+        let frames = if frames.is_empty() {
+            "unavailable".to_string()
+        } else {
+            let count = frames.len() as u64;
+            format!(
+                "{} / {}",
+                frames.iter().map(|f| f.shared).sum::<u64>() / count,
+                frames.iter().map(|f| f.copied).sum::<u64>() / count
+            )
+        };
+        println!(
+            "{:>10} {:>10.1} {:>14.0} {:>22.0} {:>26.0} {:>10.0} {:>10.3} {:>26}",
+            generation,
+            mean(&|run| run.fork_us),
+            mean(&|run| run.post_fork.rss_kb as f64),
+            mean(&|run| run.post_fork.buffer.shared_dirty_kb as f64),
+            mean(&|run| run.post_write.buffer.private_dirty_kb as f64),
+            mean(&|run| run.post_write.minor_faults as f64),
+            mean(&|run| run.post_write.touch_ms),
+            frames
+        );
+    }
+}
+
+/// FNV-1a over the buffer's 64-bit words (and any trailing bytes), fast
+/// enough to run over multi-gigabyte buffers.
+fn checksum(data: &[u8]) -> u64 {
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+    let words = data.chunks_exact(8);
+    let tail = words.remainder();
+    for word in words {
+        let word = u64::from_le_bytes(word.try_into().expect("eight bytes"));
+        hash = (hash ^ word).wrapping_mul(PRIME);
+    }
+    for &byte in tail {
+        hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// Writes `len` bytes of the fill pattern to `file`.
+fn write_pattern(file: &mut File, len: usize) -> io::Result<()> {
+    // 1 MiB is a whole number of the pattern's 256-byte periods.
+    let chunk: Vec<u8> = (0..1 << 20).map(|i| (i & 0xFF) as u8).collect();
+    let mut left = len;
+    while left > 0 {
+        let bytes = left.min(chunk.len());
+        file.write_all(&chunk[..bytes])?;
+        left -= bytes;
+    }
+    Ok(())
+}
+
+/// The physical address of each of the first `blocks` blocks of `file`,
+/// and whether its extent is flagged shared; holes are `None`.
+fn physical_blocks(file: &File, block: u64, blocks: usize) -> io::Result<Vec<(Option<u64>, bool)>> {
+    let mut map = vec![(None, false); blocks];
+    for extent in sys::file_extents(file.as_fd())? {
+        let shared = extent.flags & sys::FIEMAP_EXTENT_SHARED != 0;
+        let first = extent.logical / block;
+        let last = (extent.logical + extent.length)
+            .div_ceil(block)
+            .min(blocks as u64);
+        for index in first..last {
+            let offset = index * block - extent.logical;
+            map[index as usize] = (Some(extent.physical + offset), shared);
+        }
+    }
+    Ok(map)
+}
+
+fn block_sharing(
+    source: &File,
+    clone: &File,
+    block: u64,
+    blocks: usize,
+) -> io::Result<BlockSharing> {
+    let source = physical_blocks(source, block, blocks)?;
+    let clone = physical_blocks(clone, block, blocks)?;
+    let mut sharing = BlockSharing {
+        shared: 0,
+        diverged: 0,
+        flagged_shared: 0,
+    };
+    for (&(from, _), &(to, flagged)) in source.iter().zip(&clone) {
+        if to.is_some() && to == from {
+            sharing.shared += 1;
+        } else {
+            sharing.diverged += 1;
+        }
+        sharing.flagged_shared += flagged as u64;
+    }
+    Ok(sharing)
+}
+
+/// The filesystem counterpart of the fork experiments (`--fs-cow`): clones a
+/// file of `size_mb` in `dir` with `FICLONE`, writes one byte to the clone's
+/// blocks in the configured touch order, and reports how many blocks stay
+/// shared with the source. Both files are removed afterwards.
+fn run_fs_cow(dir: &Path, size_mb: usize, config: &Config) -> Result<(), Error> {
+    println!(
+        "== Filesystem copy-on-write: FICLONE of a {size_mb} MB file in {} ==",
+        dir.display()
+    );
+    let pid = std::process::id();
+    let source = dir.join(format!("cow-fs-{pid}.source"));
+    let clone = dir.join(format!("cow-fs-{pid}.clone"));
+    let result = clone_and_write(&source, &clone, size_mb, config);
+    let _ = std::fs::remove_file(&source);
+    let _ = std::fs::remove_file(&clone);
+    result
+}
+
+fn clone_and_write(
+    source_path: &Path,
+    clone_path: &Path,
+    size_mb: usize,
+    config: &Config,
+) -> Result<(), Error> {
+    let create = |path: &Path| {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(Error::sys(format!("creating {}", path.display())))
+    };
+    let len = size_mb * 1024 * 1024;
+    let mut source = create(source_path)?;
+    write_pattern(&mut source, len)
+        .and_then(|()| source.sync_all())
+        .map_err(Error::sys(format!("writing {}", source_path.display())))?;
+    let clone = create(clone_path)?;
+
+    let start = Instant::now();
+    sys::clone_file(clone.as_fd(), source.as_fd()).map_err(|e| {
+        Error::sys("FICLONE")(e).explained(
+            "reflinks need btrfs, or XFS with reflink=1, and both files on the same filesystem"
+                .into(),
+        )
+    })?;
+    let clone_us = start.elapsed().as_secs_f64() * 1_000_000.0;
+    let block = clone
+        .metadata()
+        .map_err(Error::sys("stat of the clone"))?
+        .blksize();
+    let blocks = len.div_ceil(block as usize);
+    let extents = |what: &str, sharing: io::Result<BlockSharing>| {
+        sharing.map_err(Error::sys(format!("FIEMAP {what}")))
+    };
+    let before = extents(
+        "after cloning",
+        block_sharing(&source, &clone, block, blocks),
+    )?;
+    println!(
+        "Cloned in {clone_us:.1} us: {} of {blocks} blocks ({block} bytes each) at the source's \
+place on disk, {} flagged shared",
+        before.shared, before.flagged_shared
+    );
+
+    // Each write adds one to the first byte of a block, as a child does to
+    // the first byte of a page.
+    let order = page_order(blocks, config.touch_order, config.seed, config.stride);
+    let start = Instant::now();
+    for &index in &order {
+        let offset = index as u64 * block;
+        let mut byte = [0u8];
+        clone
+            .read_exact_at(&mut byte, offset)
+            .and_then(|()| clone.write_all_at(&[byte[0].wrapping_add(1)], offset))
+            .map_err(Error::sys("writing the clone"))?;
+    }
+    let write_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let start = Instant::now();
+    clone.sync_all().map_err(Error::sys("syncing the clone"))?;
+    let sync_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let after = extents(
+        "after writing",
+        block_sharing(&source, &clone, block, blocks),
+    )?;
+    println!(
+        "Wrote one byte to {} of {blocks} blocks of the clone in {write_ms:.3} ms, synced in \
+{sync_ms:.3} ms: {} blocks still shared, {} diverged, {} flagged shared",
+        order.len(),
+        after.shared,
+        after.diverged,
+        after.flagged_shared
+    );
+
+    // The source must be untouched: its first byte of each block is still 0.
+    let unchanged = order
+        .iter()
+        .filter(|&&index| {
+            let mut byte = [0u8];
+            source
+                .read_exact_at(&mut byte, index as u64 * block)
+                .is_ok()
+                && byte[0] == 0
+        })
+        .count();
+    if unchanged == order.len() {
+        println!(
+            "The source still holds its original bytes in all {} blocks written in the clone",
+            order.len()
+        );
+    } else {
+        eprintln!(
+            "Warning: the source changed in {} of the {} blocks written in the clone",
+            order.len() - unchanged,
+            order.len()
+        );
+    }
+    Ok(())
+}
+
+fn run_thread_baseline(data: &mut [u8], plan: TouchPlan) -> Result<ChildRun, Error> {
+    // The counters are process-wide here, so report them relative to the
+    // moment the thread was started.
+    let pid = std::process::id();
+    let baseline = FaultBaseline::now(pid);
+    let spawned_at = Timestamp::now();
+    let (records, spawn_us) = std::thread::scope(|scope| {
+        let spawn_start = Instant::now();
+        let handle = scope.spawn(move || {
+            let peer = Peer {
+                pid,
+                same_layout: true,
+            };
+            measure_touch(data, plan, baseline, peer)
+        });
+        let spawn_us = spawn_start.elapsed().as_secs_f64() * 1_000_000.0;
+        (handle.join(), spawn_us)
+    });
+    let records = records.map_err(|_| Error::Child("baseline thread panicked".into()))?;
+    Ok(ChildRun {
+        fork_us: spawn_us,
+        forked_at: Some(spawned_at),
+        ..collect_run(records)?
+    })
+}
+
+/// Forks a child that copies the whole buffer into a fresh allocation as
+/// soon as it starts: what copy-on-write would cost if fork copied eagerly.
+fn run_eager_copy(
+    data: &mut [u8],
+    page: usize,
+    deadline: Option<Instant>,
+) -> Result<ChildRun, Error> {
+    let (mut parent_end, child_end) = Channel::pair().map_err(Error::sys("pipe"))?;
+    let forked_at = Timestamp::now();
+    let fork_start = Instant::now();
+    let forked = sys::fork_process().map_err(Error::sys("fork"))?;
+    let fork_us = fork_start.elapsed().as_secs_f64() * 1_000_000.0;
+    let pid = match forked {
+        Fork::Child => {
+            drop(parent_end);
+            eager_copy_routine(data, page, child_end);
+        }
+        Fork::Parent(pid) => pid,
+    };
+    drop(child_end);
+    let reply = parent_end.reply_to(Checkpoint::PostWrite, deadline);
+    drop(parent_end);
+    let status = wait_child(pid, deadline);
+    let records = reply.map_err(|err| match &status {
+        Ok(status) if !status.success() => err.explained(format!("eager copy child: {status}")),
+        _ => err,
+    })?;
+    Ok(ChildRun {
+        fork_us,
+        forked_at: Some(forked_at),
+        exit: Some(status?),
+        ..collect_run(records)?
+    })
+}
+
+/// The eager copy child: measures itself, copies `data`, measures again
+/// while the copy is still alive, and reports both stages in one reply.
+fn eager_copy_routine(data: &mut [u8], page: usize, channel: Channel) -> ! {
+    let plan = TouchPlan {
+        page,
+        order: &[],
+        write_bytes: 0,
+        rounds: 1,
+        latency: false,
+        soft_dirty: false,
+        gate: None,
+        verify: false,
+        numa: None,
+        series: None,
+        discard: None,
+    };
+    let peer = Peer {
+        pid: std::os::unix::process::parent_id(),
+        same_layout: true,
+    };
+    let mut toucher = Toucher::new(data, plan, FaultBaseline::default(), peer);
+    let mut records = toucher.reach(Checkpoint::PostFork);
+    let start = Instant::now();
+    let copy = std::hint::black_box(toucher.data.to_vec());
+    let copy_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let mut copied = toucher.reach(Checkpoint::PostWrite);
+    if let Some(Record::Stage(stage)) = copied.last_mut() {
+        stage.touch_ms = copy_ms;
+        stage.touched_bytes = copy.len() as u64;
+    }
+    drop(copy);
+    records.extend(copied);
+    let status = match channel.send(&report::encode_reply(&records)) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("eager copy child failed to write report: {err}");
+            1
+        }
+    };
+    drop(channel);
+    sys::exit_now(status);
+}
+
+/// Forks a child that execs this program as soon as the parent has measured
+/// it (`--exec`), and measures it again once the new program has started.
+/// Exec replaces the child's address space, so its share of the buffer is
+/// released: its RSS drops to the new program's, and the parent's buffer
+/// pages stop being shared.
+fn run_exec_child(data: &[u8], deadline: Option<Instant>) -> Result<ExecReport, Error> {
+    let exe = env::current_exe().map_err(Error::sys("locating own executable"))?;
+    let (parent_end, child_end) = Channel::pair().map_err(Error::sys("pipe"))?;
+    let argv = [
+        exe.to_string_lossy().into_owned(),
+        "--exec-helper".into(),
+        child_end.inherit().map_err(Error::sys("fcntl"))?,
+    ]
+    .into_iter()
+    .map(|arg| CString::new(arg).map_err(io::Error::from))
+    .collect::<io::Result<Vec<_>>>()
+    .map_err(Error::sys("building the exec arguments"))?;
+    let fork_start = Instant::now();
+    let forked = sys::fork_process().map_err(Error::sys("fork"))?;
+    let fork_us = fork_start.elapsed().as_secs_f64() * 1_000_000.0;
+    let pid = match forked {
+        Fork::Child => {
+            drop(parent_end);
+            let mut release = [0u8];
+            let waited = write_all(child_end.output.as_fd(), b"f")
+                .and_then(|()| sys::read_fd(child_end.input.as_fd(), &mut release));
+            if let Err(err) = waited {
+                eprintln!("exec child failed to reach the parent: {err}");
+                sys::exit_now(1);
+            }
+            let err = sys::exec(&argv);
+            eprintln!("exec child failed to exec {}: {err}", exe.display());
+            sys::exit_now(1);
+        }
+        Fork::Parent(pid) => pid,
+    };
+    drop(child_end);
+    let measured = measure_exec_child(pid as u32, data, &parent_end, deadline);
+    // Closing the pipe lets the new program exit.
+    drop(parent_end);
+    let status = wait_child(pid, deadline);
+    let report = measured.map_err(|err| match &status {
+        Ok(status) if !status.success() => err.explained(format!("exec child: {status}")),
+        _ => err,
+    })?;
+    let status = status?;
+    if !status.success() {
+        return Err(Error::Child(format!(
+            "exec child ended abnormally ({status})"
+        )));
+    }
+    Ok(ExecReport { fork_us, ..report })
+}
+
+fn measure_exec_child(
+    pid: u32,
+    data: &[u8],
+    channel: &Channel,
+    deadline: Option<Instant>,
+) -> Result<ExecReport, Error> {
+    let parent_pid = std::process::id();
+    let await_byte = |what: &str| -> Result<(), Error> {
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let left_ms = left.as_millis().min(i32::MAX as u128) as i32;
+            if !sys::wait_readable(channel.input.as_fd(), left_ms).map_err(Error::sys("poll"))? {
+                return Err(Error::Child(format!(
+                    "the exec child did not {what} by the --child-timeout"
+                )));
+            }
+        }
+        let mut byte = [0u8];
+        match sys::read_fd(channel.input.as_fd(), &mut byte) {
+            Ok(1) => Ok(()),
+            Ok(_) => Err(Error::Child(format!(
+                "the exec child exited before it could {what}"
+            ))),
+            Err(err) => Err(Error::sys("reading from the exec child")(err)),
+        }
+    };
+    await_byte("fork")?;
+    let before_rss_kb = read_rss_kb(pid).map_err(Error::measure("RSS", pid))?;
+    let before_buffer = read_buffer_usage(pid, data).unwrap_or_default();
+    let before_private_dirty_kb = read_private_dirty_kb(pid).unwrap_or(0);
+    let before_shared_dirty_kb = read_shared_dirty_kb(pid).unwrap_or(0);
+    let parent_buffer_before = read_buffer_usage(parent_pid, data).unwrap_or_default();
+
+    let start = Instant::now();
+    write_all(channel.output.as_fd(), b"x").map_err(Error::sys("releasing the exec child"))?;
+    await_byte("exec")?;
+    let exec_us = start.elapsed().as_secs_f64() * 1_000_000.0;
+    Ok(ExecReport {
+        fork_us: 0.0,
+        exec_us,
+        before_rss_kb,
+        before_private_dirty_kb,
+        before_shared_dirty_kb,
+        before_buffer,
+        after_rss_kb: read_rss_kb(pid).map_err(Error::measure("RSS", pid))?,
+        after_private_dirty_kb: read_private_dirty_kb(pid).unwrap_or(0),
+        after_shared_dirty_kb: read_shared_dirty_kb(pid).unwrap_or(0),
+        parent_buffer_before,
+        parent_buffer_after: read_buffer_usage(parent_pid, data).unwrap_or_default(),
+    })
+}
+
+/// Entry point of the program an `--exec` child became: announces itself,
+/// then waits for the parent to close the pipe so it can be measured.
+fn run_exec_helper(channel: Channel) -> ! {
+    if let Err(err) = write_all(channel.output.as_fd(), b"e") {
+        eprintln!("exec helper failed to reach the parent: {err}");
+        sys::exit_now(1);
+    }
+    let mut byte = [0u8];
+    while let Ok(1) = sys::read_fd(channel.input.as_fd(), &mut byte) {}
+    sys::exit_now(0);
+}
+
+/// The exec child against the children that kept the buffer.
+fn print_exec(exec: &ExecReport, children: &[ChildRun]) {
+    println!(
+        "Exec child before exec (fork {:.1} us): RSS {} kB, Private_Dirty {} kB, Shared_Dirty {} kB; \
+buffer mapping RSS {} kB, Shared_Dirty {} kB",
+        exec.fork_us,
+        exec.before_rss_kb,
+        exec.before_private_dirty_kb,
+        exec.before_shared_dirty_kb,
+        exec.before_buffer.rss_kb,
+        exec.before_buffer.shared_dirty_kb
+    );
+    println!(
+        "Exec child after exec ({:.1} us): RSS {} kB, Private_Dirty {} kB, Shared_Dirty {} kB; \
+the buffer is no longer mapped",
+        exec.exec_us, exec.after_rss_kb, exec.after_private_dirty_kb, exec.after_shared_dirty_kb
+    );
+    println!(
+        "Parent buffer mapping: Private_Dirty {} kB / Shared_Dirty {} kB while the exec child \
+shared it, {} kB / {} kB after its exec",
+        exec.parent_buffer_before.private_dirty_kb,
+        exec.parent_buffer_before.shared_dirty_kb,
+        exec.parent_buffer_after.private_dirty_kb,
+        exec.parent_buffer_after.shared_dirty_kb
+    );
+    if let Some(child) = children.first() {
+        println!(
+            "Exec child released {} kB of RSS; the first non-exec child still held {} kB after \
+fork",
+            exec.before_rss_kb.saturating_sub(exec.after_rss_kb),
+            child.post_fork.rss_kb
+        );
+    }
+}
+
+fn print_verification(verification: Verification, children: usize) {
+    println!(
+        "Checksum: parent buffer {:#018x} before fork, {:#018x} after the children finished: {}",
+        verification.parent_before,
+        verification.parent_after,
+        verification.status()
+    );
+    if !verification.parent_ok() {
+        eprintln!("Warning: the parent's view of its private buffer changed across the fork");
+    }
+    if verification.children_checked == 0 {
+        return;
+    }
+    println!(
+        "Checksum: {} of {} children saw the parent's pre-fork contents before writing{}",
+        verification.children_matched,
+        verification.children_checked,
+        if verification.children_checked < children {
+            " (spawned children have no inherited buffer to check)"
+        } else {
+            ""
+        }
+    );
+    if verification.children_matched < verification.children_checked {
+        eprintln!(
+            "Warning: {} children saw different contents before writing",
+            verification.children_checked - verification.children_matched
+        );
+    }
+}
+
+/// The eager copy against the children's lazy copies: how much memory and
+/// time copy-on-write spent for what they touched, relative to copying
+/// everything up front.
+fn print_eager_copy(eager: &ChildRun, children: &[ChildRun]) {
+    let (before, after) = (&eager.post_fork, &eager.post_write);
+    let eager_kb = after
+        .private_dirty_kb
+        .saturating_sub(before.private_dirty_kb);
+    println!(
+        "Eager copy fork(): {:.1} us; copied the whole buffer in {:.3} ms ({:.1} MB/s), \
+{} minor / {} major faults; RSS {} -> {} kB, Private_Dirty {} -> {} kB",
+        eager.fork_us,
+        after.touch_ms,
+        after.touch_mb_per_s(),
+        after.minor_faults,
+        after.major_faults,
+        before.rss_kb,
+        after.rss_kb,
+        before.private_dirty_kb,
+        after.private_dirty_kb
+    );
+    if children.is_empty() || eager_kb == 0 || after.touch_ms <= 0.0 {
+        return;
+    }
+    let count = children.len() as f64;
+    let lazy_kb = children
+        .iter()
+        .map(|child| {
+            child
+                .post_write
+                .private_dirty_kb
+                .saturating_sub(child.post_fork.private_dirty_kb) as f64
+        })
+        .sum::<f64>()
+        / count;
+    let lazy_ms = children
+        .iter()
+        .map(|child| child.post_write.touch_ms)
+        .sum::<f64>()
+        / count;
+    println!(
+        "Lazy copy-on-write per child: {lazy_kb:.0} kB of Private_Dirty in {lazy_ms:.3} ms, \
+{:.0}% of the eager copy's {eager_kb} kB and {:.0}% of its {:.3} ms",
+        lazy_kb * 100.0 / eager_kb as f64,
+        lazy_ms * 100.0 / after.touch_ms,
+        after.touch_ms
+    );
+}
+
+fn run_experiment(
+    size_mb: usize,
+    iteration: Iteration,
+    config: &Config,
+    cgroup: Option<&MemoryCgroup>,
+    stream: Option<&JsonLines>,
+) -> Result<ExperimentResult, Error> {
+    let size_bytes = size_mb * 1024 * 1024;
+    let (title, of_runs, run) = match iteration {
+        Iteration::Warmup(run) => (
+            "Warming up",
+            format!(", warmup {} of {}, not recorded", run + 1, config.warmup),
+            run,
+        ),
+        Iteration::Measured(run) if config.repeat > 1 => (
+            "Running",
+            format!(", run {} of {}", run + 1, config.repeat),
+            run,
+        ),
+        Iteration::Measured(run) => ("Running", String::new(), run),
+    };
+    println!(
+        "== {title} Copy-on-Write demo for {size_mb} MB ({} buffer, {} touch order{of_runs}) ==",
+        config.alloc.label(),
+        config.touch_order.label()
+    );
+
+    let parent_pid = std::process::id();
+    // Read first and last, so the system-wide window holds the process's.
+    let vm_start = read_vm_faults().ok();
+    let stat_start = read_stat_faults(parent_pid).unwrap_or_default();
+    let cgroup_start = cgroup.map(MemoryCgroup::events);
+    let mut buffer = Buffer::allocate(config.alloc, size_bytes).map_err(Error::sys(format!(
+        "allocating the {} buffer",
+        config.alloc.label()
+    )))?;
+    let tui = if config.tui {
+        let title = format!(
+            "Copy-on-write live: {size_mb} MB {} buffer, {} touch order",
+            config.alloc.label(),
+            config.touch_order.label()
+        );
+        Tui::start(title, size_mb as u64 * 1024, parent_pid, config.children)
+    } else {
+        None
+    };
+    let data = buffer.as_mut_slice();
+    let page = touch_page_size(config.alloc);
+    if let Some(thp) = config.thp {
+        // Advise before the buffer is populated so the fill can use huge pages.
+        thp.apply(data, page)
+            .map_err(Error::sys(format!("madvise for THP {}", thp.label())))?;
+        println!(
+            "THP: {} (system policy: {})",
+            thp.label(),
+            system_thp_policy()
+        );
+    }
+    if let Some(nodes) = config.numa {
+        // Bound before the fill so every page is allocated on the node.
+        move_to_node(data, page, nodes.buffer).map_err(Error::sys(format!(
+            "binding the buffer to NUMA node {}",
+            nodes.buffer
+        )))?;
+        println!(
+            "NUMA: buffer and parent on node {}, children on node {}",
+            nodes.buffer, nodes.child
+        );
+    }
+    if config.alloc == AllocKind::File {
+        // The file already holds the pattern. Writing would replace the
+        // parent's pages with private copies, so only read them in.
+        let sum = data
+            .iter()
+            .step_by(page)
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        std::hint::black_box(sum);
+        println!(
+            "File buffer: a {size_mb} MB temporary file in {} mapped MAP_PRIVATE, read but not \
+written by the parent",
+            env::temp_dir().display()
+        );
+    } else {
+        // The pattern repeats every 256 bytes, so every page holds the same
+        // contents and KSM can merge all of them.
+        let progress = Progress::new(
+            format!("Filling the {size_mb} MB buffer"),
+            data.len(),
+            data.len(),
+        );
+        let step = progress.step();
+        for (index, chunk) in data.chunks_mut(step).enumerate() {
+            let offset = index * step;
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = ((offset + i) & 0xFF) as u8;
+            }
+            progress.update(offset + chunk.len());
+        }
+    }
+    if config.mlock {
+        lock_buffer(data)?;
+    }
+    let ksm = if config.ksm {
+        let report = merge_with_ksm(data, page)?;
+        println!(
+            "KSM merged {} of {} buffer pages after {:.0} ms; system-wide {} KSM page(s) \
+back {} more mapping(s), saving {} kB",
+            report.merging_pages,
+            data.len() / page,
+            report.wait_ms,
+            report.pages_shared,
+            report.pages_sharing,
+            report.pages_sharing * page as u64 / 1024
+        );
+        Some(report)
+    } else {
+        None
+    };
+
+    let parent_rss = read_rss_kb(parent_pid).map_err(Error::measure("RSS", parent_pid))?;
+    let parent_private_dirty = read_private_dirty_kb(parent_pid).unwrap_or(0);
+    let parent_anon_huge = read_anon_huge_kb(parent_pid).unwrap_or(0);
+    let parent_buffer = read_buffer_usage(parent_pid, data).unwrap_or_default();
+
+    let stat_before_fork = read_stat_faults(parent_pid).unwrap_or_default();
+    let parent_fill_faults = stat_before_fork.since(stat_start);
+
+    println!(
+        "Parent RSS before fork: {} kB (Private_Dirty {} kB, AnonHugePages {} kB)",
+        parent_rss, parent_private_dirty, parent_anon_huge
+    );
+    println!(
+        "Parent buffer mapping before fork: RSS {} kB, Private_Dirty {} kB, Private_Clean {} kB, \
+Shared_Clean {} kB, Shared_Dirty {} kB",
+        parent_buffer.rss_kb,
+        parent_buffer.private_dirty_kb,
+        parent_buffer.private_clean_kb,
+        parent_buffer.shared_clean_kb,
+        parent_buffer.shared_dirty_kb
+    );
+    println!(
+        "Parent faults while filling: {} minor / {} major",
+        parent_fill_faults.min_flt, parent_fill_faults.maj_flt
+    );
+    let parent_locked = read_locked_kb(parent_pid).unwrap_or(0);
+    if config.mlock {
+        println!("Parent VmLck before fork: {parent_locked} kB");
+    }
+    let parent_swap = read_swap_kb(parent_pid).unwrap_or(0);
+    let parent_nodes = if config.numa.is_some() {
+        let nodes = read_buffer_nodes(parent_pid, data).unwrap_or_default();
+        println!(
+            "Parent buffer by NUMA node before fork: {}",
+            nodes.summary()
+        );
+        nodes
+    } else {
+        NodeUsage::default()
+    };
+    if parent_swap > 0 || parent_buffer.swap_kb > 0 {
+        println!(
+            "Parent swap before fork: VmSwap {parent_swap} kB, buffer mapping Swap {} kB",
+            parent_buffer.swap_kb
+        );
+    }
+
+    let pages = data.len().div_ceil(page);
+    let order = page_order(pages, config.touch_order, config.seed, config.stride);
+    if config.stride > 1 {
+        println!(
+            "Stride {}: touching {} of {} pages",
+            config.stride,
+            order.len(),
+            pages
+        );
+    }
+    if matches!(config.alloc, AllocKind::Hugetlb) {
+        // A private hugetlb mapping copies into fresh huge pages on write; if
+        // the pool is empty the child is killed with SIGBUS instead.
+        let free = read_meminfo("HugePages_Free").unwrap_or(0) as usize;
+        let needed = order.len() * config.children * config.depth;
+        if free < needed {
+            return Err(Error::Unsupported(format!(
+                "the children need {needed} free huge pages for their copies but only {free} are free"
+            )));
+        }
+    }
+    let write_bytes = config.write_size.bytes(page);
+    let half = pages / 2;
+    let (child_order, parent_order): (Vec<usize>, Vec<usize>) = match config.parent_write {
+        None => (order.clone(), Vec::new()),
+        Some(ParentWrite::Same) => (order.clone(), order.clone()),
+        Some(ParentWrite::Disjoint) => order.iter().partition(|&&index| index < half),
+    };
+
+    // Run before forking: afterwards the parent's own pages are write-protected
+    // too, and its first write to each would take a (copy-free) fault.
+    let thread_baseline = if config.thread_baseline {
+        let plan = TouchPlan {
+            page,
+            order: &order,
+            write_bytes,
+            rounds: config.rounds,
+            latency: config.latency,
+            soft_dirty: config.soft_dirty,
+            gate: None,
+            verify: false,
+            numa: None,
+            series: None,
+            discard: None,
+        };
+        let run = run_thread_baseline(data, plan)?;
+        print_run("Thread baseline", "spawn", &run, write_bytes, config.alloc);
+        Some(run)
+    } else {
+        None
+    };
+
+    // Remember the first byte of every page to tell afterwards which of the
+    // children's writes reached the parent's view of the buffer.
+    let before: Vec<u8> = (0..pages).map(|index| data[index * page]).collect();
+
+    if let Some(advice) = config.fork_advice {
+        advice
+            .apply(data, page)
+            .map_err(Error::sys(format!("madvise({})", advice.label())))?;
+        println!("Fork advice: {} on the buffer", advice.label());
+    }
+
+    let checksum_before = config.verify.then(|| checksum(data));
+
+    // Children still running at the deadline are killed and the experiment
+    // fails, rather than hanging the whole run.
+    let deadline = config.child_timeout.map(|timeout| Instant::now() + timeout);
+
+    // Mapped before forking so every child and generation shares it.
+    let mut gate_memory = if config.concurrent {
+        Some(
+            Buffer::allocate(AllocKind::Shared, page_size())
+                .map_err(Error::sys("mapping the start gate"))?,
+        )
+    } else {
+        None
+    };
+    let gate = gate_memory
+        .as_mut()
+        .map(|memory| StartGate::new(memory.as_mut_slice(), config.children * config.depth));
+
+    // Fork every child before stepping any of them so they all share the
+    // parent's pages at the same time. Each child gets the channels of its
+    // whole fork chain and is stepped through the first; the parent steps
+    // every generation alike.
+    let mut started = Vec::with_capacity(config.children);
+    let mut channels = Vec::with_capacity(config.children * config.depth);
+    let mut samplers = Vec::new();
+    for index in 0..config.children {
+        let mut parent_ends = Vec::with_capacity(config.depth);
+        let mut chain = Vec::with_capacity(config.depth);
+        for _ in 0..config.depth {
+            let (parent_end, child_end) = Channel::pair().map_err(Error::sys("pipe"))?;
+            parent_ends.push(parent_end);
+            chain.push(child_end);
+        }
+        // (report read, report write) and (ack read, ack write).
+        let series_pipes = if config.dirty_series {
+            let pipes = sys::create_pipe().and_then(|report| Ok((report, sys::create_pipe()?)));
+            Some(pipes.map_err(Error::sys("pipe"))?)
+        } else {
+            None
+        };
+
+        let forked_at = Timestamp::now();
+        let fork_start = Instant::now();
+        let forked = match config.spawn {
+            SpawnKind::Fork => sys::fork_process().map_err(Error::sys("fork"))?,
+            SpawnKind::PosixSpawn => Fork::Parent(spawn_helper(size_mb, config, &chain[0])?),
+        };
+        let fork_us = fork_start.elapsed().as_secs_f64() * 1_000_000.0;
+
+        let pid = match forked {
+            Fork::Child => {
+                drop(parent_ends);
+                let child_ends = series_pipes.map(|((_, report), (ack, _))| (report, ack));
+                let series = child_ends.as_ref().map(|(report, ack)| SeriesPipe {
+                    report: report.as_fd(),
+                    ack: ack.as_fd(),
+                });
+                let plan = TouchPlan {
+                    page,
+                    order: &child_order,
+                    write_bytes,
+                    rounds: config.rounds,
+                    latency: config.latency,
+                    soft_dirty: config.soft_dirty,
+                    gate,
+                    verify: config.verify,
+                    numa: config.numa.map(|nodes| nodes.child),
+                    series,
+                    discard: config.discard,
+                };
+                child_routine(data, chain, plan, config.fork_advice, true);
+            }
+            Fork::Parent(pid) => pid,
+        };
+        if let Some(tui) = &tui {
+            let label = if config.children > 1 {
+                format!("Child {index}")
+            } else {
+                "Child".to_string()
+            };
+            tui.watch(label, pid as u32);
+        }
+        if cgroup.is_some() {
+            // Under the limit the OOM killer should take a child, which is
+            // reported as killed, rather than the parent running the show.
+            if let Err(err) = std::fs::write(format!("/proc/{pid}/oom_score_adj"), "1000") {
+                eprintln!("Warning: could not make child {pid} the preferred OOM victim: {err}");
+            }
+        }
+
+        if let Some(((report, _), (_, ack))) = series_pipes {
+            samplers.push(DirtySampler::start(pid as u32, data, report, ack));
+        }
+        drop(chain);
+        started.push((pid, fork_us, forked_at));
+        channels.extend(parent_ends);
+    }
+
+    let mut parent_write = None;
+    let checkpoints: Vec<Checkpoint> = Checkpoint::ALL
+        .into_iter()
+        .filter(|&checkpoint| match checkpoint {
+            Checkpoint::PostRead => config.read_stage,
+            Checkpoint::PostDiscard => config.discard.is_some(),
+            _ => true,
+        })
+        .collect();
+    let stepped = step_children(
+        &mut channels,
+        &checkpoints,
+        data,
+        parent_pid,
+        deadline,
+        |data| {
+            // Write while the children are touching their pages: whichever side
+            // writes a still-shared page first takes the copy.
+            if let Some(mode) = config.parent_write {
+                let plan = TouchPlan {
+                    page,
+                    order: &parent_order,
+                    write_bytes,
+                    rounds: config.rounds,
+                    latency: config.latency,
+                    soft_dirty: config.soft_dirty,
+                    gate: None,
+                    verify: false,
+                    numa: None,
+                    series: None,
+                    discard: None,
+                };
+                parent_write = Some(run_parent_write(data, plan, mode, parent_pid)?);
+            }
+            Ok(())
+        },
+        |figures| {
+            let Some(stream) = stream.filter(|_| config.stream_samples) else {
+                return;
+            };
+            // Read here rather than taken from the replies, which are only
+            // decoded once every checkpoint is through.
+            let children: Vec<String> = started
+                .iter()
+                .map(|&(pid, _, _)| {
+                    let pid = pid as u32;
+                    format!(
+                        "{{\"pid\":{pid},\"rss_kb\":{},\"private_dirty_kb\":{}}}",
+                        read_rss_kb(pid).unwrap_or_default(),
+                        read_private_dirty_kb(pid).unwrap_or_default()
+                    )
+                })
+                .collect();
+            stream.emit(
+                "checkpoint",
+                &format!(
+                    "\"size_mb\":{size_mb},\"run\":{run},\"at\":{},\"parent\":{},\"children\":[{}]}}",
+                    Timestamp::now().to_json(),
+                    figures.to_json(),
+                    children.join(",")
+                ),
+            );
+        },
+    );
+    // The children are still alive, waiting on their channels.
+    let parent_rss_after = read_rss_kb(parent_pid).unwrap_or(0);
+    let parent_private_dirty_after = read_private_dirty_kb(parent_pid).unwrap_or(0);
+    let parent_buffer_after = read_buffer_usage(parent_pid, data).unwrap_or_default();
+    // The last frame shows the children as they finished touching.
+    drop(tui);
+    // Closing the channels lets every child exit, including any still
+    // waiting for a checkpoint after an error.
+    drop(channels);
+    let statuses: Vec<_> = started
+        .iter()
+        .map(|&(pid, ..)| wait_child(pid, deadline))
+        .collect();
+    // Every child has finished its series by now, or exited.
+    let mut dirty_series: Vec<Vec<DirtySample>> =
+        samplers.into_iter().map(DirtySampler::finish).collect();
+    let cgroup_report = cgroup
+        .zip(cgroup_start)
+        .map(|(cgroup, start)| cgroup.events().since(start));
+    let (replies, parent_checkpoints) = stepped.map_err(|err| {
+        // How the children ended usually explains a missing reply, and so
+        // does the cgroup running out of memory.
+        let mut notes: Vec<String> = statuses
+            .iter()
+            .enumerate()
+            .filter_map(|(index, status)| match status {
+                Ok(status) if !status.success() => Some(format!("child {index}: {status}")),
+                _ => None,
+            })
+            .collect();
+        if let Some(report) = cgroup_report {
+            notes.push(format!("cgroup memory.events: {}", report.summary()));
+        }
+        if notes.is_empty() {
+            err
+        } else {
+            err.explained(notes.join(", "))
+        }
+    })?;
+    let statuses = statuses.into_iter().collect::<Result<Vec<_>, _>>()?;
+    let parent_wait_faults = read_stat_faults(parent_pid)
+        .unwrap_or_default()
+        .since(stat_before_fork);
+    let vm_faults = vm_start.and_then(|start| Some(read_vm_faults().ok()?.since(start)));
+
+    let mut children = Vec::with_capacity(replies.len());
+    // Each generation after the first was forked by the one before, which
+    // reported how long that took.
+    let mut forked_next = 0.0;
+    for (index, reply) in replies.into_iter().enumerate() {
+        let (chain, generation) = (index / config.depth, index % config.depth + 1);
+        let label = match (config.children > 1, config.depth > 1) {
+            (false, false) => "Child".to_string(),
+            (true, false) => format!("Child {chain}"),
+            (false, true) => format!("Generation {generation}"),
+            (true, true) => format!("Child {chain} generation {generation}"),
+        };
+        let (fork_us, forked_at, exit) = if generation == 1 {
+            let (_, fork_us, forked_at) = started[chain];
+            (fork_us, Some(forked_at), Some(statuses[chain]))
+        } else {
+            (forked_next, None, None)
+        };
+        forked_next = reply
+            .iter()
+            .find_map(|record| match record {
+                Record::Forked(fork_us) => Some(*fork_us),
+                _ => None,
+            })
+            .unwrap_or(0.0);
+        let series = match dirty_series.get_mut(chain) {
+            Some(series) if generation == 1 => std::mem::take(series),
+            _ => Vec::new(),
+        };
+        let run = ChildRun {
+            fork_us,
+            forked_at,
+            generation,
+            exit,
+            dirty_series: series,
+            ..collect_run(reply)?
+        };
+        print_run(
+            &label,
+            config.spawn.label(),
+            &run,
+            write_bytes,
+            config.alloc,
+        );
+        print_dirty_series(&label, &run.dirty_series, page);
+        children.push(run);
+    }
+    for checkpoint in &parent_checkpoints {
+        let swap = if checkpoint.swap_kb > 0 || checkpoint.buffer.swap_kb > 0 {
+            format!(
+                "; VmSwap {} kB, buffer mapping Swap {} kB",
+                checkpoint.swap_kb, checkpoint.buffer.swap_kb
+            )
+        } else {
+            String::new()
+        };
+        println!(
+            "Parent at {}: RSS {} kB, Private_Dirty {} kB, Shared_Dirty {} kB; \
+buffer mapping Private_Dirty {} kB, Shared_Dirty {} kB{swap}",
+            checkpoint.checkpoint.label(),
+            checkpoint.rss_kb,
+            checkpoint.private_dirty_kb,
+            checkpoint.shared_dirty_kb,
+            checkpoint.buffer.private_dirty_kb,
+            checkpoint.buffer.shared_dirty_kb
+        );
+    }
+    println!(
+        "Parent after collecting the children's reports: RSS {parent_rss_after} kB ({:+} kB since \
+before the fork), Private_Dirty {parent_private_dirty_after} kB ({:+} kB), buffer mapping \
+Private_Dirty {} kB ({:+} kB)",
+        parent_rss_after as i64 - parent_rss as i64,
+        parent_private_dirty_after as i64 - parent_private_dirty as i64,
+        parent_buffer_after.private_dirty_kb,
+        parent_buffer_after.private_dirty_kb as i64 - parent_buffer.private_dirty_kb as i64
+    );
+    println!(
+        "Parent /proc stat faults: {} minor / {} major while the children ran; \
+reaped children total {} minor / {} major",
+        parent_wait_faults.min_flt,
+        parent_wait_faults.maj_flt,
+        parent_wait_faults.cmin_flt,
+        parent_wait_faults.cmaj_flt
+    );
+    if let Some(vm) = vm_faults {
+        let own = [parent_fill_faults, parent_wait_faults]
+            .iter()
+            .map(|faults| faults.min_flt + faults.maj_flt + faults.cmin_flt + faults.cmaj_flt)
+            .sum::<u64>();
+        println!(
+            "System-wide /proc/vmstat over the run: pgfault {}, pgmajfault {}, thp_fault_alloc {}; \
+this process and its children took {own} of the faults ({:.1}%)",
+            vm.pgfault,
+            vm.pgmajfault,
+            vm.thp_fault_alloc,
+            own as f64 * 100.0 / vm.pgfault.max(1) as f64
+        );
+    }
+    if let Some(report) = cgroup_report {
+        println!(
+            "Cgroup memory.max {} MB; memory.events during the run: {}",
+            report.limit_mb,
+            report.summary()
+        );
+    }
+    if config.depth > 1 {
+        print_generations(&children, config.depth);
+    }
+    if config.concurrent {
+        print_concurrent(&children);
+    }
+    // Forked once the children are gone, so it has the buffer to itself.
+    let eager_copy = if config.eager_copy {
+        let run = run_eager_copy(data, page, deadline)?;
+        print_eager_copy(&run, &children);
+        Some(run)
+    } else {
+        None
+    };
+    // Also after the children, so the buffer is shared with it alone.
+    let exec = if config.exec {
+        let report = run_exec_child(data, deadline)?;
+        print_exec(&report, &children);
+        Some(report)
+    } else {
+        None
+    };
+    if children.len() > 1 {
+        // RSS counts shared pages once per process; Private_Dirty only counts
+        // what each child has copied for itself.
+        let sum = |stage: fn(&ChildRun) -> &ChildStage, field: fn(&ChildStage) -> u64| {
+            children
+                .iter()
+                .map(|child| field(stage(child)))
+                .sum::<u64>()
+        };
+        println!(
+            "All {} children: RSS sum {} kB after fork / {} kB after touching, \
+Private_Dirty sum {} kB after fork / {} kB after touching",
+            children.len(),
+            sum(|c| &c.post_fork, |s| s.rss_kb),
+            sum(|c| &c.post_write, |s| s.rss_kb),
+            sum(|c| &c.post_fork, |s| s.private_dirty_kb),
+            sum(|c| &c.post_write, |s| s.private_dirty_kb)
+        );
+    }
+
+    // Each writer added one to the first byte of every page it touched.
+    let mut parent_added = vec![0u8; before.len()];
+    for &index in &parent_order {
+        parent_added[index] = 1;
+    }
+    let mut children_added = vec![0u8; before.len()];
+    for &index in &child_order {
+        children_added[index] = (config.children * config.depth) as u8;
+    }
+    let parent_visible_pages = before
+        .iter()
+        .enumerate()
+        .filter(|&(index, &byte)| data[index * page] != byte.wrapping_add(parent_added[index]))
+        .count();
+    println!(
+        "Parent sees the children's writes in {} of {} pages",
+        parent_visible_pages,
+        before.len()
+    );
+    if config.alloc.is_shared() && matches!(config.spawn, SpawnKind::Fork) {
+        let expected =
+            |index: usize| before[index].wrapping_add(children_added[index] + parent_added[index]);
+        let verified = (0..before.len())
+            .filter(|&index| data[index * page] == expected(index))
+            .count();
+        if verified == before.len() {
+            println!(
+                "Verified: every page of the parent's mapping holds every expected write \
+({} child increment(s) per touched page)",
+                config.children * config.depth
+            );
+        } else {
+            // Concurrent writers increment the same bytes without atomics, so
+            // an update can occasionally be lost.
+            eprintln!(
+                "Warning: only {verified} of {} pages hold every expected write \
+({} child increment(s) per touched page)",
+                before.len(),
+                config.children * config.depth
+            );
+        }
+    }
+
+    let verification = checksum_before.map(|parent_before| {
+        let verification = Verification {
+            parent_before,
+            parent_after: checksum(data),
+            parent_may_change: config.alloc.is_shared() || config.parent_write.is_some(),
+            children_matched: children
+                .iter()
+                .filter(|child| child.checksum == Some(parent_before))
+                .count(),
+            children_checked: children
+                .iter()
+                .filter(|child| child.checksum.is_some())
+                .count(),
+        };
+        print_verification(verification, children.len());
+        verification
+    });
+
+    Ok(ExperimentResult {
+        size_mb,
+        run,
+        alloc: config.alloc,
+        touch_order: config.touch_order,
+        stride: config.stride,
+        write_bytes,
+        thp: config.thp,
+        spawn: config.spawn,
+        depth: config.depth,
+        concurrent: config.concurrent,
+        parent_rss_kb: parent_rss,
+        parent_private_dirty_kb: parent_private_dirty,
+        parent_rss_after_kb: parent_rss_after,
+        parent_private_dirty_after_kb: parent_private_dirty_after,
+        parent_anon_huge_kb: parent_anon_huge,
+        parent_buffer,
+        parent_fill_faults,
+        parent_wait_faults,
+        vm_faults,
+        parent_visible_pages,
+        children,
+        thread_baseline,
+        eager_copy,
+        exec,
+        verification,
+        parent_write,
+        parent_checkpoints,
+        fork_advice: config.fork_advice,
+        discard: config.discard,
+        mlock: config.mlock,
+        parent_locked_kb: parent_locked,
+        parent_swap_kb: parent_swap,
+        ksm,
+        cgroup: cgroup_report,
+        numa: config.numa,
+        parent_nodes,
+    })
+}
+
+impl Stats {
+    fn of(samples: &[f64]) -> Stats {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Stats {
+            mean,
+            stddev: variance.sqrt(),
+            min: samples.iter().copied().fold(f64::INFINITY, f64::min),
+            max: samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+impl LinearFit {
+    /// The fit of `points`, or `None` unless they span more than one `x`.
+    fn of(points: &[(f64, f64)]) -> Option<LinearFit> {
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let spread: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+        if points.len() < 2 || spread <= 0.0 {
+            return None;
+        }
+        let slope = points
+            .iter()
+            .map(|p| (p.0 - mean_x) * (p.1 - mean_y))
+            .sum::<f64>()
+            / spread;
+        let intercept = mean_y - slope * mean_x;
+        let total: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+        let residual: f64 = points
+            .iter()
+            .map(|p| (p.1 - slope * p.0 - intercept).powi(2))
+            .sum();
+        // A flat line through flat data explains all of it.
+        let r_squared = if total > 0.0 {
+            1.0 - residual / total
+        } else {
+            1.0
+        };
+        Some(LinearFit {
+            slope,
+            intercept,
+            r_squared,
+            points: points.len(),
+        })
+    }
+}
+
+/// Fits the touch time and the copied memory of every child against its
+/// buffer size; empty unless `results` cover at least two sizes.
+fn fit_scaling(results: &[ExperimentResult]) -> Vec<ScalingFit> {
+    let copied_kb = |child: &ChildRun| {
+        child
+            .post_write
+            .buffer
+            .private_dirty_kb
+            .saturating_sub(child.post_fork.buffer.private_dirty_kb) as f64
+    };
+    [
+        fit_against_size(results, "touch_ms", "ms", |child| child.post_write.touch_ms),
+        fit_against_size(results, "copied_kb", "kB", copied_kb),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn fit_against_size(
+    results: &[ExperimentResult],
+    metric: &'static str,
+    unit: &'static str,
+    value: fn(&ChildRun) -> f64,
+) -> Option<ScalingFit> {
+    let points: Vec<(f64, f64)> = results
+        .iter()
+        .flat_map(|entry| {
+            entry
+                .children
+                .iter()
+                .map(|child| (entry.size_mb as f64, value(child)))
+        })
+        .collect();
+    LinearFit::of(&points).map(|fit| ScalingFit { metric, unit, fit })
+}
+
+fn print_scaling(fits: &[ScalingFit]) {
+    let Some(first) = fits.first() else {
+        return;
+    };
+    println!(
+        "== Scaling across sizes (least squares over {} children) ==",
+        first.fit.points
+    );
+    for fit in fits {
+        println!(
+            "{}: {:.4} {unit} per MB + {:.4} {unit} (R² {:.4})",
+            fit.metric,
+            fit.fit.slope,
+            fit.fit.intercept,
+            fit.fit.r_squared,
+            unit = fit.unit
+        );
+    }
+    let slope = |metric: &str| {
+        fits.iter()
+            .find(|fit| fit.metric == metric)
+            .map(|fit| fit.fit.slope)
+    };
+    if let (Some(ms_per_mb), Some(kb_per_mb)) = (slope("touch_ms"), slope("copied_kb")) {
+        println!(
+            "Copy cost ≈ {:.1} us per MB of buffer, {:.0} kB copied per MB",
+            ms_per_mb * 1000.0,
+            kb_per_mb
+        );
+    }
+}
+
+fn write_scaling_csv(path: &Path, fits: &[ScalingFit]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "metric,unit,points,slope_per_mb,intercept,r_squared")?;
+    for fit in fits {
+        writeln!(
+            file,
+            "{},{},{},{:.6},{:.6},{:.6}",
+            fit.metric,
+            fit.unit,
+            fit.fit.points,
+            fit.fit.slope,
+            fit.fit.intercept,
+            fit.fit.r_squared
+        )?;
+    }
+    Ok(())
+}
+
+/// Groups `results` by size and aggregates the noisy metrics of each group.
+fn summarize(results: &[ExperimentResult]) -> Vec<SizeSummary> {
+    let mut summaries = Vec::new();
+    let mut sizes: Vec<usize> = Vec::new();
+    for entry in results {
+        if !sizes.contains(&entry.size_mb) {
+            sizes.push(entry.size_mb);
+        }
+    }
+    for size_mb in sizes {
+        let runs: Vec<&ExperimentResult> = results
+            .iter()
+            .filter(|entry| entry.size_mb == size_mb)
+            .collect();
+        let children: Vec<&ChildRun> = runs.iter().flat_map(|entry| &entry.children).collect();
+        let per_child = |metric: fn(&ChildRun) -> f64| -> Vec<f64> {
+            children.iter().map(|child| metric(child)).collect()
+        };
+        let per_run = |metric: fn(&ExperimentResult) -> u64| -> Vec<f64> {
+            runs.iter().map(|entry| metric(entry) as f64).collect()
+        };
+        summaries.push(SizeSummary {
+            size_mb,
+            runs: runs.len(),
+            metrics: vec![
+                (
+                    "touch_ms",
+                    Stats::of(&per_child(|child| child.post_write.touch_ms)),
+                ),
+                (
+                    "touch_mb_per_s",
+                    Stats::of(&per_child(|child| child.post_write.touch_mb_per_s())),
+                ),
+                (
+                    "first_write_us",
+                    Stats::of(&per_child(|child| {
+                        child
+                            .post_write
+                            .first_write
+                            .map_or(0.0, |first| first.ns as f64 / 1000.0)
+                    })),
+                ),
+                ("fork_us", Stats::of(&per_child(|child| child.fork_us))),
+                (
+                    "child_post_write_rss_kb",
+                    Stats::of(&per_child(|child| child.post_write.rss_kb as f64)),
+                ),
+                (
+                    "child_post_write_private_dirty_kb",
+                    Stats::of(&per_child(|child| child.post_write.private_dirty_kb as f64)),
+                ),
+                (
+                    "parent_rss_kb",
+                    Stats::of(&per_run(|entry| entry.parent_rss_kb)),
+                ),
+                (
+                    "parent_rss_after_kb",
+                    Stats::of(&per_run(|entry| entry.parent_rss_after_kb)),
+                ),
+            ],
+        });
+    }
+    summaries
+}
+
+impl PageVariant {
+    /// Page size backing the variant once the kernel gives it what it asked
+    /// for; the `thp` variant falls back to base pages where THP is off.
+    fn page_kb(&self) -> usize {
+        match (self.alloc, self.thp) {
+            (AllocKind::Mmap, Some(ThpMode::Never)) => page_size() / 1024,
+            _ => huge_page_size() / 1024,
+        }
+    }
+
+    fn matches(&self, entry: &ExperimentResult) -> bool {
+        self.alloc == entry.alloc && self.thp == entry.thp
+    }
+}
+
+/// The `PAGE_VARIANTS` this machine can run, noting why any are left out or
+/// will not get the pages they ask for.
+fn available_page_variants() -> Vec<&'static PageVariant> {
+    let policy = system_thp_policy();
+    if policy == "never" {
+        println!("Note: THP is disabled system-wide; the thp variant will use base pages");
+    }
+    let reserved = read_meminfo("HugePages_Total").unwrap_or(0);
+    if reserved == 0 {
+        println!(
+            "Note: no huge pages are reserved (/proc/sys/vm/nr_hugepages); skipping the hugetlb \
+variant"
+        );
+    }
+    PAGE_VARIANTS
+        .iter()
+        .filter(|variant| variant.alloc != AllocKind::Hugetlb || reserved > 0)
+        .collect()
+}
+
+/// One row per size and page variant of a `--compare-pages` run.
+fn compare_page_variants(results: &[ExperimentResult]) -> Vec<PageComparison> {
+    let mut sizes: Vec<usize> = Vec::new();
+    for entry in results {
+        if !sizes.contains(&entry.size_mb) {
+            sizes.push(entry.size_mb);
+        }
+    }
+    let mut rows = Vec::new();
+    for size_mb in sizes {
+        for variant in &PAGE_VARIANTS {
+            let runs: Vec<&ExperimentResult> = results
+                .iter()
+                .filter(|entry| entry.size_mb == size_mb && variant.matches(entry))
+                .collect();
+            let children: Vec<&ChildRun> = runs.iter().flat_map(|entry| &entry.children).collect();
+            if children.is_empty() {
+                continue;
+            }
+            let mean = |metric: fn(&ChildRun) -> f64| -> f64 {
+                children.iter().map(|child| metric(child)).sum::<f64>() / children.len() as f64
+            };
+            let touch: Vec<f64> = children
+                .iter()
+                .map(|child| child.post_write.touch_ms)
+                .collect();
+            let faults = mean(|child| {
+                (child.post_write.minor_faults + child.post_write.major_faults) as f64
+            });
+            let touch_ms = Stats::of(&touch);
+            let parent_anon_huge_kb = runs
+                .iter()
+                .map(|entry| entry.parent_anon_huge_kb as f64)
+                .sum::<f64>()
+                / runs.len() as f64;
+            rows.push(PageComparison {
+                size_mb,
+                variant: variant.label,
+                page_kb: variant.page_kb(),
+                runs: runs.len(),
+                fork_us: mean(|child| child.fork_us),
+                touch_ms,
+                faults,
+                us_per_fault: if faults == 0.0 {
+                    0.0
+                } else {
+                    touch_ms.mean * 1000.0 / faults
+                },
+                private_dirty_kb: mean(|child| child.post_write.private_dirty_kb as f64),
+                huge_kb: mean(|child| {
+                    (child.post_write.anon_huge_kb + child.post_write.hugetlb_kb) as f64
+                }),
+                parent_anon_huge_kb,
+            });
+        }
+    }
+    rows
+}
+
+fn print_page_comparison(rows: &[PageComparison]) {
+    println!("== Page size comparison (mean over every child of every run) ==");
+    println!(
+        "{:>8} {:>8} {:>8} {:>5} {:>10} {:>18} {:>10} {:>8} {:>16} {:>14} {:>18}",
+        "size_mb",
+        "variant",
+        "page_kb",
+        "runs",
+        "fork_us",
+        "touch_ms",
+        "faults",
+        "us/fault",
+        "Private_Dirty kB",
+        "child huge kB",
+        "parent AnonHuge kB"
+    );
+    for row in rows {
+        println!(
+            "{:>8} {:>8} {:>8} {:>5} {:>10.1} {:>18} {:>10.0} {:>8.2} {:>16.0} {:>14.0} {:>18.0}",
+            row.size_mb,
+            row.variant,
+            row.page_kb,
+            row.runs,
+            row.fork_us,
+            format!("{:.3} ± {:.3}", row.touch_ms.mean, row.touch_ms.stddev),
+            row.faults,
+            row.us_per_fault,
+            row.private_dirty_kb,
+            row.huge_kb,
+            row.parent_anon_huge_kb
+        );
+    }
+}
+
+fn print_summary(summaries: &[SizeSummary]) {
+    for summary in summaries {
+        println!(
+            "== Summary for {} MB over {} runs (mean ± stddev, min..max) ==",
+            summary.size_mb, summary.runs
+        );
+        for (name, stats) in &summary.metrics {
+            println!(
+                "{name}: {:.3} ± {:.3} ({:.3}..{:.3})",
+                stats.mean, stats.stddev, stats.min, stats.max
+            );
+        }
+    }
+}
+
+/// Where a companion table of a CSV written to `path` goes: for `summary`,
+/// `results.csv` becomes `results_summary.csv`.
+fn companion_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "results".into(), |stem| stem.to_string_lossy());
+    path.with_file_name(format!("{stem}_{suffix}.csv"))
+}
+
+/// Every run of an experiment, named as in the companion CSVs' `process`
+/// column: `child<N>`, `thread` or `parent`.
+fn process_runs(entry: &ExperimentResult) -> impl Iterator<Item = (String, &ChildRun)> {
+    let children = entry
+        .children
+        .iter()
+        .enumerate()
+        .map(|(index, run)| (format!("child{index}"), run));
+    let others = [
+        ("thread", entry.thread_baseline.as_ref()),
+        ("eager_copy", entry.eager_copy.as_ref()),
+        ("parent", entry.parent_write.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, run)| run.map(|run| (name.to_string(), run)));
+    children.chain(others)
+}
+
+/// One row per `--rounds` measurement of every child, thread baseline, and
+/// parent write.
+fn write_rounds_csv(path: &Path, results: &[ExperimentResult]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "size_mb,run,process,round,pages_touched,rss_kb,private_dirty_kb,buffer_private_dirty_kb,touch_ms"
+    )?;
+    for entry in results {
+        for (process, run) in process_runs(entry) {
+            for (index, round) in run.rounds.iter().enumerate() {
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{},{},{},{:.4}",
+                    entry.size_mb,
+                    entry.run,
+                    process,
+                    index + 1,
+                    round.pages_touched,
+                    round.rss_kb,
+                    round.private_dirty_kb,
+                    round.buffer_private_dirty_kb,
+                    round.touch_ms
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One row per `--dirty-series` sample of every first-generation child.
+fn write_series_csv(path: &Path, results: &[ExperimentResult]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "size_mb,run,process,sample,pages_touched,private_dirty_kb,buffer_private_dirty_kb,elapsed_ms"
+    )?;
+    for entry in results {
+        for (process, run) in process_runs(entry) {
+            for (index, sample) in run.dirty_series.iter().enumerate() {
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{},{},{:.4}",
+                    entry.size_mb,
+                    entry.run,
+                    process,
+                    index,
+                    sample.pages_touched,
+                    sample.private_dirty_kb,
+                    sample.buffer_private_dirty_kb,
+                    sample.elapsed_ms
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One row per histogram bucket of every `--latency` run; empty buckets
+/// are skipped.
+fn write_latency_csv(path: &Path, results: &[ExperimentResult]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "size_mb,run,process,write,bucket_low_ns,bucket_high_ns,count"
+    )?;
+    for entry in results {
+        for (process, run) in process_runs(entry) {
+            let Some(latency) = run.latency else {
+                continue;
+            };
+            for (write, stats) in [("first", latency.first), ("rewrite", latency.rewrite)] {
+                for (bucket, &count) in stats.buckets.iter().enumerate() {
+                    if count == 0 {
+                        continue;
+                    }
+                    writeln!(
+                        file,
+                        "{},{},{},{},{},{},{}",
+                        entry.size_mb,
+                        entry.run,
+                        process,
+                        write,
+                        if bucket == 0 { 0 } else { 1u64 << bucket },
+                        (1u64 << (bucket + 1)) - 1,
+                        count
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_pages_csv(path: &Path, rows: &[PageComparison]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "size_mb,variant,page_kb,runs,fork_us,touch_ms_mean,touch_ms_stddev,faults,us_per_fault,\
+private_dirty_kb,huge_kb,parent_anon_huge_kb"
+    )?;
+    for row in rows {
+        writeln!(
+            file,
+            "{},{},{},{},{:.3},{:.4},{:.4},{:.1},{:.4},{:.1},{:.1},{:.1}",
+            row.size_mb,
+            row.variant,
+            row.page_kb,
+            row.runs,
+            row.fork_us,
+            row.touch_ms.mean,
+            row.touch_ms.stddev,
+            row.faults,
+            row.us_per_fault,
+            row.private_dirty_kb,
+            row.huge_kb,
+            row.parent_anon_huge_kb
+        )?;
+    }
+    Ok(())
+}
+
+fn write_summary_csv(path: &Path, summaries: &[SizeSummary]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "size_mb,runs,metric,mean,stddev,min,max")?;
+    for summary in summaries {
+        for (name, stats) in &summary.metrics {
+            writeln!(
+                file,
+                "{},{},{},{:.4},{:.4},{:.4},{:.4}",
+                summary.size_mb, summary.runs, name, stats.mean, stats.stddev, stats.min, stats.max
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Renders RSS-vs-size and touch-time-vs-size charts as one SVG. Each point
+/// is the mean over every run and child of that size.
+fn write_plot(path: &Path, results: &[ExperimentResult]) -> io::Result<()> {
+    let mut sizes: Vec<usize> = results.iter().map(|entry| entry.size_mb).collect();
+    sizes.sort_unstable();
+    sizes.dedup();
+    let mean_per_size = |metric: &dyn Fn(&ExperimentResult) -> Vec<f64>| -> Vec<(f64, f64)> {
+        sizes
+            .iter()
+            .filter_map(|&size| {
+                let samples: Vec<f64> = results
+                    .iter()
+                    .filter(|entry| entry.size_mb == size)
+                    .flat_map(metric)
+                    .collect();
+                (!samples.is_empty()).then(|| {
+                    (
+                        size as f64,
+                        samples.iter().sum::<f64>() / samples.len() as f64,
+                    )
+                })
+            })
+            .collect()
+    };
+    let children_mb = |stage: fn(&ChildRun) -> u64| {
+        mean_per_size(&|entry: &ExperimentResult| {
+            entry
+                .children
+                .iter()
+                .map(|child| stage(child) as f64 / 1024.0)
+                .collect()
+        })
+    };
+
+    let mut rss = plot::Chart {
+        title: "RSS vs buffer size".into(),
+        x_label: "Buffer size (MB)".into(),
+        y_label: "Memory (MB)".into(),
+        series: vec![
+            plot::Series {
+                label: "Parent RSS".into(),
+                points: mean_per_size(&|entry: &ExperimentResult| {
+                    vec![entry.parent_rss_kb as f64 / 1024.0]
+                }),
+            },
+            plot::Series {
+                label: "Child RSS after fork".into(),
+                points: children_mb(|child| child.post_fork.rss_kb),
+            },
+            plot::Series {
+                label: "Child RSS after touch".into(),
+                points: children_mb(|child| child.post_write.rss_kb),
+            },
+            plot::Series {
+                label: "Child Private_Dirty".into(),
+                points: children_mb(|child| child.post_write.private_dirty_kb),
+            },
+        ],
+    };
+    rss.series.retain(|series| !series.points.is_empty());
+
+    let mut touch = plot::Chart {
+        title: "Touch time vs buffer size".into(),
+        x_label: "Buffer size (MB)".into(),
+        y_label: "Touch time (ms)".into(),
+        series: vec![
+            plot::Series {
+                label: "Child".into(),
+                points: mean_per_size(&|entry: &ExperimentResult| {
+                    entry
+                        .children
+                        .iter()
+                        .map(|child| child.post_write.touch_ms)
+                        .collect()
+                }),
+            },
+            plot::Series {
+                label: "Thread baseline".into(),
+                points: mean_per_size(&|entry: &ExperimentResult| {
+                    entry
+                        .thread_baseline
+                        .iter()
+                        .map(|run| run.post_write.touch_ms)
+                        .collect()
+                }),
+            },
+            plot::Series {
+                label: "Eager copy".into(),
+                points: mean_per_size(&|entry: &ExperimentResult| {
+                    entry
+                        .eager_copy
+                        .iter()
+                        .map(|run| run.post_write.touch_ms)
+                        .collect()
+                }),
+            },
+        ],
+    };
+    touch.series.retain(|series| !series.points.is_empty());
+
+    std::fs::write(path, plot::render(&[rss, touch]))
+}
+
+/// A frame count as a CSV cell, empty when the frames were not compared.
+fn frame_cell(frames: Option<FrameSharing>, count: fn(FrameSharing) -> u64) -> String {
+    frames.map_or_else(String::new, |frames| count(frames).to_string())
+}
+
+/// A KSM counter as a CSV cell, empty without `--ksm`.
+fn ksm_cell(ksm: Option<KsmReport>, count: fn(KsmReport) -> u64) -> String {
+    ksm.map_or_else(String::new, |ksm| count(ksm).to_string())
+}
+
+fn cgroup_cell(cgroup: Option<CgroupReport>, count: fn(CgroupReport) -> u64) -> String {
+    cgroup.map_or_else(String::new, |cgroup| count(cgroup).to_string())
+}
+
+/// `0` for a clean exit, the code or signal otherwise.
+fn exit_label(exit: ExitStatus) -> String {
+    match (exit.code(), exit.signal()) {
+        (Some(code), _) => code.to_string(),
+        (None, Some(signal)) => format!("signal {signal}"),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+fn exit_json(exit: Option<ExitStatus>) -> String {
+    match exit.map(|exit| (exit.code(), exit.signal())) {
+        Some((Some(code), _)) => format!("{{\"code\":{code}}}"),
+        Some((None, Some(signal))) => format!("{{\"signal\":{signal}}}"),
+        _ => "null".to_string(),
+    }
+}
+
+fn latency_cell(run: &ChildRun, field: fn(WriteLatency) -> u64) -> String {
+    run.latency
+        .map_or_else(String::new, |latency| field(latency).to_string())
+}
+
+fn mid_touch_cell(run: &ChildRun, field: fn(&ChildStage) -> u64) -> String {
+    run.mid_touch
+        .as_ref()
+        .map_or_else(String::new, |stage| field(stage).to_string())
+}
+
+fn verification_cell(entry: &ExperimentResult, field: fn(Verification) -> String) -> String {
+    entry.verification.map_or_else(String::new, field)
+}
+
+fn exec_cell(entry: &ExperimentResult, field: fn(&ExecReport) -> String) -> String {
+    entry.exec.as_ref().map_or_else(String::new, field)
+}
+
+fn vm_cell(entry: &ExperimentResult, field: fn(VmFaults) -> u64) -> String {
+    entry
+        .vm_faults
+        .map_or_else(String::new, |vm| field(vm).to_string())
+}
+
+fn post_read_cell(run: &ChildRun, field: fn(&ChildStage) -> u64) -> String {
+    run.post_read
+        .as_ref()
+        .map_or_else(String::new, |stage| field(stage).to_string())
+}
+
+fn discard_cell(run: &ChildRun, field: fn(&ChildStage, DiscardReport) -> u64) -> String {
+    match (&run.post_discard, run.discard) {
+        (Some(stage), Some(report)) => field(stage, report).to_string(),
+        _ => String::new(),
+    }
+}
+
+fn pass_cell(run: &ChildRun, field: fn((PassCost, PassCost)) -> String) -> String {
+    run.passes().map_or_else(String::new, field)
+}
+
+/// What one CSV row is drawn from: a child, thread baseline, eager copy or
+/// parent write of an experiment, and the run's metadata.
+struct CsvRow<'a> {
+    entry: &'a ExperimentResult,
+    child: &'a ChildRun,
+    /// The child's index among the experiment's children; 0 for the others.
+    index: usize,
+    /// `fork` or `posix_spawn` for children, otherwise what the row is.
+    spawn: &'static str,
+    metadata: &'a RunMetadata,
+}
+
+type CsvColumn = (&'static str, fn(&CsvRow) -> String);
+
+/// The columns of the `--output` CSV and the SQLite table, in order, each
+/// with how its cell is computed. A new column goes at the end, so files
+/// written before keep lining up as a prefix.
+const CSV_COLUMNS: &[CsvColumn] = &[
+    ("size_mb", |row| row.entry.size_mb.to_string()),
+    ("run", |row| row.entry.run.to_string()),
+    ("parent_rss_kb", |row| row.entry.parent_rss_kb.to_string()),
+    ("child_post_fork_rss_kb", |row| {
+        row.child.post_fork.rss_kb.to_string()
+    }),
+    ("child_post_fork_private_dirty_kb", |row| {
+        row.child.post_fork.private_dirty_kb.to_string()
+    }),
+    ("child_post_write_rss_kb", |row| {
+        row.child.post_write.rss_kb.to_string()
+    }),
+    ("child_post_write_private_dirty_kb", |row| {
+        row.child.post_write.private_dirty_kb.to_string()
+    }),
+    ("touch_ms", |row| row.child.post_write.touch_ms.to_string()),
+    ("alloc", |row| row.entry.alloc.label().to_string()),
+    ("touch_order", |row| {
+        row.entry.touch_order.label().to_string()
+    }),
+    ("write_bytes_per_page", |row| {
+        row.entry.write_bytes.to_string()
+    }),
+    ("thp", |row| {
+        row.entry.thp.map_or("default", ThpMode::label).to_string()
+    }),
+    ("parent_anon_huge_kb", |row| {
+        row.entry.parent_anon_huge_kb.to_string()
+    }),
+    ("child_post_fork_anon_huge_kb", |row| {
+        row.child.post_fork.anon_huge_kb.to_string()
+    }),
+    ("child_post_write_anon_huge_kb", |row| {
+        row.child.post_write.anon_huge_kb.to_string()
+    }),
+    ("child_post_fork_hugetlb_kb", |row| {
+        row.child.post_fork.hugetlb_kb.to_string()
+    }),
+    ("child_post_write_hugetlb_kb", |row| {
+        row.child.post_write.hugetlb_kb.to_string()
+    }),
+    ("child_post_fork_minor_faults", |row| {
+        row.child.post_fork.minor_faults.to_string()
+    }),
+    ("child_post_fork_major_faults", |row| {
+        row.child.post_fork.major_faults.to_string()
+    }),
+    ("child_post_write_minor_faults", |row| {
+        row.child.post_write.minor_faults.to_string()
+    }),
+    ("child_post_write_major_faults", |row| {
+        row.child.post_write.major_faults.to_string()
+    }),
+    ("child_post_fork_stat_min_flt", |row| {
+        row.child.post_fork.stat_min_flt.to_string()
+    }),
+    ("child_post_fork_stat_maj_flt", |row| {
+        row.child.post_fork.stat_maj_flt.to_string()
+    }),
+    ("child_post_write_stat_min_flt", |row| {
+        row.child.post_write.stat_min_flt.to_string()
+    }),
+    ("child_post_write_stat_maj_flt", |row| {
+        row.child.post_write.stat_maj_flt.to_string()
+    }),
+    ("parent_fill_min_flt", |row| {
+        row.entry.parent_fill_faults.min_flt.to_string()
+    }),
+    ("parent_fill_maj_flt", |row| {
+        row.entry.parent_fill_faults.maj_flt.to_string()
+    }),
+    ("parent_wait_min_flt", |row| {
+        row.entry.parent_wait_faults.min_flt.to_string()
+    }),
+    ("parent_wait_maj_flt", |row| {
+        row.entry.parent_wait_faults.maj_flt.to_string()
+    }),
+    ("parent_wait_cmin_flt", |row| {
+        row.entry.parent_wait_faults.cmin_flt.to_string()
+    }),
+    ("parent_wait_cmaj_flt", |row| {
+        row.entry.parent_wait_faults.cmaj_flt.to_string()
+    }),
+    ("child", |row| row.index.to_string()),
+    ("fork_us", |row| format!("{:.1}", row.child.fork_us)),
+    ("spawn", |row| row.spawn.to_string()),
+    ("child_post_fork_shared_dirty_kb", |row| {
+        row.child.post_fork.shared_dirty_kb.to_string()
+    }),
+    ("child_post_write_shared_dirty_kb", |row| {
+        row.child.post_write.shared_dirty_kb.to_string()
+    }),
+    ("post_fork_parent_private_dirty_kb", |row| {
+        row.child.post_fork.parent_private_dirty_kb.to_string()
+    }),
+    ("post_fork_parent_shared_dirty_kb", |row| {
+        row.child.post_fork.parent_shared_dirty_kb.to_string()
+    }),
+    ("post_write_parent_private_dirty_kb", |row| {
+        row.child.post_write.parent_private_dirty_kb.to_string()
+    }),
+    ("post_write_parent_shared_dirty_kb", |row| {
+        row.child.post_write.parent_shared_dirty_kb.to_string()
+    }),
+    ("parent_visible_pages", |row| {
+        row.entry.parent_visible_pages.to_string()
+    }),
+    ("child_missing_pages", |row| {
+        row.child.post_fork.missing_pages.to_string()
+    }),
+    ("child_zeroed_pages", |row| {
+        row.child.post_fork.zeroed_pages.to_string()
+    }),
+    ("fork_advice", |row| {
+        row.entry
+            .fork_advice
+            .map_or("none", ForkAdvice::label)
+            .to_string()
+    }),
+    ("mlock", |row| row.entry.mlock.to_string()),
+    ("parent_locked_kb", |row| {
+        row.entry.parent_locked_kb.to_string()
+    }),
+    ("child_post_fork_locked_kb", |row| {
+        row.child.post_fork.locked_kb.to_string()
+    }),
+    ("child_post_write_locked_kb", |row| {
+        row.child.post_write.locked_kb.to_string()
+    }),
+    ("memory_source", |_| memory_source().label().to_string()),
+    ("parent_buffer_rss_kb", |row| {
+        row.entry.parent_buffer.rss_kb.to_string()
+    }),
+    ("parent_buffer_private_dirty_kb", |row| {
+        row.entry.parent_buffer.private_dirty_kb.to_string()
+    }),
+    ("parent_buffer_shared_clean_kb", |row| {
+        row.entry.parent_buffer.shared_clean_kb.to_string()
+    }),
+    ("parent_buffer_shared_dirty_kb", |row| {
+        row.entry.parent_buffer.shared_dirty_kb.to_string()
+    }),
+    ("child_post_fork_buffer_rss_kb", |row| {
+        row.child.post_fork.buffer.rss_kb.to_string()
+    }),
+    ("child_post_fork_buffer_private_dirty_kb", |row| {
+        row.child.post_fork.buffer.private_dirty_kb.to_string()
+    }),
+    ("child_post_fork_buffer_shared_clean_kb", |row| {
+        row.child.post_fork.buffer.shared_clean_kb.to_string()
+    }),
+    ("child_post_fork_buffer_shared_dirty_kb", |row| {
+        row.child.post_fork.buffer.shared_dirty_kb.to_string()
+    }),
+    ("child_post_write_buffer_rss_kb", |row| {
+        row.child.post_write.buffer.rss_kb.to_string()
+    }),
+    ("child_post_write_buffer_private_dirty_kb", |row| {
+        row.child.post_write.buffer.private_dirty_kb.to_string()
+    }),
+    ("child_post_write_buffer_shared_clean_kb", |row| {
+        row.child.post_write.buffer.shared_clean_kb.to_string()
+    }),
+    ("child_post_write_buffer_shared_dirty_kb", |row| {
+        row.child.post_write.buffer.shared_dirty_kb.to_string()
+    }),
+    ("child_post_fork_shared_frames", |row| {
+        frame_cell(row.child.post_fork.frames, |f| f.shared)
+    }),
+    ("child_post_fork_copied_frames", |row| {
+        frame_cell(row.child.post_fork.frames, |f| f.copied)
+    }),
+    ("child_post_write_shared_frames", |row| {
+        frame_cell(row.child.post_write.frames, |f| f.shared)
+    }),
+    ("child_post_write_copied_frames", |row| {
+        frame_cell(row.child.post_write.frames, |f| f.copied)
+    }),
+    ("ksm_merging_pages", |row| {
+        ksm_cell(row.entry.ksm, |k| k.merging_pages)
+    }),
+    ("ksm_pages_shared", |row| {
+        ksm_cell(row.entry.ksm, |k| k.pages_shared)
+    }),
+    ("ksm_pages_sharing", |row| {
+        ksm_cell(row.entry.ksm, |k| k.pages_sharing)
+    }),
+    ("touched_bytes", |row| {
+        row.child.post_write.touched_bytes.to_string()
+    }),
+    ("touch_mb_per_s", |row| {
+        format!("{:.1}", row.child.post_write.touch_mb_per_s())
+    }),
+    ("stride", |row| row.entry.stride.to_string()),
+    ("child_mid_touch_rss_kb", |row| {
+        mid_touch_cell(row.child, |s| s.rss_kb)
+    }),
+    ("child_mid_touch_private_dirty_kb", |row| {
+        mid_touch_cell(row.child, |s| s.private_dirty_kb)
+    }),
+    ("parent_at_post_fork_rss_kb", |row| {
+        row.entry.parent_cell(Checkpoint::PostFork, |p| p.rss_kb)
+    }),
+    ("parent_at_post_fork_private_dirty_kb", |row| {
+        row.entry
+            .parent_cell(Checkpoint::PostFork, |p| p.private_dirty_kb)
+    }),
+    ("parent_at_mid_touch_rss_kb", |row| {
+        row.entry.parent_cell(Checkpoint::MidTouch, |p| p.rss_kb)
+    }),
+    ("parent_at_mid_touch_private_dirty_kb", |row| {
+        row.entry
+            .parent_cell(Checkpoint::MidTouch, |p| p.private_dirty_kb)
+    }),
+    ("parent_at_post_write_rss_kb", |row| {
+        row.entry.parent_cell(Checkpoint::PostWrite, |p| p.rss_kb)
+    }),
+    ("parent_at_post_write_private_dirty_kb", |row| {
+        row.entry
+            .parent_cell(Checkpoint::PostWrite, |p| p.private_dirty_kb)
+    }),
+    ("child_exit", |row| {
+        row.child.exit.map_or_else(String::new, exit_label)
+    }),
+    ("child_errors", |row| row.child.errors.join(" | ")),
+    ("timestamp", |row| row.metadata.timestamp.to_string()),
+    ("hostname", |row| row.metadata.hostname.to_string()),
+    ("kernel", |row| row.metadata.kernel.to_string()),
+    ("page_size", |row| row.metadata.page_size.to_string()),
+    ("system_thp", |row| row.metadata.system_thp.to_string()),
+    ("cgroup_limit_mb", |row| {
+        cgroup_cell(row.entry.cgroup, |c| c.limit_mb as u64)
+    }),
+    ("cgroup_low", |row| cgroup_cell(row.entry.cgroup, |c| c.low)),
+    ("cgroup_high", |row| {
+        cgroup_cell(row.entry.cgroup, |c| c.high)
+    }),
+    ("cgroup_max", |row| cgroup_cell(row.entry.cgroup, |c| c.max)),
+    ("cgroup_oom", |row| cgroup_cell(row.entry.cgroup, |c| c.oom)),
+    ("cgroup_oom_kill", |row| {
+        cgroup_cell(row.entry.cgroup, |c| c.oom_kill)
+    }),
+    ("parent_swap_kb", |row| row.entry.parent_swap_kb.to_string()),
+    ("parent_buffer_swap_kb", |row| {
+        row.entry.parent_buffer.swap_kb.to_string()
+    }),
+    ("child_post_fork_swap_kb", |row| {
+        row.child.post_fork.swap_kb.to_string()
+    }),
+    ("child_post_write_swap_kb", |row| {
+        row.child.post_write.swap_kb.to_string()
+    }),
+    ("child_post_fork_buffer_swap_kb", |row| {
+        row.child.post_fork.buffer.swap_kb.to_string()
+    }),
+    ("child_post_write_buffer_swap_kb", |row| {
+        row.child.post_write.buffer.swap_kb.to_string()
+    }),
+    ("parent_at_post_fork_swap_kb", |row| {
+        row.entry.parent_cell(Checkpoint::PostFork, |p| p.swap_kb)
+    }),
+    ("parent_at_mid_touch_swap_kb", |row| {
+        row.entry.parent_cell(Checkpoint::MidTouch, |p| p.swap_kb)
+    }),
+    ("parent_at_post_write_swap_kb", |row| {
+        row.entry.parent_cell(Checkpoint::PostWrite, |p| p.swap_kb)
+    }),
+    ("first_write_p50_ns", |row| {
+        latency_cell(row.child, |l| l.first.p50_ns)
+    }),
+    ("first_write_p90_ns", |row| {
+        latency_cell(row.child, |l| l.first.p90_ns)
+    }),
+    ("first_write_p99_ns", |row| {
+        latency_cell(row.child, |l| l.first.p99_ns)
+    }),
+    ("first_write_max_ns", |row| {
+        latency_cell(row.child, |l| l.first.max_ns)
+    }),
+    ("rewrite_p50_ns", |row| {
+        latency_cell(row.child, |l| l.rewrite.p50_ns)
+    }),
+    ("rewrite_p90_ns", |row| {
+        latency_cell(row.child, |l| l.rewrite.p90_ns)
+    }),
+    ("rewrite_p99_ns", |row| {
+        latency_cell(row.child, |l| l.rewrite.p99_ns)
+    }),
+    ("rewrite_max_ns", |row| {
+        latency_cell(row.child, |l| l.rewrite.max_ns)
+    }),
+    ("child_mid_touch_soft_dirty_pages", |row| {
+        row.child
+            .mid_touch
+            .as_ref()
+            .and_then(|mid| mid.soft_dirty_pages)
+            .map_or_else(String::new, |pages| pages.to_string())
+    }),
+    ("child_post_write_soft_dirty_pages", |row| {
+        row.child
+            .post_write
+            .soft_dirty_pages
+            .map_or_else(String::new, |pages| pages.to_string())
+    }),
+    ("depth", |row| row.entry.depth.to_string()),
+    ("generation", |row| row.child.generation.to_string()),
+    ("parent_buffer_private_clean_kb", |row| {
+        row.entry.parent_buffer.private_clean_kb.to_string()
+    }),
+    ("child_post_fork_buffer_private_clean_kb", |row| {
+        row.child.post_fork.buffer.private_clean_kb.to_string()
+    }),
+    ("child_post_write_buffer_private_clean_kb", |row| {
+        row.child.post_write.buffer.private_clean_kb.to_string()
+    }),
+    ("concurrent", |row| row.entry.concurrent.to_string()),
+    ("child_post_read_rss_kb", |row| {
+        post_read_cell(row.child, |s| s.rss_kb)
+    }),
+    ("child_post_read_private_dirty_kb", |row| {
+        post_read_cell(row.child, |s| s.private_dirty_kb)
+    }),
+    ("child_post_read_minor_faults", |row| {
+        post_read_cell(row.child, |s| s.minor_faults)
+    }),
+    ("child_post_read_major_faults", |row| {
+        post_read_cell(row.child, |s| s.major_faults)
+    }),
+    ("exec_fork_us", |row| {
+        exec_cell(row.entry, |e| format!("{:.1}", e.fork_us))
+    }),
+    ("exec_us", |row| {
+        exec_cell(row.entry, |e| format!("{:.1}", e.exec_us))
+    }),
+    ("exec_child_before_rss_kb", |row| {
+        exec_cell(row.entry, |e| e.before_rss_kb.to_string())
+    }),
+    ("exec_child_after_rss_kb", |row| {
+        exec_cell(row.entry, |e| e.after_rss_kb.to_string())
+    }),
+    ("exec_parent_buffer_shared_dirty_kb", |row| {
+        exec_cell(row.entry, |e| {
+            e.parent_buffer_before.shared_dirty_kb.to_string()
+        })
+    }),
+    ("exec_parent_buffer_shared_dirty_after_kb", |row| {
+        exec_cell(row.entry, |e| {
+            e.parent_buffer_after.shared_dirty_kb.to_string()
+        })
+    }),
+    ("parent_checksum_before", |row| {
+        verification_cell(row.entry, |v| format!("{:#018x}", v.parent_before))
+    }),
+    ("parent_checksum_after", |row| {
+        verification_cell(row.entry, |v| format!("{:#018x}", v.parent_after))
+    }),
+    ("parent_checksum_status", |row| {
+        verification_cell(row.entry, |v| v.status().to_string())
+    }),
+    ("child_checksum", |row| {
+        row.child
+            .checksum
+            .map_or_else(String::new, |sum| format!("{sum:#018x}"))
+    }),
+    ("child_checksum_matched", |row| {
+        match (row.entry.verification, row.child.checksum) {
+            (Some(v), Some(sum)) => (sum == v.parent_before).to_string(),
+            _ => String::new(),
+        }
+    }),
+    ("vm_pgfault", |row| vm_cell(row.entry, |vm| vm.pgfault)),
+    ("vm_pgmajfault", |row| {
+        vm_cell(row.entry, |vm| vm.pgmajfault)
+    }),
+    ("vm_thp_fault_alloc", |row| {
+        vm_cell(row.entry, |vm| vm.thp_fault_alloc)
+    }),
+    ("rss_source", |_| rss_source().label().to_string()),
+    ("forked_monotonic_ns", |row| {
+        Timestamp::csv_cell(row.child.forked_at, |at| at.monotonic_ns)
+    }),
+    ("forked_unix_ns", |row| {
+        Timestamp::csv_cell(row.child.forked_at, |at| at.unix_ns)
+    }),
+    ("post_fork_measured_monotonic_ns", |row| {
+        row.child.post_fork.measured_at.monotonic_ns.to_string()
+    }),
+    ("post_fork_measured_unix_ns", |row| {
+        row.child.post_fork.measured_at.unix_ns.to_string()
+    }),
+    ("touch_start_monotonic_ns", |row| {
+        Timestamp::csv_cell(row.child.post_write.touch_started_at, |at| at.monotonic_ns)
+    }),
+    ("touch_start_unix_ns", |row| {
+        Timestamp::csv_cell(row.child.post_write.touch_started_at, |at| at.unix_ns)
+    }),
+    ("touch_end_monotonic_ns", |row| {
+        Timestamp::csv_cell(row.child.post_write.touch_ended_at, |at| at.monotonic_ns)
+    }),
+    ("touch_end_unix_ns", |row| {
+        Timestamp::csv_cell(row.child.post_write.touch_ended_at, |at| at.unix_ns)
+    }),
+    ("report_collected_monotonic_ns", |row| {
+        Timestamp::csv_cell(row.child.post_write.collected_at, |at| at.monotonic_ns)
+    }),
+    ("report_collected_unix_ns", |row| {
+        Timestamp::csv_cell(row.child.post_write.collected_at, |at| at.unix_ns)
+    }),
+    ("numa_buffer_node", |row| {
+        row.entry
+            .numa
+            .map_or_else(String::new, |nodes| nodes.buffer.to_string())
+    }),
+    ("numa_child_node", |row| {
+        row.entry
+            .numa
+            .map_or_else(String::new, |nodes| nodes.child.to_string())
+    }),
+    ("parent_numa_kb", |row| row.entry.parent_nodes.csv_cell()),
+    ("child_post_fork_numa_kb", |row| {
+        row.child.post_fork.nodes.csv_cell()
+    }),
+    ("child_post_write_numa_kb", |row| {
+        row.child.post_write.nodes.csv_cell()
+    }),
+    ("parent_private_dirty_kb", |row| {
+        row.entry.parent_private_dirty_kb.to_string()
+    }),
+    ("parent_rss_after_kb", |row| {
+        row.entry.parent_rss_after_kb.to_string()
+    }),
+    ("parent_private_dirty_after_kb", |row| {
+        row.entry.parent_private_dirty_after_kb.to_string()
+    }),
+    ("overcommit_memory", |row| {
+        row.metadata
+            .overcommit
+            .map_or_else(String::new, |policy| policy.mode.to_string())
+    }),
+    ("overcommit_ratio", |row| {
+        row.metadata
+            .overcommit
+            .map_or_else(String::new, |policy| policy.ratio.to_string())
+    }),
+    ("overcommit_kbytes", |row| {
+        row.metadata
+            .overcommit
+            .map_or_else(String::new, |policy| policy.kbytes.to_string())
+    }),
+    ("commit_limit_kb", |row| {
+        row.metadata
+            .overcommit
+            .map_or_else(String::new, |policy| policy.commit_limit_kb.to_string())
+    }),
+    ("committed_as_kb", |row| {
+        row.metadata
+            .overcommit
+            .map_or_else(String::new, |policy| policy.committed_kb.to_string())
+    }),
+    ("cpu_model", |row| row.metadata.cpu_model.clone()),
+    ("cpus", |row| row.metadata.cpus.to_string()),
+    ("mem_total_kb", |row| {
+        row.metadata
+            .mem_total_kb
+            .map_or_else(String::new, |kb| kb.to_string())
+    }),
+    ("swap_total_kb", |row| {
+        row.metadata
+            .swap
+            .map_or_else(String::new, |swap| swap.total_kb.to_string())
+    }),
+    ("swap_free_kb", |row| {
+        row.metadata
+            .swap
+            .map_or_else(String::new, |swap| swap.free_kb.to_string())
+    }),
+    ("first_write_ns", |row| {
+        row.child
+            .post_write
+            .first_write
+            .map_or_else(String::new, |first| first.ns.to_string())
+    }),
+    ("first_write_minor_faults", |row| {
+        row.child
+            .post_write
+            .first_write
+            .map_or_else(String::new, |first| first.minor_faults.to_string())
+    }),
+    ("first_write_major_faults", |row| {
+        row.child
+            .post_write
+            .first_write
+            .map_or_else(String::new, |first| first.major_faults.to_string())
+    }),
+    ("later_write_ns", |row| {
+        row.child
+            .post_write
+            .later_write_ns(touch_page_size(row.entry.alloc))
+            .map_or_else(String::new, |later| format!("{later:.1}"))
+    }),
+    ("child_post_fork_pss_kb", |row| {
+        row.child.post_fork.pss_kb.to_string()
+    }),
+    ("child_post_write_pss_kb", |row| {
+        row.child.post_write.pss_kb.to_string()
+    }),
+    ("child_post_fork_shared_clean_kb", |row| {
+        row.child.post_fork.shared_clean_kb.to_string()
+    }),
+    ("child_post_write_shared_clean_kb", |row| {
+        row.child.post_write.shared_clean_kb.to_string()
+    }),
+    ("post_fork_parent_pss_kb", |row| {
+        row.child.post_fork.parent_pss_kb.to_string()
+    }),
+    ("post_write_parent_pss_kb", |row| {
+        row.child.post_write.parent_pss_kb.to_string()
+    }),
+    ("post_fork_parent_shared_clean_kb", |row| {
+        row.child.post_fork.parent_shared_clean_kb.to_string()
+    }),
+    ("post_write_parent_shared_clean_kb", |row| {
+        row.child.post_write.parent_shared_clean_kb.to_string()
+    }),
+    ("child_read_pass_ms", |row| {
+        pass_cell(row.child, |(read, _)| format!("{:.4}", read.ms))
+    }),
+    ("child_read_pass_copied_kb", |row| {
+        pass_cell(row.child, |(read, _)| read.copied_kb.to_string())
+    }),
+    ("child_write_pass_faults", |row| {
+        pass_cell(row.child, |(_, write)| write.faults.to_string())
+    }),
+    ("child_write_pass_copied_kb", |row| {
+        pass_cell(row.child, |(_, write)| write.copied_kb.to_string())
+    }),
+    ("discard", |row| {
+        row.entry
+            .discard
+            .map_or("none", DiscardAdvice::label)
+            .to_string()
+    }),
+    ("child_post_discard_rss_kb", |row| {
+        discard_cell(row.child, |stage, _| stage.rss_kb)
+    }),
+    ("child_post_discard_private_dirty_kb", |row| {
+        discard_cell(row.child, |stage, _| stage.private_dirty_kb)
+    }),
+    ("child_discarded_kb", |row| {
+        discard_cell(row.child, |_, report| report.discarded_bytes / 1024)
+    }),
+    ("child_lazy_free_kb", |row| {
+        discard_cell(row.child, |_, report| report.lazy_free_kb)
+    }),
+    ("child_reread_kept_pages", |row| {
+        discard_cell(row.child, |_, report| report.kept_pages)
+    }),
+    ("child_reread_zeroed_pages", |row| {
+        discard_cell(row.child, |_, report| report.zeroed_pages)
+    }),
+    ("child_reread_other_pages", |row| {
+        discard_cell(row.child, |_, report| report.other_pages)
+    }),
+    ("child_reread_faults", |row| {
+        discard_cell(row.child, |_, report| report.reread_faults)
+    }),
+    ("child_reread_rss_kb", |row| {
+        discard_cell(row.child, |_, report| report.reread_rss_kb)
+    }),
+];
+
+/// The CSV header line, which an appended file must already have.
+fn csv_header() -> String {
+    let names: Vec<&str> = CSV_COLUMNS.iter().map(|(name, _)| *name).collect();
+    names.join(",")
+}
+
+/// Where and when this invocation ran, collected once at the start. It heads
+/// the JSON and the report and is repeated on every CSV row, so rows
+/// appended by different runs stay self-describing.
+struct RunMetadata {
+    /// Start of the invocation, in UTC.
+    timestamp: String,
+    hostname: String,
+    kernel: String,
+    page_size: usize,
+    huge_page_size: usize,
+    system_thp: String,
+    overcommit: Option<Overcommit>,
+    cpu_model: String,
+    /// CPUs this process may run on.
+    cpus: usize,
+    mem_total_kb: Option<u64>,
+    swap: Option<SwapTotals>,
+}
+
+#[derive(Clone, Copy)]
+struct SwapTotals {
+    total_kb: u64,
+    free_kb: u64,
+}
+
+impl RunMetadata {
+    fn collect() -> RunMetadata {
+        RunMetadata {
+            timestamp: utc_timestamp(SystemTime::now()),
+            hostname: sys::hostname().unwrap_or_else(|_| "unknown".into()),
+            kernel: kernel_release(),
+            page_size: page_size(),
+            huge_page_size: huge_page_size(),
+            system_thp: system_thp_policy(),
+            overcommit: Overcommit::read(),
+            cpu_model: cpu_model(),
+            cpus: std::thread::available_parallelism().map_or(0, |cpus| cpus.get()),
+            mem_total_kb: mem_total_kb(),
+            swap: swap_totals(),
+        }
+    }
+
+    /// One line for the start of the run.
+    fn summary(&self) -> String {
+        let memory = self
+            .mem_total_kb
+            .map_or_else(|| "unknown".to_string(), |kb| format!("{} MB", kb / 1024));
+        let swap = self.swap.map_or_else(
+            || "unknown".to_string(),
+            |swap| {
+                format!(
+                    "{} MB ({} MB free)",
+                    swap.total_kb / 1024,
+                    swap.free_kb / 1024
+                )
+            },
+        );
+        let overcommit = self
+            .overcommit
+            .map_or_else(|| "unknown".to_string(), Overcommit::summary);
+        format!(
+            "kernel {}, {} ({} CPUs), RAM {memory}, swap {swap}, {} B pages, THP {}, overcommit {overcommit}",
+            self.kernel, self.cpu_model, self.cpus, self.page_size, self.system_thp
+        )
+    }
+
+    /// The `metadata` of the JSON output and stream, without the settings.
+    fn to_json(&self) -> String {
+        let optional =
+            |value: Option<u64>| value.map_or_else(|| "null".to_string(), |v| v.to_string());
+        format!(
+            "\"timestamp\":{},\"hostname\":{},\"kernel\":{},\"page_size\":{},\"huge_page_size\":{},\"system_thp\":{},\"overcommit\":{},\"cpu_model\":{},\"cpus\":{},\"mem_total_kb\":{},\"swap_total_kb\":{},\"swap_free_kb\":{}",
+            json_string(&self.timestamp),
+            json_string(&self.hostname),
+            json_string(&self.kernel),
+            self.page_size,
+            self.huge_page_size,
+            json_string(&self.system_thp),
+            self.overcommit
+                .map_or_else(|| "null".to_string(), Overcommit::to_json),
+            json_string(&self.cpu_model),
+            self.cpus,
+            optional(self.mem_total_kb),
+            optional(self.swap.map(|swap| swap.total_kb)),
+            optional(self.swap.map(|swap| swap.free_kb))
+        )
+    }
+}
+
+/// The processor's marketing name, from `/proc/cpuinfo` (`model name` on
+/// x86, `Model` or `Hardware` on most Arm boards) or the
+/// `machdep.cpu.brand_string` sysctl on macOS.
+fn cpu_model() -> String {
+    #[cfg(target_os = "macos")]
+    let model = sys::sysctl("machdep.cpu.brand_string").ok().map(|bytes| {
+        String::from_utf8_lossy(&bytes)
+            .trim_end_matches('\0')
+            .to_string()
+    });
+    #[cfg(not(target_os = "macos"))]
+    let model = std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|text| {
+            ["model name", "Model", "Hardware", "cpu model"]
+                .iter()
+                .find_map(|key| {
+                    text.lines().find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        (name.trim() == *key).then(|| value.trim().to_string())
+                    })
+                })
+        });
+    model
+        .filter(|model| !model.is_empty())
+        .unwrap_or_else(|| "unknown CPU".into())
+}
+
+fn mem_total_kb() -> Option<u64> {
+    #[cfg(target_os = "macos")]
+    return sys::sysctl("hw.memsize")
+        .ok()
+        .and_then(|bytes| Some(u64::from_ne_bytes(bytes.get(..8)?.try_into().ok()?) / 1024));
+    #[cfg(not(target_os = "macos"))]
+    read_meminfo("MemTotal")
+}
+
+fn swap_totals() -> Option<SwapTotals> {
+    // `struct xsw_usage` starts with the total and available bytes.
+    #[cfg(target_os = "macos")]
+    return sys::sysctl("vm.swapusage").ok().and_then(|bytes| {
+        let field = |at: usize| Some(u64::from_ne_bytes(bytes.get(at..at + 8)?.try_into().ok()?));
+        Some(SwapTotals {
+            total_kb: field(0)? / 1024,
+            free_kb: field(8)? / 1024,
+        })
+    });
+    #[cfg(not(target_os = "macos"))]
+    Some(SwapTotals {
+        total_kb: read_meminfo("SwapTotal")?,
+        free_kb: read_meminfo("SwapFree")?,
+    })
+}
+
+/// `time` as an RFC 3339 UTC timestamp, to the second.
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, of_day) = (secs / 86_400, secs % 86_400);
+    // Days since 1970-01-01 to a proleptic Gregorian date, counting from
+    // 0000-03-01 so the leap day falls at the end of each year.
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let of_era = days.rem_euclid(146_097);
+    let year_of_era = (of_era - of_era / 1_460 + of_era / 36_524 - of_era / 146_096) / 365;
+    let of_year = of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * of_year + 2) / 153;
+    let day = of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        of_day / 3_600,
+        of_day % 3_600 / 60,
+        of_day % 60
+    )
+}
+
+/// Opens the `--output` CSV for writing rows, writing the header first
+/// unless `append` finds it already there. Refuses to append to a file
+/// whose header differs, since its rows would not line up.
+fn open_csv(path: &Path, append: bool) -> io::Result<File> {
+    if !append {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", csv_header())?;
+        return Ok(file);
+    }
+    let mut first = String::new();
+    match File::open(path) {
+        Ok(file) => {
+            BufReader::new(file).read_line(&mut first)?;
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let header = csv_header();
+    if first.is_empty() {
+        writeln!(file, "{header}")?;
+    } else if first.trim_end() != header {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the existing file has different columns; write to a new file instead",
+        ));
+    }
+    Ok(file)
+}
+
+fn write_csv(
+    path: &Path,
+    results: &[ExperimentResult],
+    metadata: &RunMetadata,
+    append: bool,
+) -> io::Result<()> {
+    let mut file = open_csv(path, append)?;
+    for row in csv_rows(results, metadata) {
+        let cells: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+        writeln!(file, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+/// A cell as a CSV field: quoted, with quotes doubled, when it holds a comma,
+/// a quote or a line break (RFC 4180), otherwise as it is.
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// One row per child, thread baseline, eager copy and parent write, with a
+/// cell per column of `CSV_COLUMNS`. Cells are unescaped: the CSV and the SQL
+/// writer each quote them their own way.
+fn csv_rows(results: &[ExperimentResult], metadata: &RunMetadata) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    for entry in results {
+        // The thread baseline and the parent's writes get their own rows,
+        // marked by the spawn column.
+        let runs = entry
+            .children
+            .iter()
+            .enumerate()
+            .map(|(index, child)| (index, child, entry.spawn.label()))
+            .chain(entry.thread_baseline.iter().map(|run| (0, run, "thread")))
+            .chain(entry.eager_copy.iter().map(|run| (0, run, "eager_copy")))
+            .chain(entry.parent_write.iter().map(|run| (0, run, "parent")));
+        for (index, child, spawn) in runs {
+            let row = CsvRow {
+                entry,
+                child,
+                index,
+                spawn,
+                metadata,
+            };
+            rows.push(CSV_COLUMNS.iter().map(|(_, cell)| cell(&row)).collect());
+        }
+    }
+    rows
+}
+
+/// Appends the CSV rows to table `experiments` of the SQLite database at
+/// `path`, created on first use, by piping SQL to the `sqlite3` shell. The
+/// columns are the CSV's, so the metadata is repeated on every row as there;
+/// columns added since the table was created are added to it first.
+fn write_sqlite(
+    path: &Path,
+    results: &[ExperimentResult],
+    metadata: &RunMetadata,
+) -> io::Result<()> {
+    let sqlite3 = |args: &[&str]| {
+        std::process::Command::new("sqlite3")
+            .arg("-batch")
+            .arg("-bail")
+            .arg(path)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => {
+                    io::Error::new(err.kind(), "the sqlite3 shell is not installed")
+                }
+                _ => err,
+            })
+    };
+    // Under -bail an error ends the shell with a failure status; anything on
+    // stderr without one still means a statement went wrong.
+    let finish = |child: std::process::Child| -> io::Result<String> {
+        let output = child.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() || !stderr.trim().is_empty() {
+            return Err(io::Error::other(format!(
+                "sqlite3 {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let existing = finish(sqlite3(&[
+        "SELECT name FROM pragma_table_info('experiments');",
+    ])?)?;
+    let existing: Vec<&str> = existing.lines().collect();
+    let columns: Vec<&str> = CSV_COLUMNS.iter().map(|(name, _)| *name).collect();
+    let quoted: Vec<String> = columns.iter().map(|name| format!("\"{name}\"")).collect();
+
+    let mut sql = String::from("BEGIN;\n");
+    if existing.is_empty() {
+        sql.push_str(&format!(
+            "CREATE TABLE experiments ({});\n",
+            quoted.join(", ")
+        ));
+    } else {
+        for (name, column) in columns.iter().zip(&quoted) {
+            if !existing.contains(name) {
+                sql.push_str(&format!("ALTER TABLE experiments ADD COLUMN {column};\n"));
+            }
+        }
+    }
+    for row in csv_rows(results, metadata) {
+        let values: Vec<String> = row.iter().map(|cell| sql_value(cell)).collect();
+        sql.push_str(&format!(
+            "INSERT INTO experiments ({}) VALUES ({});\n",
+            quoted.join(", "),
+            values.join(", ")
+        ));
+    }
+    sql.push_str("COMMIT;\n");
+
+    let mut child = sqlite3(&[])?;
+    let written = child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(sql.as_bytes());
+    // Report sqlite3's own error over the broken pipe it caused.
+    finish(child)?;
+    written
+}
+
+/// A cell as an SQL literal: empty cells are NULL and numbers stay numbers,
+/// so they compare and aggregate as such.
+fn sql_value(cell: &str) -> String {
+    if cell.is_empty() {
+        "NULL".to_string()
+    } else if cell.parse::<i64>().is_ok() || cell.parse::<f64>().is_ok_and(f64::is_finite) {
+        cell.to_string()
+    } else {
+        format!("'{}'", cell.replace('\'', "''"))
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl StatFaults {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"min_flt\":{},\"cmin_flt\":{},\"maj_flt\":{},\"cmaj_flt\":{}}}",
+            self.min_flt, self.cmin_flt, self.maj_flt, self.cmaj_flt
+        )
+    }
+}
+
+impl Overcommit {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"mode\":{},\"policy\":{},\"ratio\":{},\"kbytes\":{},\"commit_limit_kb\":{},\"committed_as_kb\":{}}}",
+            self.mode,
+            json_string(self.label()),
+            self.ratio,
+            self.kbytes,
+            self.commit_limit_kb,
+            self.committed_kb
+        )
+    }
+}
+
+impl VmFaults {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"pgfault\":{},\"pgmajfault\":{},\"thp_fault_alloc\":{}}}",
+            self.pgfault, self.pgmajfault, self.thp_fault_alloc
+        )
+    }
+}
+
+impl Verification {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"parent_before\":{},\"parent_after\":{},\"parent_status\":{},\"children_matched\":{},\"children_checked\":{}}}",
+            json_string(&format!("{:#018x}", self.parent_before)),
+            json_string(&format!("{:#018x}", self.parent_after)),
+            json_string(self.status()),
+            self.children_matched,
+            self.children_checked
+        )
+    }
+}
+
+impl ExecReport {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"fork_us\":{:.1},\"exec_us\":{:.1},\"before\":{{\"rss_kb\":{},\"private_dirty_kb\":{},\"shared_dirty_kb\":{},\"buffer\":{}}},\"after\":{{\"rss_kb\":{},\"private_dirty_kb\":{},\"shared_dirty_kb\":{}}},\"parent_buffer_before\":{},\"parent_buffer_after\":{}}}",
+            self.fork_us,
+            self.exec_us,
+            self.before_rss_kb,
+            self.before_private_dirty_kb,
+            self.before_shared_dirty_kb,
+            self.before_buffer.to_json(),
+            self.after_rss_kb,
+            self.after_private_dirty_kb,
+            self.after_shared_dirty_kb,
+            self.parent_buffer_before.to_json(),
+            self.parent_buffer_after.to_json()
+        )
+    }
+}
+
+impl BufferUsage {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"rss_kb\":{},\"private_dirty_kb\":{},\"shared_clean_kb\":{},\"shared_dirty_kb\":{},\"swap_kb\":{},\"private_clean_kb\":{}}}",
+            self.rss_kb,
+            self.private_dirty_kb,
+            self.shared_clean_kb,
+            self.shared_dirty_kb,
+            self.swap_kb,
+            self.private_clean_kb
+        )
+    }
+}
+
+impl SizeSummary {
+    fn to_json(&self) -> String {
+        let metrics: Vec<String> = self
+            .metrics
+            .iter()
+            .map(|(name, stats)| {
+                format!(
+                    "\"{name}\":{{\"mean\":{:.4},\"stddev\":{:.4},\"min\":{:.4},\"max\":{:.4}}}",
+                    stats.mean, stats.stddev, stats.min, stats.max
+                )
+            })
+            .collect();
+        format!(
+            "{{\"size_mb\":{},\"runs\":{},{}}}",
+            self.size_mb,
+            self.runs,
+            metrics.join(",")
+        )
+    }
+}
+
+impl ScalingFit {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"metric\":{},\"unit\":{},\"points\":{},\"slope_per_mb\":{:.6},\"intercept\":{:.6},\
+\"r_squared\":{:.6}}}",
+            json_string(self.metric),
+            json_string(self.unit),
+            self.fit.points,
+            self.fit.slope,
+            self.fit.intercept,
+            self.fit.r_squared
+        )
+    }
+}
+
+impl KsmReport {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"wait_ms\":{:.1},\"merging_pages\":{},\"pages_shared\":{},\"pages_sharing\":{}}}",
+            self.wait_ms, self.merging_pages, self.pages_shared, self.pages_sharing
+        )
+    }
+}
+
+impl LatencyStats {
+    fn to_json(self) -> String {
+        let buckets: Vec<String> = self.buckets.iter().map(u64::to_string).collect();
+        format!(
+            "{{\"p50_ns\":{},\"p90_ns\":{},\"p99_ns\":{},\"max_ns\":{},\"log2_buckets\":[{}]}}",
+            self.p50_ns,
+            self.p90_ns,
+            self.p99_ns,
+            self.max_ns,
+            buckets.join(",")
+        )
+    }
+}
+
+impl WriteLatency {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"first\":{},\"rewrite\":{}}}",
+            self.first.to_json(),
+            self.rewrite.to_json()
+        )
+    }
+}
 
-    Ok(Config {
-        sizes_mb: sizes.unwrap_or_else(|| DEFAULT_SIZES_MB.to_vec()),
-        output,
-    })
+impl CgroupReport {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"limit_mb\":{},\"low\":{},\"high\":{},\"max\":{},\"oom\":{},\"oom_kill\":{}}}",
+            self.limit_mb, self.low, self.high, self.max, self.oom, self.oom_kill
+        )
+    }
 }
 
-fn print_usage() {
-    eprintln!("Usage: cow [--sizes 64,96,128] [--output path]");
-    eprintln!("Demonstrates copy-on-write behaviour via RSS measurements.");
+impl DiscardReport {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"advice\":{},\"discarded_bytes\":{},\"lazy_free_kb\":{},\"kept_pages\":{},\
+\"zeroed_pages\":{},\"other_pages\":{},\"reread_faults\":{},\"reread_rss_kb\":{},\
+\"reread_private_dirty_kb\":{}}}",
+            json_string(self.advice.label()),
+            self.discarded_bytes,
+            self.lazy_free_kb,
+            self.kept_pages,
+            self.zeroed_pages,
+            self.other_pages,
+            self.reread_faults,
+            self.reread_rss_kb,
+            self.reread_private_dirty_kb
+        )
+    }
 }
 
-fn read_rss_kb(pid: u32) -> io::Result<u64> {
-    let path = format!("/proc/{pid}/status");
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(rest) = line.strip_prefix("VmRSS:") {
-            let value: Vec<&str> = rest.trim().split_whitespace().collect();
-            if let Some(number) = value.first() {
-                return number
-                    .parse::<u64>()
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
-            }
-        }
+impl FirstWrite {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"ns\":{},\"minor_faults\":{},\"major_faults\":{}}}",
+            self.ns, self.minor_faults, self.major_faults
+        )
     }
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        "VmRSS not found in /proc status",
-    ))
 }
 
-fn read_private_dirty_kb(pid: u32) -> io::Result<u64> {
-    let path = format!("/proc/{pid}/smaps_rollup");
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(rest) = line.strip_prefix("Private_Dirty:") {
-            let value: Vec<&str> = rest.trim().split_whitespace().collect();
-            if let Some(number) = value.first() {
-                return number
-                    .parse::<u64>()
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
-            }
-        }
+impl FrameSharing {
+    fn to_json(self) -> String {
+        format!("{{\"shared\":{},\"copied\":{}}}", self.shared, self.copied)
     }
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        "Private_Dirty not found in smaps_rollup",
-    ))
 }
 
-fn page_size() -> usize {
-    unsafe {
-        let sz = sysconf(_SC_PAGESIZE);
-        if sz > 0 {
-            sz as usize
-        } else {
-            4096
+impl ChildStage {
+    /// Dirtying bandwidth: bytes of the pages touched per second of touching.
+    fn touch_mb_per_s(&self) -> f64 {
+        if self.touch_ms <= 0.0 {
+            return 0.0;
         }
+        self.touched_bytes as f64 / (1024.0 * 1024.0) / (self.touch_ms / 1000.0)
+    }
+
+    /// Mean nanoseconds per `page` written after the first, the steady rate
+    /// the first write is set against; `None` below two pages.
+    fn later_write_ns(&self, page: usize) -> Option<f64> {
+        let first = self.first_write?;
+        let pages = self.touched_bytes / page.max(1) as u64;
+        (pages > 1).then(|| (self.touch_ms * 1_000_000.0 - first.ns as f64) / (pages - 1) as f64)
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"stage\":{},\"rss_kb\":{},\"private_dirty_kb\":{},\"shared_dirty_kb\":{},\
+\"shared_clean_kb\":{},\"pss_kb\":{},\"missing_pages\":{},\"zeroed_pages\":{},\"parent_private_dirty_kb\":{},\
+\"parent_shared_dirty_kb\":{},\"parent_shared_clean_kb\":{},\"parent_pss_kb\":{},\"anon_huge_kb\":{},\
+\"hugetlb_kb\":{},\"locked_kb\":{},\"swap_kb\":{},\"buffer\":{},\"frames\":{},\"soft_dirty_pages\":{},\"minor_faults\":{},\"major_faults\":{},\"stat_min_flt\":{},\
+\"stat_maj_flt\":{},\"touch_ms\":{:.4},\"touched_bytes\":{},\"touch_mb_per_s\":{:.1},\"first_write\":{},\"measured_at\":{},\
+\"touch_started_at\":{},\"touch_ended_at\":{},\"collected_at\":{},\"numa_kb\":{}}}",
+            json_string(&self.stage),
+            self.rss_kb,
+            self.private_dirty_kb,
+            self.shared_dirty_kb,
+            self.shared_clean_kb,
+            self.pss_kb,
+            self.missing_pages,
+            self.zeroed_pages,
+            self.parent_private_dirty_kb,
+            self.parent_shared_dirty_kb,
+            self.parent_shared_clean_kb,
+            self.parent_pss_kb,
+            self.anon_huge_kb,
+            self.hugetlb_kb,
+            self.locked_kb,
+            self.swap_kb,
+            self.buffer.to_json(),
+            self.frames.map_or_else(|| "null".to_string(), FrameSharing::to_json),
+            self.soft_dirty_pages
+                .map_or_else(|| "null".to_string(), |pages| pages.to_string()),
+            self.minor_faults,
+            self.major_faults,
+            self.stat_min_flt,
+            self.stat_maj_flt,
+            self.touch_ms,
+            self.touched_bytes,
+            self.touch_mb_per_s(),
+            self.first_write
+                .map_or_else(|| "null".to_string(), FirstWrite::to_json),
+            self.measured_at.to_json(),
+            Timestamp::optional_json(self.touch_started_at),
+            Timestamp::optional_json(self.touch_ended_at),
+            Timestamp::optional_json(self.collected_at),
+            self.nodes.to_json()
+        )
     }
 }
 
-fn touch_pages(data: &mut [u8], page: usize) {
-    if page == 0 {
-        return;
+impl Timestamp {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"monotonic_ns\":{},\"unix_ns\":{},\"utc\":{}}}",
+            self.monotonic_ns,
+            self.unix_ns,
+            json_string(&self.utc())
+        )
     }
-    for chunk in data.chunks_mut(page) {
-        if let Some(first) = chunk.first_mut() {
-            *first = first.wrapping_add(1);
-        }
+
+    fn optional_json(at: Option<Timestamp>) -> String {
+        at.map_or_else(|| "null".to_string(), Timestamp::to_json)
+    }
+
+    /// The monotonic or the Unix time as a CSV cell, empty when `None`.
+    fn csv_cell(at: Option<Timestamp>, clock: fn(Timestamp) -> u64) -> String {
+        at.map_or_else(String::new, |at| clock(at).to_string())
     }
 }
 
-fn write_all(fd: RawFd, payload: &[u8]) -> io::Result<()> {
-    let mut total = 0;
-    while total < payload.len() {
-        let written = unsafe { write(fd, payload[total..].as_ptr(), payload.len() - total) };
-        if written < 0 {
-            return Err(io::Error::last_os_error());
-        }
-        total += written as usize;
+impl ParentCheckpoint {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"checkpoint\":{},\"rss_kb\":{},\"private_dirty_kb\":{},\"shared_dirty_kb\":{},\"swap_kb\":{},\"buffer\":{}}}",
+            json_string(self.checkpoint.label()),
+            self.rss_kb,
+            self.private_dirty_kb,
+            self.shared_dirty_kb,
+            self.swap_kb,
+            self.buffer.to_json()
+        )
     }
-    Ok(())
 }
 
-fn read_to_end(fd: RawFd) -> io::Result<Vec<u8>> {
-    let mut buffer = Vec::new();
-    let mut temp = [0u8; 1024];
-    loop {
-        let read_bytes = unsafe { read(fd, temp.as_mut_ptr(), temp.len()) };
-        if read_bytes < 0 {
-            return Err(io::Error::last_os_error());
-        }
-        if read_bytes == 0 {
-            break;
-        }
-        buffer.extend_from_slice(&temp[..read_bytes as usize]);
+impl DirtySample {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"pages_touched\":{},\"private_dirty_kb\":{},\"buffer_private_dirty_kb\":{},\"elapsed_ms\":{:.4}}}",
+            self.pages_touched, self.private_dirty_kb, self.buffer_private_dirty_kb, self.elapsed_ms
+        )
     }
-    Ok(buffer)
 }
 
-fn wait_child(pid: i32) -> io::Result<i32> {
-    let mut status = 0;
-    loop {
-        let result = unsafe { waitpid(pid, &mut status, 0) };
-        if result < 0 {
-            let err = io::Error::last_os_error();
-            if err.kind() == io::ErrorKind::Interrupted {
-                continue;
-            }
-            return Err(err);
-        }
-        break;
+impl Round {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"pages_touched\":{},\"rss_kb\":{},\"private_dirty_kb\":{},\
+\"buffer_private_dirty_kb\":{},\"touch_ms\":{:.4}}}",
+            self.pages_touched,
+            self.rss_kb,
+            self.private_dirty_kb,
+            self.buffer_private_dirty_kb,
+            self.touch_ms
+        )
     }
-    Ok(status)
 }
 
-fn parse_child_report(data: &[u8]) -> Result<(ChildStage, ChildStage), String> {
-    let text = String::from_utf8_lossy(data);
-    let mut stages = Vec::new();
-    for line in text.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        let mut stage = ChildStage {
-            stage: String::new(),
-            rss_kb: 0,
-            private_dirty_kb: 0,
-            touch_ms: 0.0,
+impl ChildRun {
+    /// The `--read-stage` read pass and the write pass after it. Faults are
+    /// counted per stage, so the write pass's leave out those of reading.
+    fn passes(&self) -> Option<(PassCost, PassCost)> {
+        let read = self.post_read.as_ref()?;
+        let cost = |stage: &ChildStage, from: &ChildStage| PassCost {
+            faults: stage.minor_faults + stage.major_faults,
+            copied_kb: stage
+                .buffer
+                .private_dirty_kb
+                .saturating_sub(from.buffer.private_dirty_kb),
+            ms: stage.touch_ms,
         };
-        let mut parts = line.split(',');
-        stage.stage = parts
-            .next()
-            .ok_or_else(|| "missing stage label".to_string())?
-            .trim()
-            .to_string();
-        for entry in parts {
-            let (key, value) = entry
-                .trim()
-                .split_once('=')
-                .ok_or_else(|| format!("invalid entry: {}", entry))?;
-            match key.trim() {
-                "rss_kb" => {
-                    stage.rss_kb = value
-                        .trim()
-                        .parse()
-                        .map_err(|e| format!("bad rss_kb value: {e}"))?
-                }
-                "private_dirty_kb" => {
-                    stage.private_dirty_kb = value
-                        .trim()
-                        .parse()
-                        .map_err(|e| format!("bad private_dirty_kb value: {e}"))?
-                }
-                "touch_ms" => {
-                    stage.touch_ms = value
-                        .trim()
-                        .parse()
-                        .map_err(|e| format!("bad touch_ms value: {e}"))?
-                }
-                other => return Err(format!("unknown key {other} in child report")),
-            }
-        }
-        stages.push(stage);
+        Some((cost(read, &self.post_fork), cost(&self.post_write, read)))
     }
-    if stages.len() != 2 {
-        return Err("expected exactly two stages from child".into());
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"fork_us\":{:.1},\"forked_at\":{},\"generation\":{},\"post_fork\":{},\"post_read\":{},\"mid_touch\":{},\"post_write\":{},\"post_discard\":{},\"discard\":{},\"rounds\":[{}],\"latency\":{},\"dirty_series\":[{}],\"checksum\":{},\"exit\":{},\"errors\":[{}]}}",
+            self.fork_us,
+            Timestamp::optional_json(self.forked_at),
+            self.generation,
+            self.post_fork.to_json(),
+            self.post_read
+                .as_ref()
+                .map_or_else(|| "null".to_string(), ChildStage::to_json),
+            self.mid_touch
+                .as_ref()
+                .map_or_else(|| "null".to_string(), ChildStage::to_json),
+            self.post_write.to_json(),
+            self.post_discard
+                .as_ref()
+                .map_or_else(|| "null".to_string(), ChildStage::to_json),
+            self.discard
+                .map_or_else(|| "null".to_string(), DiscardReport::to_json),
+            self.rounds
+                .iter()
+                .map(Round::to_json)
+                .collect::<Vec<_>>()
+                .join(","),
+            self.latency
+                .map_or_else(|| "null".to_string(), WriteLatency::to_json),
+            self.dirty_series
+                .iter()
+                .map(|sample| sample.to_json())
+                .collect::<Vec<_>>()
+                .join(","),
+            self.checksum
+                .map_or_else(|| "null".to_string(), |sum| json_string(&format!("{sum:#018x}"))),
+            exit_json(self.exit),
+            self.errors
+                .iter()
+                .map(|error| json_string(error))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
     }
-    Ok((stages.remove(0), stages.remove(0)))
 }
 
-fn child_routine(data: &mut [u8], pipe_write: RawFd, page: usize) -> ! {
-    let pid = std::process::id();
-    let rss_post_fork = read_rss_kb(pid).unwrap_or_default();
-    let private_dirty_post_fork = read_private_dirty_kb(pid).unwrap_or_default();
+impl ExperimentResult {
+    /// A CSV cell for the parent's figures at `checkpoint`, empty when the
+    /// run had no children to step through it.
+    fn parent_cell(&self, checkpoint: Checkpoint, field: fn(&ParentCheckpoint) -> u64) -> String {
+        self.parent_checkpoints
+            .iter()
+            .find(|parent| parent.checkpoint == checkpoint)
+            .map_or_else(String::new, |parent| field(parent).to_string())
+    }
 
-    let start = Instant::now();
-    touch_pages(data, page);
-    let touch_ms = start.elapsed().as_secs_f64() * 1000.0;
+    fn to_json(&self) -> String {
+        let children: Vec<String> = self
+            .children
+            .iter()
+            .enumerate()
+            .map(|(index, child)| format!("{{\"child\":{},{}", index, &child.to_json()[1..]))
+            .collect();
+        let optional = |run: &Option<ChildRun>| {
+            run.as_ref()
+                .map_or_else(|| "null".to_string(), ChildRun::to_json)
+        };
+        format!(
+            "{{\"size_mb\":{},\"run\":{},\"alloc\":{},\"touch_order\":{},\"stride\":{},\"write_bytes_per_page\":{},\
+\"thp\":{},\"spawn\":{},\"depth\":{},\"concurrent\":{},\"fork_advice\":{},\"discard\":{},\"mlock\":{},\"parent_locked_kb\":{},\"parent_swap_kb\":{},\"ksm\":{},\"cgroup\":{},\"numa\":{},\"parent_numa_kb\":{},\"parent_rss_kb\":{},\"parent_private_dirty_kb\":{},\"parent_rss_after_kb\":{},\"parent_private_dirty_after_kb\":{},\"parent_anon_huge_kb\":{},\"parent_buffer\":{},\"parent_fill_faults\":{},\
+\"parent_wait_faults\":{},\"vm_faults\":{},\"parent_visible_pages\":{},\"children\":[{}],\"thread_baseline\":{},\"eager_copy\":{},\"exec\":{},\"verification\":{},\"parent_write\":{},\"parent_checkpoints\":[{}]}}",
+            self.size_mb,
+            self.run,
+            json_string(self.alloc.label()),
+            json_string(self.touch_order.label()),
+            self.stride,
+            self.write_bytes,
+            json_string(self.thp.map_or("default", ThpMode::label)),
+            json_string(self.spawn.label()),
+            self.depth,
+            self.concurrent,
+            json_string(self.fork_advice.map_or("none", ForkAdvice::label)),
+            json_string(self.discard.map_or("none", DiscardAdvice::label)),
+            self.mlock,
+            self.parent_locked_kb,
+            self.parent_swap_kb,
+            self.ksm.map_or_else(|| "null".to_string(), KsmReport::to_json),
+            self.cgroup
+                .map_or_else(|| "null".to_string(), CgroupReport::to_json),
+            self.numa.map_or_else(
+                || "null".to_string(),
+                |nodes| format!(
+                    "{{\"buffer_node\":{},\"child_node\":{}}}",
+                    nodes.buffer, nodes.child
+                )
+            ),
+            self.parent_nodes.to_json(),
+            self.parent_rss_kb,
+            self.parent_private_dirty_kb,
+            self.parent_rss_after_kb,
+            self.parent_private_dirty_after_kb,
+            self.parent_anon_huge_kb,
+            self.parent_buffer.to_json(),
+            self.parent_fill_faults.to_json(),
+            self.parent_wait_faults.to_json(),
+            self.vm_faults
+                .map_or_else(|| "null".to_string(), VmFaults::to_json),
+            self.parent_visible_pages,
+            children.join(","),
+            optional(&self.thread_baseline),
+            optional(&self.eager_copy),
+            self.exec
+                .map_or_else(|| "null".to_string(), ExecReport::to_json),
+            self.verification
+                .map_or_else(|| "null".to_string(), Verification::to_json),
+            optional(&self.parent_write),
+            self.parent_checkpoints
+                .iter()
+                .map(|parent| parent.to_json())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
 
-    let rss_post_write = read_rss_kb(pid).unwrap_or_default();
-    let private_dirty_post_write = read_private_dirty_kb(pid).unwrap_or_default();
+/// Writes one JSON document: run metadata plus every result with its child
+/// stages nested, mirroring the structs rather than the flat CSV columns.
+fn kernel_release() -> String {
+    #[cfg(target_os = "macos")]
+    let release = sys::sysctl("kern.osrelease").map(|bytes| {
+        String::from_utf8_lossy(&bytes)
+            .trim_end_matches('\0')
+            .to_string()
+    });
+    #[cfg(not(target_os = "macos"))]
+    let release =
+        std::fs::read_to_string("/proc/sys/kernel/osrelease").map(|text| text.trim().to_string());
+    release.unwrap_or_else(|_| "unknown".into())
+}
 
-    let report = format!(
-        "post_fork,rss_kb={rss_post_fork},private_dirty_kb={private_dirty_post_fork},touch_ms=0.0\n\
-post_write,rss_kb={rss_post_write},private_dirty_kb={private_dirty_post_write},touch_ms={touch_ms:.4}\n"
-    );
+/// What was worked out from the results once every experiment had run.
+#[derive(Clone, Copy)]
+struct Analysis<'a> {
+    summaries: &'a [SizeSummary],
+    pages: &'a [PageComparison],
+    scaling: &'a [ScalingFit],
+}
 
-    if let Err(err) = write_all(pipe_write, report.as_bytes()) {
-        eprintln!("child failed to write report: {err}");
+/// Writes a Markdown report for submission: the configuration, the
+/// environment, every result, the `--repeat` summary, the fit across sizes,
+/// and notes on reading the numbers.
+fn write_report(
+    path: &Path,
+    config: &Config,
+    metadata: &RunMetadata,
+    results: &[ExperimentResult],
+    analysis: Analysis,
+) -> io::Result<()> {
+    let Analysis {
+        summaries,
+        pages,
+        scaling,
+    } = analysis;
+    let mut md = String::new();
+    md.push_str("# Copy-on-Write Experiment Report\n\n## Configuration\n\n");
+    md.push_str("| Setting | Value |\n|---|---|\n");
+    let sizes: Vec<String> = config.sizes_mb.iter().map(|mb| mb.to_string()).collect();
+    let settings = [
+        ("Sizes (MB)", sizes.join(", ")),
+        ("Allocation", config.alloc.label().to_string()),
+        ("Touch order", config.touch_order.label().to_string()),
+        ("Stride (pages)", config.stride.to_string()),
+        ("Touch rounds", config.rounds.to_string()),
+        ("Write size", config.write_size.arg()),
+        ("Children", config.children.to_string()),
+        ("Fork-chain depth", config.depth.to_string()),
+        ("Concurrent touch", config.concurrent.to_string()),
+        ("Read-only stage", config.read_stage.to_string()),
+        (
+            "Discard after writing",
+            config
+                .discard
+                .map_or("none", DiscardAdvice::label)
+                .to_string(),
+        ),
+        ("Exec child", config.exec.to_string()),
+        ("Checksum verification", config.verify.to_string()),
+        ("Spawn", config.spawn.label().to_string()),
+        (
+            "THP",
+            config.thp.map_or("default", ThpMode::label).to_string(),
+        ),
+        (
+            "Fork advice",
+            config
+                .fork_advice
+                .map_or("none", ForkAdvice::label)
+                .to_string(),
+        ),
+        (
+            "Parent write",
+            config
+                .parent_write
+                .map_or("none", ParentWrite::label)
+                .to_string(),
+        ),
+        ("mlock", config.mlock.to_string()),
+        ("KSM", config.ksm.to_string()),
+        (
+            "Memory budget",
+            config
+                .max_memory_mb
+                .map_or_else(|| "none".to_string(), |mb| format!("{mb} MB")),
+        ),
+        (
+            "NUMA nodes",
+            config.numa.map_or_else(
+                || "default".to_string(),
+                |nodes| format!("buffer {}, children {}", nodes.buffer, nodes.child),
+            ),
+        ),
+        (
+            "Cgroup memory limit",
+            config
+                .cgroup_limit_mb
+                .map_or_else(|| "none".to_string(), |mb| format!("{mb} MB")),
+        ),
+        ("Page-size comparison", config.compare_pages.to_string()),
+        ("Warmup runs", config.warmup.to_string()),
+        ("Measured runs", config.repeat.to_string()),
+        ("Seed", config.seed.to_string()),
+    ];
+    for (name, value) in settings {
+        md.push_str(&format!("| {name} | {value} |\n"));
     }
 
-    unsafe {
-        close(pipe_write);
-        _exit(0);
+    md.push_str("\n## Environment\n\n| Property | Value |\n|---|---|\n");
+    md.push_str(&format!("| Started | {} |\n", metadata.timestamp));
+    md.push_str(&format!("| Host | {} |\n", metadata.hostname));
+    md.push_str(&format!("| Kernel | {} |\n", metadata.kernel));
+    md.push_str(&format!(
+        "| CPU | {} ({} CPUs) |\n",
+        metadata.cpu_model, metadata.cpus
+    ));
+    if let Some(kb) = metadata.mem_total_kb {
+        md.push_str(&format!("| Memory | {kb} kB |\n"));
+    }
+    if let Some(swap) = metadata.swap {
+        md.push_str(&format!(
+            "| Swap | {} kB ({} kB free) |\n",
+            swap.total_kb, swap.free_kb
+        ));
+    }
+    md.push_str(&format!("| Page size | {} B |\n", metadata.page_size));
+    md.push_str(&format!(
+        "| Huge page size | {} kB |\n",
+        metadata.huge_page_size / 1024
+    ));
+    md.push_str(&format!(
+        "| System THP policy | {} |\n",
+        metadata.system_thp
+    ));
+    md.push_str(&format!(
+        "| Memory source | {} |\n",
+        memory_source().label()
+    ));
+    md.push_str(&format!("| RSS source | {} |\n", rss_source().label()));
+    if let Some(overcommit) = metadata.overcommit {
+        md.push_str(&format!(
+            "| Overcommit | {}; CommitLimit {} kB, Committed_AS {} kB |\n",
+            overcommit.summary(),
+            overcommit.commit_limit_kb,
+            overcommit.committed_kb
+        ));
     }
-}
 
-fn run_experiment(size_mb: usize) -> Result<ExperimentResult, String> {
-    let size_bytes = size_mb * 1024 * 1024;
-    println!("== Running Copy-on-Write demo for {size_mb} MB ==");
+    md.push_str(
+        "\n## Results\n\nMemory figures are in kB. \"Fork\" is the child's state right after \
+`fork()`, \"Touch\" after it wrote to every page in its order.\n\n\
+| Size (MB) | Run | Child | Parent RSS | Parent RSS after | RSS fork | RSS touch | \
+Private_Dirty fork | Private_Dirty touch | Touch (ms) | MB/s dirtied | fork() (us) | \
+Minor faults touching |\n\
+|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n",
+    );
+    for entry in results {
+        for (index, child) in entry.children.iter().enumerate() {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {:.3} | {:.1} | {:.1} | {} |\n",
+                entry.size_mb,
+                entry.run,
+                index,
+                entry.parent_rss_kb,
+                entry.parent_rss_after_kb,
+                child.post_fork.rss_kb,
+                child.post_write.rss_kb,
+                child.post_fork.private_dirty_kb,
+                child.post_write.private_dirty_kb,
+                child.post_write.touch_ms,
+                child.post_write.touch_mb_per_s(),
+                child.fork_us,
+                child.post_write.minor_faults
+            ));
+        }
+    }
 
-    let mut data = vec![0u8; size_bytes];
-    for (i, byte) in data.iter_mut().enumerate() {
-        *byte = (i & 0xFF) as u8;
+    md.push_str(
+        "\n## Proportional share\n\nFrom smaps_rollup, in kB, for the child and for the parent \
+read at the same moment. Pss splits every shared page between the processes mapping it, so right \
+after the fork each holds about half of the buffer; the copies move it into the writer's \
+Private_Dirty.\n\n\
+| Size (MB) | Run | Child | Pss fork | Pss touch | Parent Pss fork | Parent Pss touch | \
+Shared_Clean fork | Shared_Clean touch | Shared_Dirty fork | Shared_Dirty touch |\n\
+|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n",
+    );
+    for entry in results {
+        for (index, child) in entry.children.iter().enumerate() {
+            let (fork, touch) = (&child.post_fork, &child.post_write);
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+                entry.size_mb,
+                entry.run,
+                index,
+                fork.pss_kb,
+                touch.pss_kb,
+                fork.parent_pss_kb,
+                touch.parent_pss_kb,
+                fork.shared_clean_kb,
+                touch.shared_clean_kb,
+                fork.shared_dirty_kb,
+                touch.shared_dirty_kb
+            ));
+        }
     }
 
-    let parent_pid = std::process::id();
-    let parent_rss =
-        read_rss_kb(parent_pid).map_err(|e| format!("failed to read parent RSS: {e}"))?;
-    let parent_private_dirty = read_private_dirty_kb(parent_pid).unwrap_or(0);
+    if config.read_stage {
+        md.push_str(
+            "\n## Read pass against write pass\n\nEach child first read one byte of every page \
+it would write, then wrote them. Faults are counted per pass; \"copied\" is the growth of the \
+buffer's Private_Dirty over the pass, in kB.\n\n\
+| Size (MB) | Run | Child | Read faults | Read copied | Read (ms) | Write faults | Write copied | \
+Write (ms) |\n|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n",
+        );
+        for entry in results {
+            for (index, child) in entry.children.iter().enumerate() {
+                if let Some((read, write)) = child.passes() {
+                    md.push_str(&format!(
+                        "| {} | {} | {} | {} | {} | {:.3} | {} | {} | {:.3} |\n",
+                        entry.size_mb,
+                        entry.run,
+                        index,
+                        read.faults,
+                        read.copied_kb,
+                        read.ms,
+                        write.faults,
+                        write.copied_kb,
+                        write.ms
+                    ));
+                }
+            }
+        }
+    }
 
-    println!(
-        "Parent RSS before fork: {} kB (Private_Dirty {} kB)",
-        parent_rss, parent_private_dirty
-    );
+    if let Some(advice) = config.discard {
+        md.push_str(&format!(
+            "\n## Discarding the copies\n\nAfter writing, each child applied {} to every page \
+it wrote, was measured, then read the pages back. Under MADV_DONTNEED a private copy is dropped at \
+once and reads see zero-filled pages, not the parent's data; under MADV_FREE the pages stay \
+resident as LazyFree, keeping their contents, until memory runs short. Memory figures in kB.\n\n\
+| Size (MB) | Run | Child | RSS touch | RSS discard | Private_Dirty discard | LazyFree | \
+Kept | Zeroed | Other | Read-back faults | RSS read-back |\n\
+|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n",
+            advice.label()
+        ));
+        for entry in results {
+            for (index, child) in entry.children.iter().enumerate() {
+                let (Some(stage), Some(report)) = (&child.post_discard, child.discard) else {
+                    continue;
+                };
+                md.push_str(&format!(
+                    "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+                    entry.size_mb,
+                    entry.run,
+                    index,
+                    child.post_write.rss_kb,
+                    stage.rss_kb,
+                    stage.private_dirty_kb,
+                    report.lazy_free_kb,
+                    report.kept_pages,
+                    report.zeroed_pages,
+                    report.other_pages,
+                    report.reread_faults,
+                    report.reread_rss_kb
+                ));
+            }
+        }
+    }
 
-    let page = page_size();
-    let mut pipe_fds = [0i32; 2];
-    if unsafe { pipe(pipe_fds.as_mut_ptr()) } != 0 {
-        return Err(format!("pipe failed: {}", io::Error::last_os_error()));
+    if !summaries.is_empty() {
+        md.push_str("\n## Summary over repeated runs\n\n");
+        md.push_str("| Size (MB) | Runs | Metric | Mean | Stddev | Min | Max |\n");
+        md.push_str("|---:|---:|---|---:|---:|---:|---:|\n");
+        for summary in summaries {
+            for (name, stats) in &summary.metrics {
+                md.push_str(&format!(
+                    "| {} | {} | {name} | {:.3} | {:.3} | {:.3} | {:.3} |\n",
+                    summary.size_mb, summary.runs, stats.mean, stats.stddev, stats.min, stats.max
+                ));
+            }
+        }
+    }
+
+    if !scaling.is_empty() {
+        md.push_str(
+            "\n## Scaling across sizes\n\nLeast-squares lines through every child of every run, \
+against the buffer size. \"Copied\" is the growth of the buffer's Private_Dirty from the fork \
+to the end of the touch.\n\n\
+| Metric | Children | Slope (per MB) | Intercept | R² |\n|---|---:|---:|---:|---:|\n",
+        );
+        for fit in scaling {
+            md.push_str(&format!(
+                "| {} ({}) | {} | {:.4} | {:.4} | {:.4} |\n",
+                fit.metric,
+                fit.unit,
+                fit.fit.points,
+                fit.fit.slope,
+                fit.fit.intercept,
+                fit.fit.r_squared
+            ));
+        }
+        if let Some(touch) = scaling.iter().find(|fit| fit.metric == "touch_ms") {
+            md.push_str(&format!(
+                "\nCopy cost ≈ {:.1} us per MB of buffer (R² {:.3}).\n",
+                touch.fit.slope * 1000.0,
+                touch.fit.r_squared
+            ));
+        }
     }
 
-    let pid = unsafe { fork() };
-    if pid < 0 {
-        return Err(format!("fork failed: {}", io::Error::last_os_error()));
+    if !pages.is_empty() {
+        md.push_str(
+            "\n## Page sizes\n\nEach size run with base pages, transparent huge pages and, \
+where reserved, hugetlb pages; means over every child of every run. \"Child huge\" is the \
+child's AnonHugePages plus HugetlbPages after touching, \"Parent AnonHuge\" the parent's \
+AnonHugePages before forking.\n\n\
+| Size (MB) | Pages | Page (kB) | Runs | fork() (us) | Touch (ms) | Faults | us/fault | \
+Private_Dirty (kB) | Child huge (kB) | Parent AnonHuge (kB) |\n\
+|---:|---|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n",
+        );
+        for row in pages {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {:.1} | {:.3} ± {:.3} | {:.0} | {:.2} | {:.0} | {:.0} | {:.0} |\n",
+                row.size_mb,
+                row.variant,
+                row.page_kb,
+                row.runs,
+                row.fork_us,
+                row.touch_ms.mean,
+                row.touch_ms.stddev,
+                row.faults,
+                row.us_per_fault,
+                row.private_dirty_kb,
+                row.huge_kb,
+                row.parent_anon_huge_kb
+            ));
+        }
     }
 
-    if pid == 0 {
-        unsafe {
-            close(pipe_fds[PIPE_READ]);
+    md.push_str("\n## Interpretation\n\n");
+    for entry in results.iter().filter(|entry| entry.run == 0) {
+        let Some(child) = entry.children.first() else {
+            continue;
+        };
+        let buffer_kb = entry.size_mb as u64 * 1024;
+        let copied_kb = child
+            .post_write
+            .buffer
+            .private_dirty_kb
+            .saturating_sub(child.post_fork.buffer.private_dirty_kb);
+        md.push_str(&format!(
+            "- {} MB: the Private_Dirty of the child's buffer mapping grew by {copied_kb} kB while touching, \
+{:.0}% of the buffer, over {} minor faults.\n",
+            entry.size_mb,
+            copied_kb as f64 * 100.0 / buffer_kb as f64,
+            child.post_write.minor_faults
+        ));
+        if let Some(exec) = &entry.exec {
+            md.push_str(&format!(
+                "- {} MB: a child that exec'd right after `fork()` went from {} kB to {} kB of RSS, \
+and the parent's buffer Shared_Dirty from {} kB to {} kB, as exec dropped the child's share of \
+the copy-on-write pages.\n",
+                entry.size_mb,
+                exec.before_rss_kb,
+                exec.after_rss_kb,
+                exec.parent_buffer_before.shared_dirty_kb,
+                exec.parent_buffer_after.shared_dirty_kb
+            ));
+        }
+        if let Some(eager) = &entry.eager_copy {
+            md.push_str(&format!(
+                "- {} MB: copying the whole buffer eagerly right after `fork()` took {:.3} ms and \
+{} kB of Private_Dirty, against {:.3} ms and {copied_kb} kB for the child's lazy copies.\n",
+                entry.size_mb,
+                eager.post_write.touch_ms,
+                eager
+                    .post_write
+                    .private_dirty_kb
+                    .saturating_sub(eager.post_fork.private_dirty_kb),
+                child.post_write.touch_ms
+            ));
         }
-        child_routine(&mut data, pipe_fds[PIPE_WRITE], page);
     }
+    md.push_str(
+        "- Right after `fork()` the child's RSS already includes the whole buffer, but its \
+Private_Dirty stays small: the pages are mapped in both processes and counted in both RSS \
+figures, yet exist only once in memory.\n\
+- Each first write to a shared page takes a minor fault and copies the page, so Private_Dirty \
+grows by one page per touched page and the minor-fault count tracks the number of pages \
+touched.\n\
+- With `--alloc shared` or `memfd` nothing is copied: the writes land in the pages the parent \
+sees, and Private_Dirty does not grow.\n\
+- With `--alloc file` the pages start out as the file's page cache, clean and shared with the \
+parent. Each write copies one into anonymous Private_Dirty memory, and the file never changes.\n",
+    );
+    std::fs::write(path, md)
+}
 
-    unsafe {
-        close(pipe_fds[PIPE_WRITE]);
+/// The JSON `metadata` object: the system, and the settings that apply to
+/// every result.
+fn metadata_json(config: &Config, metadata: &RunMetadata) -> String {
+    let sizes: Vec<String> = config.sizes_mb.iter().map(|mb| mb.to_string()).collect();
+    format!(
+        "{{{},\"memory_source\":{},\"rss_source\":{},\"seed\":{},\"warmup\":{},\"repeat\":{},\"rounds\":{},\"sizes_mb\":[{}]}}",
+        metadata.to_json(),
+        json_string(memory_source().label()),
+        json_string(rss_source().label()),
+        config.seed,
+        config.warmup,
+        config.repeat,
+        config.rounds,
+        sizes.join(",")
+    )
+}
+
+fn write_json(
+    path: &PathBuf,
+    config: &Config,
+    metadata: &RunMetadata,
+    results: &[ExperimentResult],
+    summaries: &[SizeSummary],
+    scaling: &[ScalingFit],
+) -> io::Result<()> {
+    let entries: Vec<String> = results
+        .iter()
+        .map(|entry| format!("    {}", entry.to_json()))
+        .collect();
+    let mut file = File::create(path)?;
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"metadata\": {},", metadata_json(config, metadata))?;
+    writeln!(file, "  \"results\": [")?;
+    writeln!(file, "{}", entries.join(",\n"))?;
+    let sections = [
+        (
+            "summary",
+            summaries
+                .iter()
+                .map(SizeSummary::to_json)
+                .collect::<Vec<_>>(),
+        ),
+        ("scaling", scaling.iter().map(ScalingFit::to_json).collect()),
+    ];
+    write!(file, "  ]")?;
+    for (name, items) in sections.iter().filter(|(_, items)| !items.is_empty()) {
+        let items: Vec<String> = items.iter().map(|item| format!("    {item}")).collect();
+        writeln!(file, ",")?;
+        writeln!(file, "  \"{name}\": [")?;
+        writeln!(file, "{}", items.join(",\n"))?;
+        write!(file, "  ]")?;
     }
-    let payload = read_to_end(pipe_fds[PIPE_READ])
-        .map_err(|e| format!("failed to read child report: {e}"))?;
-    unsafe {
-        close(pipe_fds[PIPE_READ]);
+    writeln!(file)?;
+    writeln!(file, "}}")?;
+    Ok(())
+}
+
+/// How the buffer's pages stand when a `--bench-touch` sample starts.
+#[derive(Clone, Copy)]
+enum BenchMemory {
+    /// Already written by this process, so only the loop itself is timed.
+    Resident,
+    /// Freshly mapped, so every page touched takes a zero-fill fault.
+    Faulting,
+}
+
+impl BenchMemory {
+    fn label(self) -> &'static str {
+        match self {
+            BenchMemory::Resident => "resident",
+            BenchMemory::Faulting => "faulting",
+        }
     }
+}
 
-    wait_child(pid).map_err(|e| format!("waitpid failed: {e}"))?;
+/// Points stdout at /dev/null for `--quiet`, children and helpers included,
+/// and returns the original.
+fn silence_stdout() -> io::Result<File> {
+    let null = OpenOptions::new().write(true).open("/dev/null")?;
+    Ok(File::from(sys::redirect_stdout(null.as_fd())?))
+}
 
-    let (post_fork, post_write) = parse_child_report(&payload)?;
+/// Shows how the overcommit policy meets copy-on-write (`--overcommit`):
+/// maps a private writable buffer sized against the commit limit without
+/// touching it, then forks. Every private writable page is charged once per
+/// process that maps it, so under strict accounting (mode 2) the buffer is
+/// sized to fit once but not twice, and the fork is refused although it would
+/// copy nothing. Under the other modes the buffer is sized so that two full
+/// copies would not fit in `MemAvailable`: the allocation and the fork both
+/// succeed, and only the page faults of the copies could fail. Neither side
+/// writes more than one page, so nothing is at risk.
+fn run_overcommit_demo() -> Result<(), Error> {
+    let before = Overcommit::read()
+        .ok_or_else(|| Error::Unsupported("the overcommit settings are unreadable".into()))?;
+    let available_kb = read_meminfo("MemAvailable").unwrap_or(0);
     println!(
-        "Child after fork: RSS {} kB, Private_Dirty {} kB",
-        post_fork.rss_kb, post_fork.private_dirty_kb
+        "Overcommit policy: {}; CommitLimit {} MB, Committed_AS {} MB, MemAvailable {} MB",
+        before.summary(),
+        before.commit_limit_kb / 1024,
+        before.committed_kb / 1024,
+        available_kb / 1024
     );
+    let target_kb = if before.mode == 2 {
+        before.headroom_kb().max(0) as u64
+    } else {
+        available_kb
+    };
+    let size_mb = (target_kb / 1024 * 3 / 4) as usize;
+    if size_mb == 0 {
+        return Err(Error::Unsupported(
+            "no commit headroom is left to size the buffer against".into(),
+        ));
+    }
+    let mut buffer = match Buffer::map(size_mb << 20, MAP_PRIVATE) {
+        Ok(buffer) => buffer,
+        Err(err) => {
+            println!(
+                "Mapping {size_mb} MB failed at once ({err}): the allocation itself was refused"
+            );
+            return Ok(());
+        }
+    };
+    let mapped = Overcommit::read().unwrap_or(before);
     println!(
-        "Child after touching pages: RSS {} kB, Private_Dirty {} kB (touch {:.3} ms)",
-        post_write.rss_kb, post_write.private_dirty_kb, post_write.touch_ms
+        "Mapped {size_mb} MB private and writable, untouched: Committed_AS {:+} MB, RSS {} kB",
+        (mapped.committed_kb as i64 - before.committed_kb as i64) / 1024,
+        read_rss_kb(std::process::id()).unwrap_or(0)
     );
-
-    Ok(ExperimentResult {
-        size_mb,
-        parent_rss_kb: parent_rss,
-        child_post_fork: post_fork,
-        child_post_write: post_write,
-    })
+    let data = buffer.as_mut_slice();
+    let (wait_read, wait_write) = sys::create_pipe().map_err(Error::sys("pipe"))?;
+    let pid = match sys::fork_process() {
+        Ok(Fork::Child) => {
+            drop(wait_write);
+            // One write still takes a copy, whatever was promised.
+            data[0] = 1;
+            // Stays mapped until the parent has read the counters.
+            let _ = sys::read_fd(wait_read.as_fd(), &mut [0u8; 1]);
+            sys::exit_now(0);
+        }
+        Ok(Fork::Parent(pid)) => pid,
+        Err(err) => {
+            if err.raw_os_error() == Some(ENOMEM) {
+                println!(
+                    "fork() failed: {err}. The child would have been charged another {size_mb} MB, \
+past the {} MB of commit left, although copy-on-write would copy nothing until written",
+                    mapped.headroom_kb().max(0) / 1024
+                );
+                return Ok(());
+            }
+            return Err(Error::sys("fork")(err));
+        }
+    };
+    drop(wait_read);
+    // Give the child's page write time to land before reading the counters.
+    std::thread::sleep(Duration::from_millis(50));
+    let forked = Overcommit::read().unwrap_or(mapped);
+    drop(wait_write);
+    let status = wait_child(pid, None)?;
+    println!(
+        "fork() succeeded: Committed_AS {:+} MB to {} MB against a CommitLimit of {} MB, \
+which the {} policy does not enforce",
+        (forked.committed_kb as i64 - mapped.committed_kb as i64) / 1024,
+        forked.committed_kb / 1024,
+        forked.commit_limit_kb / 1024,
+        forked.label()
+    );
+    println!(
+        "Both processes may now write all {size_mb} MB, {} MB of copies against {} MB available: \
+the allocation and the fork succeeded, and it is the page faults of the copies that would fail, \
+through the OOM killer rather than ENOMEM (child {status})",
+        size_mb * 2,
+        available_kb / 1024
+    );
+    Ok(())
 }
 
-fn write_csv(path: &PathBuf, results: &[ExperimentResult]) -> io::Result<()> {
-    let mut file = File::create(path)?;
-    writeln!(
-        file,
-        "size_mb,parent_rss_kb,child_post_fork_rss_kb,child_post_fork_private_dirty_kb,\
-child_post_write_rss_kb,child_post_write_private_dirty_kb,touch_ms"
-    )?;
-    for entry in results {
-        writeln!(
-            file,
-            "{},{},{},{},{},{},{}",
-            entry.size_mb,
-            entry.parent_rss_kb,
-            entry.child_post_fork.rss_kb,
-            entry.child_post_fork.private_dirty_kb,
-            entry.child_post_write.rss_kb,
-            entry.child_post_write.private_dirty_kb,
-            entry.child_post_write.touch_ms
-        )?;
+/// Times `touch_pages` on a private mapping of the smallest configured size
+/// for every mix of memory state, touch order and write size
+/// (`--bench-touch`), and prints one line per variant. Runs in this process
+/// alone: no fork, so no copy-on-write, just the loop and its faults.
+fn run_touch_bench(config: &Config) -> Result<(), Error> {
+    let size_mb = config.sizes_mb.iter().copied().min().unwrap_or(64);
+    let len = size_mb * 1024 * 1024;
+    let page = page_size();
+    let pages = len.div_ceil(page);
+    println!(
+        "== Touch loop benchmark: {size_mb} MB mmap buffer, {pages} pages, {BENCH_SAMPLES} \
+samples after {BENCH_WARMUP} warmups (mean ± stddev, min..max) =="
+    );
+    let map = || -> Result<Buffer, Error> {
+        let mut buffer =
+            Buffer::allocate(AllocKind::Mmap, len).map_err(Error::sys("mapping the buffer"))?;
+        if let Some(thp) = config.thp {
+            thp.apply(buffer.as_mut_slice(), page)
+                .map_err(Error::sys(format!("madvise for THP {}", thp.label())))?;
+        }
+        Ok(buffer)
+    };
+    let mut resident = map()?;
+    resident.as_mut_slice().fill(1);
+    for memory in [BenchMemory::Resident, BenchMemory::Faulting] {
+        for order in [TouchOrder::Sequential, TouchOrder::Random] {
+            let indices = page_order(pages, order, config.seed, config.stride);
+            for write_size in [WriteSize::Byte, WriteSize::FullPage] {
+                let write_bytes = write_size.bytes(page);
+                let mut samples = Vec::with_capacity(BENCH_SAMPLES);
+                for sample in 0..BENCH_WARMUP + BENCH_SAMPLES {
+                    let mut fresh = match memory {
+                        BenchMemory::Resident => None,
+                        BenchMemory::Faulting => Some(map()?),
+                    };
+                    let data = match &mut fresh {
+                        Some(buffer) => buffer.as_mut_slice(),
+                        None => resident.as_mut_slice(),
+                    };
+                    let start = Instant::now();
+                    touch_pages(data, page, &indices, write_bytes);
+                    // Keeps the writes before the clock is read again.
+                    std::hint::black_box(data);
+                    let ms = start.elapsed().as_secs_f64() * 1000.0;
+                    if sample >= BENCH_WARMUP {
+                        samples.push(ms);
+                    }
+                }
+                let stats = Stats::of(&samples);
+                println!(
+                    "{:<9} {:<11} {:<10} {:>9.3} ± {:.3} ms ({:.3}..{:.3}), {:.1} ns/page",
+                    memory.label(),
+                    order.label(),
+                    write_size.arg(),
+                    stats.mean,
+                    stats.stddev,
+                    stats.min,
+                    stats.max,
+                    stats.mean * 1_000_000.0 / indices.len() as f64
+                );
+            }
+        }
     }
     Ok(())
 }
 
+/// Verifies what `config` needs before anything runs (`--check`) and prints
+/// one line per prerequisite. Returns whether no check failed.
+fn run_check(config: &Config) -> bool {
+    let mut items = Vec::new();
+    let mut add = |readiness, name, detail: String| {
+        items.push(CheckItem {
+            readiness,
+            name,
+            detail,
+        })
+    };
+    let pid = std::process::id();
+
+    match read_rss_kb(pid) {
+        Ok(kb) => add(
+            Readiness::Ok,
+            "RSS",
+            format!("{kb} kB for this process, from {}", rss_source().label()),
+        ),
+        Err(err) => add(Readiness::Fail, "RSS", format!("unreadable: {err}")),
+    }
+    if let Some(budget) = config.max_memory_mb {
+        let hard = sys::resource_limit(RLIMIT_AS).map_or(RLIM_INFINITY, |(_, hard)| hard);
+        let (size_kb, _) = read_mapped_kb(pid).unwrap_or_default();
+        let wanted = size_kb * 1024 + budget as u64 * 1024 * 1024;
+        if wanted > hard {
+            add(
+                Readiness::Warn,
+                "memory budget",
+                format!(
+                    "the hard RLIMIT_AS of {} MB is below the {} MB asked for; the hard limit \
+applies",
+                    hard >> 20,
+                    wanted >> 20
+                ),
+            );
+        } else {
+            add(
+                Readiness::Ok,
+                "memory budget",
+                format!("RLIMIT_AS and RLIMIT_DATA will allow {budget} MB above current use"),
+            );
+        }
+    }
+    match read_stat_faults(pid) {
+        Ok(faults) => add(
+            Readiness::Ok,
+            "fault counters",
+            format!("{} minor / {} major so far", faults.min_flt, faults.maj_flt),
+        ),
+        Err(err) => add(
+            Readiness::Warn,
+            "fault counters",
+            format!("unreadable: {err}"),
+        ),
+    }
+    if cfg!(target_os = "linux") {
+        match memory_source() {
+            MemorySource::SmapsRollup => add(
+                Readiness::Ok,
+                "smaps",
+                "/proc/self/smaps_rollup is readable".into(),
+            ),
+            MemorySource::Smaps => add(
+                Readiness::Warn,
+                "smaps",
+                "smaps_rollup is missing; /proc/<pid>/smaps is summed instead (slower)".into(),
+            ),
+            MemorySource::RssOnly => add(
+                Readiness::Warn,
+                "smaps",
+                "neither smaps_rollup nor smaps is readable; Private_Dirty, Shared_Dirty, Shared_Clean, Pss and \
+AnonHugePages read as 0 kB"
+                    .into(),
+            ),
+        }
+        if let Some(nodes) = config.numa {
+            match std::fs::read_to_string("/proc/self/numa_maps") {
+                Ok(_) => add(
+                    Readiness::Ok,
+                    "NUMA",
+                    format!(
+                        "buffer on node {}, children on node {}; numa_maps is readable",
+                        nodes.buffer, nodes.child
+                    ),
+                ),
+                Err(err) => add(
+                    Readiness::Warn,
+                    "NUMA",
+                    format!("numa_maps is unreadable ({err}); pages per node will not be shown"),
+                ),
+            }
+        }
+        // Worst case: every child of every generation copies the whole buffer.
+        let largest = config.sizes_mb.iter().copied().max().unwrap_or(0) as u64;
+        let copies = if matches!(config.alloc, AllocKind::Shared) {
+            0
+        } else {
+            (config.children * config.depth) as u64 + config.eager_copy as u64
+        };
+        let needed_kb = largest * (1 + copies) * 1024;
+        match read_meminfo("MemAvailable") {
+            Some(available) if available >= needed_kb => add(
+                Readiness::Ok,
+                "MemAvailable",
+                format!(
+                    "{} MB available, {} MB needed at worst",
+                    available / 1024,
+                    needed_kb / 1024
+                ),
+            ),
+            Some(available) => add(
+                Readiness::Fail,
+                "MemAvailable",
+                format!(
+                    "{} MB available but {} MB needed at worst; use smaller --sizes or fewer \
+--children",
+                    available / 1024,
+                    needed_kb / 1024
+                ),
+            ),
+            None => add(
+                Readiness::Warn,
+                "MemAvailable",
+                "not found in /proc/meminfo".into(),
+            ),
+        }
+    } else {
+        add(
+            Readiness::Warn,
+            "smaps",
+            "macOS reports RSS and fault counts only".into(),
+        );
+    }
+    match sys::fork_process() {
+        Ok(Fork::Child) => sys::exit_now(0),
+        Ok(Fork::Parent(pid)) => match sys::wait_pid(pid).map(ExitStatus::from_raw) {
+            Ok(status) if status.success() => add(
+                Readiness::Ok,
+                "fork",
+                "a test child forked and exited".into(),
+            ),
+            Ok(status) => add(
+                Readiness::Fail,
+                "fork",
+                format!("the test child ended with {status}"),
+            ),
+            Err(err) => add(Readiness::Fail, "fork", format!("waitpid failed: {err}")),
+        },
+        Err(err) => add(Readiness::Fail, "fork", format!("fork failed: {err}")),
+    }
+    if matches!(config.alloc, AllocKind::Hugetlb) {
+        let free = read_meminfo("HugePages_Free").unwrap_or(0);
+        let huge = huge_page_size() as u64;
+        let largest = config.sizes_mb.iter().copied().max().unwrap_or(0) as u64;
+        let needed =
+            (largest * 1024 * 1024).div_ceil(huge) * (1 + (config.children * config.depth) as u64);
+        let readiness = if free >= needed {
+            Readiness::Ok
+        } else {
+            Readiness::Fail
+        };
+        add(
+            readiness,
+            "huge pages",
+            format!(
+                "{free} free of {} kB, {needed} needed at worst",
+                huge / 1024
+            ),
+        );
+    }
+    if config.soft_dirty {
+        if soft_dirty_supported() {
+            add(
+                Readiness::Ok,
+                "soft-dirty",
+                "pagemap reports soft-dirty bits".into(),
+            );
+        } else {
+            add(
+                Readiness::Fail,
+                "soft-dirty",
+                "the kernel lacks CONFIG_MEM_SOFT_DIRTY".into(),
+            );
+        }
+    }
+
+    println!("== Readiness check ==");
+    for item in &items {
+        println!(
+            "  {:<4}  {}: {}",
+            item.readiness.label(),
+            item.name,
+            item.detail
+        );
+    }
+    let failed = items
+        .iter()
+        .filter(|item| item.readiness == Readiness::Fail)
+        .count();
+    let warned = items
+        .iter()
+        .filter(|item| item.readiness == Readiness::Warn)
+        .count();
+    if failed == 0 {
+        println!("Ready to run ({warned} warning(s))");
+    } else {
+        println!("Not ready: {failed} check(s) failed, {warned} warning(s)");
+    }
+    failed == 0
+}
+
 fn main() {
     let config = match parse_args() {
         Ok(cfg) => cfg,
@@ -386,21 +8349,367 @@ fn main() {
         }
     };
 
+    // SAFETY: a helper's descriptors were handed to it by the parent and
+    // are used by nothing else.
+    if let Some((input, output)) = config.spawn_helper {
+        run_spawn_helper(&config, unsafe { Channel::inherited(input, output) });
+    }
+    if let Some((input, output)) = config.exec_helper {
+        run_exec_helper(unsafe { Channel::inherited(input, output) });
+    }
+    // Kept for the JSON lines of `--machine` once stdout is silenced.
+    let stdout = if config.quiet {
+        match silence_stdout() {
+            Ok(stdout) => Some(stdout),
+            Err(err) => {
+                eprintln!("Failed to silence stdout: {err}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    if config.check {
+        std::process::exit(if run_check(&config) { 0 } else { 1 });
+    }
+    if config.bench_touch {
+        if let Err(err) = run_touch_bench(&config) {
+            eprintln!("Touch benchmark failed: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if config.overcommit {
+        if let Err(err) = run_overcommit_demo() {
+            eprintln!("Overcommit experiment failed: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    let metadata = RunMetadata::collect();
+    println!("Environment: {}", metadata.summary());
+
+    if cfg!(target_os = "macos") {
+        println!(
+            "Note: macOS reports RSS and fault counts only; Private_Dirty, Shared_Dirty, \
+AnonHugePages and VmLck read as 0 kB"
+        );
+    } else {
+        match memory_source() {
+            MemorySource::SmapsRollup => {}
+            MemorySource::Smaps => {
+                println!("Note: smaps_rollup is unavailable; summing /proc/<pid>/smaps instead")
+            }
+            MemorySource::RssOnly => println!(
+                "Note: neither smaps_rollup nor smaps is readable; only RSS is measured and \
+Private_Dirty, Shared_Dirty, Shared_Clean, Pss and AnonHugePages read as 0 kB"
+            ),
+        }
+        if rss_source() == RssSource::Statm {
+            println!(
+                "Note: VmRSS is unavailable; RSS is the resident page count of /proc/<pid>/statm"
+            );
+        }
+    }
+
+    // Entered after the spawn helper check: helpers inherit the group.
+    let cgroup = match config.cgroup_limit_mb.map(MemoryCgroup::enter).transpose() {
+        Ok(cgroup) => cgroup,
+        Err(err) => {
+            eprintln!("Failed to set up the memory cgroup: {err}");
+            std::process::exit(1);
+        }
+    };
+    if config.soft_dirty && !soft_dirty_supported() {
+        eprintln!("--soft-dirty needs a kernel built with CONFIG_MEM_SOFT_DIRTY");
+        std::process::exit(1);
+    }
+    if let Some(budget) = config.max_memory_mb {
+        match limit_memory(budget) {
+            Ok((address_space, data)) => println!(
+                "Memory budget {budget} MB: RLIMIT_AS {} MB, RLIMIT_DATA {} MB",
+                address_space >> 20,
+                data >> 20
+            ),
+            Err(err) => {
+                eprintln!("Failed to set the memory limits: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(cgroup) = &cgroup {
+        println!(
+            "Running in cgroup {} with memory.max {} MB",
+            cgroup.path.display(),
+            cgroup.limit_mb
+        );
+    }
+
+    // Without --compare-pages every size runs once, as configured.
+    let variants: Vec<Config> = if config.compare_pages {
+        available_page_variants()
+            .into_iter()
+            .map(|variant| Config {
+                alloc: variant.alloc,
+                thp: variant.thp,
+                ..config.clone()
+            })
+            .collect()
+    } else {
+        vec![config.clone()]
+    };
+    let stream = match config
+        .stream
+        .as_deref()
+        .map(|path| JsonLines::open(path, stdout.as_ref()))
+        .transpose()
+    {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("Failed to open the JSON-lines stream: {err}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(stream) = &stream {
+        stream.emit(
+            "metadata",
+            &format!("\"metadata\":{}}}", metadata_json(&config, &metadata)),
+        );
+    }
     let mut results = Vec::new();
     for size in &config.sizes_mb {
-        match run_experiment(*size) {
-            Ok(res) => results.push(res),
-            Err(err) => {
-                eprintln!("Experiment failed for size {size} MB: {err}");
+        for variant in &variants {
+            // Warmups settle the allocator and page cache; their results are
+            // dropped.
+            for run in 0..config.warmup {
+                if let Err(err) = run_experiment(
+                    *size,
+                    Iteration::Warmup(run),
+                    variant,
+                    cgroup.as_ref(),
+                    None,
+                ) {
+                    eprintln!("Warmup failed for size {size} MB: {err}");
+                }
             }
+            for run in 0..config.repeat {
+                match run_experiment(
+                    *size,
+                    Iteration::Measured(run),
+                    variant,
+                    cgroup.as_ref(),
+                    stream.as_ref(),
+                ) {
+                    Ok(res) => {
+                        if let Some(stream) = &stream {
+                            stream.emit("experiment", &format!("\"result\":{}}}", res.to_json()));
+                        }
+                        results.push(res);
+                    }
+                    Err(err) => {
+                        eprintln!("Experiment failed for size {size} MB: {err}");
+                        if let Some(stream) = &stream {
+                            stream.emit(
+                                "error",
+                                &format!(
+                                    "\"size_mb\":{size},\"run\":{run},\"alloc\":{},\"message\":{}}}",
+                                    json_string(variant.alloc.label()),
+                                    json_string(&err.to_string())
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(dir) = &config.fs_cow {
+        let size_mb = config.sizes_mb.iter().copied().min().unwrap_or(16);
+        if let Err(err) = run_fs_cow(dir, size_mb, &config) {
+            eprintln!("Filesystem copy-on-write experiment failed: {err}");
+        }
+    }
+
+    // The page comparison keeps the variants apart; a per-size summary
+    // would mix them.
+    let pages = if config.compare_pages {
+        compare_page_variants(&results)
+    } else {
+        Vec::new()
+    };
+    let summaries = if config.repeat > 1 && !config.compare_pages {
+        summarize(&results)
+    } else {
+        Vec::new()
+    };
+    print_summary(&summaries);
+    if !pages.is_empty() {
+        print_page_comparison(&pages);
+    }
+    // Like the summary, a fit across the page variants would mix them.
+    let scaling = if config.compare_pages {
+        Vec::new()
+    } else {
+        fit_scaling(&results)
+    };
+    print_scaling(&scaling);
+
+    if let Some(path) = &config.plot {
+        match write_plot(path, &results) {
+            Ok(()) => println!("Saved plot to {:?}", path),
+            Err(err) => eprintln!("Failed to write plot: {err}"),
+        }
+    }
+
+    if let Some(path) = &config.report {
+        let analysis = Analysis {
+            summaries: &summaries,
+            pages: &pages,
+            scaling: &scaling,
+        };
+        match write_report(path, &config, &metadata, &results, analysis) {
+            Ok(()) => println!("Saved report to {:?}", path),
+            Err(err) => eprintln!("Failed to write report: {err}"),
+        }
+    }
+
+    if let Some(path) = &config.sqlite {
+        match write_sqlite(path, &results, &metadata) {
+            Ok(()) => println!("Appended results to SQLite database {:?}", path),
+            Err(err) => eprintln!("Failed to write SQLite database: {err}"),
         }
     }
 
     if let Some(path) = &config.output {
-        if let Err(err) = write_csv(path, &results) {
-            eprintln!("Failed to write CSV: {err}");
+        let (label, written) = match config.format {
+            OutputFormat::Csv => ("CSV", write_csv(path, &results, &metadata, config.append)),
+            OutputFormat::Json => (
+                "JSON",
+                write_json(path, &config, &metadata, &results, &summaries, &scaling),
+            ),
+        };
+        if let Err(err) = written {
+            eprintln!("Failed to write {label}: {err}");
         } else {
-            println!("Saved CSV results to {:?}", path);
+            let verb = if config.append { "Appended" } else { "Saved" };
+            println!("{verb} {label} results to {:?}", path);
+        }
+        if matches!(config.format, OutputFormat::Csv) && !summaries.is_empty() {
+            let summary = companion_path(path, "summary");
+            match write_summary_csv(&summary, &summaries) {
+                Ok(()) => println!("Saved CSV summary to {:?}", summary),
+                Err(err) => eprintln!("Failed to write CSV summary: {err}"),
+            }
+        }
+        if matches!(config.format, OutputFormat::Csv) && !scaling.is_empty() {
+            let table = companion_path(path, "scaling");
+            match write_scaling_csv(&table, &scaling) {
+                Ok(()) => println!("Saved scaling fit CSV to {:?}", table),
+                Err(err) => eprintln!("Failed to write scaling fit CSV: {err}"),
+            }
+        }
+        if matches!(config.format, OutputFormat::Csv) && !pages.is_empty() {
+            let table = companion_path(path, "pages");
+            match write_pages_csv(&table, &pages) {
+                Ok(()) => println!("Saved page size comparison CSV to {:?}", table),
+                Err(err) => eprintln!("Failed to write page size comparison CSV: {err}"),
+            }
+        }
+        if matches!(config.format, OutputFormat::Csv) && config.rounds > 1 {
+            let rounds = companion_path(path, "rounds");
+            match write_rounds_csv(&rounds, &results) {
+                Ok(()) => println!("Saved per-round CSV to {:?}", rounds),
+                Err(err) => eprintln!("Failed to write per-round CSV: {err}"),
+            }
+        }
+        if matches!(config.format, OutputFormat::Csv) && config.latency {
+            let latency = companion_path(path, "latency");
+            match write_latency_csv(&latency, &results) {
+                Ok(()) => println!("Saved latency histogram CSV to {:?}", latency),
+                Err(err) => eprintln!("Failed to write latency histogram CSV: {err}"),
+            }
+        }
+        if matches!(config.format, OutputFormat::Csv) && config.dirty_series {
+            let series = companion_path(path, "series");
+            match write_series_csv(&series, &results) {
+                Ok(()) => println!("Saved dirty series CSV to {:?}", series),
+                Err(err) => eprintln!("Failed to write dirty series CSV: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let entry = ExperimentResult {
+            children: vec![ChildRun::default(), ChildRun::default()],
+            thread_baseline: Some(ChildRun::default()),
+            parent_write: Some(ChildRun::default()),
+            ..ExperimentResult::default()
+        };
+        let metadata = RunMetadata {
+            timestamp: "2024-01-01T00:00:00Z".into(),
+            hostname: "host".into(),
+            kernel: "6.1.0".into(),
+            page_size: 4096,
+            huge_page_size: 2 << 20,
+            system_thp: "madvise".into(),
+            overcommit: None,
+            cpu_model: "Test CPU".into(),
+            cpus: 4,
+            mem_total_kb: None,
+            swap: None,
+        };
+        let header = csv_header();
+        let columns = header.split(',').count();
+        assert_eq!(columns, CSV_COLUMNS.len());
+        let rows = csv_rows(&[entry], &metadata);
+        assert_eq!(rows.len(), 4);
+        for row in rows {
+            assert_eq!(row.len(), columns);
         }
     }
+
+    #[test]
+    fn cells_are_escaped_per_format() {
+        assert_eq!(
+            csv_field("Intel(R) Xeon(R) CPU @ 2.20GHz"),
+            "Intel(R) Xeon(R) CPU @ 2.20GHz"
+        );
+        assert_eq!(csv_field("post_fork: a, b"), "\"post_fork: a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        assert_eq!(sql_value(""), "NULL");
+        assert_eq!(sql_value("42"), "42");
+        assert_eq!(sql_value("1.5"), "1.5");
+        assert_eq!(sql_value("inf"), "'inf'");
+        assert_eq!(sql_value("a, b"), "'a, b'");
+        assert_eq!(sql_value("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn quoted_cells_survive_the_sql_insert() {
+        let cell = "bob's box, \"lab\"";
+        let script = format!(
+            "CREATE TABLE t (c); INSERT INTO t VALUES ({}); SELECT c FROM t;",
+            sql_value(cell)
+        );
+        let output = match std::process::Command::new("sqlite3")
+            .args([":memory:", &script])
+            .output()
+        {
+            Ok(output) => output,
+            // Nothing to run the statement against.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+            Err(err) => panic!("sqlite3: {err}"),
+        };
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), cell);
+    }
 }
@@ -0,0 +1,104 @@
+//! Why an experiment, or the command line, could not be used. Every variant
+//! keeps what was being done when it failed: the argument, the system call,
+//! the process being measured, or the checkpoint a child was answering, so a
+//! failure part-way through a run says where it happened and not just what
+//! the OS returned.
+
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    /// An argument, or combination of arguments, that cannot be used.
+    Usage(String),
+    /// A system call that failed, with what it was doing.
+    Sys { call: String, source: io::Error },
+    /// A figure of process `pid` that could not be read from /proc (or from
+    /// the kernel on macOS).
+    Measure {
+        what: &'static str,
+        pid: u32,
+        source: io::Error,
+    },
+    /// A child's reply that could not be decoded, or lacked a stage. `stage`
+    /// is the checkpoint it was answering, if known.
+    Report {
+        stage: Option<&'static str>,
+        message: String,
+    },
+    /// A child that exited, stalled or was killed before answering.
+    Child(String),
+    /// Something this system cannot do as configured, such as KSM with ksmd
+    /// stopped or hugetlb copies without enough free huge pages.
+    Unsupported(String),
+    /// `error` together with what else was seen at the time, such as how the
+    /// children ended, which usually explains it.
+    Explained { error: Box<Error>, why: String },
+}
+
+impl Error {
+    /// For `map_err`: a failed `call`.
+    pub fn sys(call: impl Into<String>) -> impl FnOnce(io::Error) -> Error {
+        let call = call.into();
+        move |source| Error::Sys { call, source }
+    }
+
+    /// For `map_err`: `what` could not be read for `pid`.
+    pub fn measure(what: &'static str, pid: u32) -> impl FnOnce(io::Error) -> Error {
+        move |source| Error::Measure { what, pid, source }
+    }
+
+    /// A report error without a stage, from decoding, gets `stage`.
+    pub fn at_stage(self, stage: &'static str) -> Error {
+        match self {
+            Error::Report {
+                stage: None,
+                message,
+            } => Error::Report {
+                stage: Some(stage),
+                message,
+            },
+            other => other,
+        }
+    }
+
+    pub fn explained(self, why: String) -> Error {
+        Error::Explained {
+            error: Box::new(self),
+            why,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(message) | Error::Child(message) | Error::Unsupported(message) => {
+                f.write_str(message)
+            }
+            Error::Sys { call, source } => write!(f, "{call} failed: {source}"),
+            Error::Measure { what, pid, source } => {
+                write!(f, "cannot read the {what} of process {pid}: {source}")
+            }
+            Error::Report {
+                stage: Some(stage),
+                message,
+            } => write!(f, "invalid child report at {stage}: {message}"),
+            Error::Report {
+                stage: None,
+                message,
+            } => write!(f, "invalid report: {message}"),
+            Error::Explained { error, why } => write!(f, "{error} ({why})"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Sys { source, .. } | Error::Measure { source, .. } => Some(source),
+            Error::Explained { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,793 @@
+//! Memory figures of a process, parsed from the text files under
+//! `/proc/<pid>`. Files are read through a [`ProcSource`]: [`Procfs`] is the
+//! live filesystem, and any `Fn(pid, file)` closure can stand in for it with
+//! fixed contents, such as a snapshot saved from another machine. Each
+//! `parse_*` function works on the text alone.
+//!
+//! The `read_*` functions measure a live process. On macOS RSS and fault
+//! counts come from `task_info` instead, and the /proc-only figures fail.
+
+use std::io;
+use std::sync::OnceLock;
+
+use crate::sys;
+
+/// Where the text of `/proc/<pid>/<file>` comes from.
+pub trait ProcSource {
+    fn read(&self, pid: u32, file: &str) -> io::Result<String>;
+
+    /// The page size `statm` counts in; this system's unless overridden.
+    fn page_size(&self) -> usize {
+        page_size()
+    }
+}
+
+/// The live `/proc` filesystem.
+pub struct Procfs;
+
+impl ProcSource for Procfs {
+    fn read(&self, pid: u32, file: &str) -> io::Result<String> {
+        std::fs::read_to_string(format!("/proc/{pid}/{file}"))
+    }
+}
+
+impl<F: Fn(u32, &str) -> io::Result<String>> ProcSource for F {
+    fn read(&self, pid: u32, file: &str) -> io::Result<String> {
+        self(pid, file)
+    }
+}
+
+/// Memory of just the mappings that overlap the experiment buffer, from their
+/// entries in `/proc/<pid>/smaps`, so allocator, stack and binary mappings do
+/// not blur the figures.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BufferUsage {
+    pub rss_kb: u64,
+    pub private_dirty_kb: u64,
+    pub shared_clean_kb: u64,
+    pub shared_dirty_kb: u64,
+    /// Pages of the buffer swapped out rather than resident.
+    pub swap_kb: u64,
+    /// Clean pages mapped by this process alone: file pages not yet written
+    /// under `--alloc file`.
+    pub private_clean_kb: u64,
+}
+
+/// Resident kB of the buffer on each NUMA node that holds any of it, in node
+/// order, from `/proc/<pid>/numa_maps`; empty when not measured.
+#[derive(Debug, Clone, Default)]
+pub struct NodeUsage(pub Vec<(u32, u64)>);
+
+/// Fault counters from fields 10-13 of `/proc/<pid>/stat`: the process's own
+/// minor/major faults and those of its reaped children.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatFaults {
+    pub min_flt: u64,
+    pub cmin_flt: u64,
+    pub maj_flt: u64,
+    pub cmaj_flt: u64,
+}
+
+/// System-wide fault counters from `/proc/vmstat`, which count every
+/// process's faults and so bound the per-process figures from above.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VmFaults {
+    pub pgfault: u64,
+    pub pgmajfault: u64,
+    /// Huge pages allocated on a fault; zero without THP.
+    pub thp_fault_alloc: u64,
+}
+
+/// Where Private_Dirty, Shared_Dirty, Shared_Clean, Pss and AnonHugePages
+/// are read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemorySource {
+    SmapsRollup,
+    /// `smaps_rollup` is missing (kernels before 4.14, some containers), so
+    /// every mapping's entry in `smaps` is summed instead.
+    Smaps,
+    /// Neither file is readable: only RSS is measured and the dirty-page
+    /// figures read as 0 kB.
+    RssOnly,
+}
+
+impl MemorySource {
+    pub fn label(self) -> &'static str {
+        match self {
+            MemorySource::SmapsRollup => "smaps_rollup",
+            MemorySource::Smaps => "smaps",
+            MemorySource::RssOnly => "rss-only",
+        }
+    }
+
+    /// The richest file `source` offers for process `pid`.
+    pub fn probe(source: &impl ProcSource, pid: u32) -> MemorySource {
+        if source.read(pid, "smaps_rollup").is_ok() {
+            MemorySource::SmapsRollup
+        } else if source.read(pid, "smaps").is_ok() {
+            MemorySource::Smaps
+        } else {
+            MemorySource::RssOnly
+        }
+    }
+}
+
+/// Where RSS is read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RssSource {
+    /// VmRSS in `status`.
+    Status,
+    /// The resident pages in `statm`, for restricted containers that hide
+    /// `status` but still show the bare page counts.
+    Statm,
+    /// Mach's `task_info` on macOS.
+    TaskInfo,
+}
+
+impl RssSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            RssSource::Status => "status",
+            RssSource::Statm => "statm",
+            RssSource::TaskInfo => "task_info",
+        }
+    }
+
+    /// `status` if `source` shows VmRSS for process `pid`, otherwise `statm`.
+    pub fn probe(source: &impl ProcSource, pid: u32) -> RssSource {
+        if status_kb(source, pid, "VmRSS").is_ok() {
+            RssSource::Status
+        } else {
+            RssSource::Statm
+        }
+    }
+}
+
+impl StatFaults {
+    pub fn since(self, earlier: StatFaults) -> StatFaults {
+        StatFaults {
+            min_flt: self.min_flt.saturating_sub(earlier.min_flt),
+            cmin_flt: self.cmin_flt.saturating_sub(earlier.cmin_flt),
+            maj_flt: self.maj_flt.saturating_sub(earlier.maj_flt),
+            cmaj_flt: self.cmaj_flt.saturating_sub(earlier.cmaj_flt),
+        }
+    }
+}
+
+impl VmFaults {
+    pub fn since(self, earlier: VmFaults) -> VmFaults {
+        VmFaults {
+            pgfault: self.pgfault.saturating_sub(earlier.pgfault),
+            pgmajfault: self.pgmajfault.saturating_sub(earlier.pgmajfault),
+            thp_fault_alloc: self.thp_fault_alloc.saturating_sub(earlier.thp_fault_alloc),
+        }
+    }
+}
+
+/// This system's page size, or 4096 if `sysconf` cannot tell.
+pub fn page_size() -> usize {
+    sys::page_size().unwrap_or(4096)
+}
+
+/// The richest source this system offers, probed once per process.
+pub fn memory_source() -> MemorySource {
+    static SOURCE: OnceLock<MemorySource> = OnceLock::new();
+    *SOURCE.get_or_init(|| MemorySource::probe(&Procfs, std::process::id()))
+}
+
+/// Where this system's RSS figures come from, probed once per process.
+pub fn rss_source() -> RssSource {
+    static SOURCE: OnceLock<RssSource> = OnceLock::new();
+    *SOURCE.get_or_init(|| {
+        if cfg!(target_os = "macos") {
+            RssSource::TaskInfo
+        } else {
+            RssSource::probe(&Procfs, std::process::id())
+        }
+    })
+}
+
+pub fn read_rss_kb(pid: u32) -> io::Result<u64> {
+    match rss_source() {
+        #[cfg(target_os = "macos")]
+        RssSource::TaskInfo => Ok(sys::task_info(pid)?.resident_bytes / 1024),
+        rss => rss_kb(&Procfs, pid, rss),
+    }
+}
+
+/// Memory locked with mlock; locks are not inherited across fork.
+pub fn read_locked_kb(pid: u32) -> io::Result<u64> {
+    status_kb(&Procfs, pid, "VmLck")
+}
+
+/// Address space (`VmSize`) and private writable memory (`VmData`), the
+/// figures `RLIMIT_AS` and `RLIMIT_DATA` cap.
+pub fn read_mapped_kb(pid: u32) -> io::Result<(u64, u64)> {
+    let text = Procfs.read(pid, "status")?;
+    Ok((
+        parse_status_kb(&text, "VmSize")?,
+        parse_status_kb(&text, "VmData")?,
+    ))
+}
+
+/// Anonymous memory swapped out; zero without swap.
+pub fn read_swap_kb(pid: u32) -> io::Result<u64> {
+    status_kb(&Procfs, pid, "VmSwap")
+}
+
+/// Huge pages from hugetlbfs mappings, which VmRSS does not include.
+pub fn read_hugetlb_kb(pid: u32) -> io::Result<u64> {
+    status_kb(&Procfs, pid, "HugetlbPages")
+}
+
+pub fn read_private_dirty_kb(pid: u32) -> io::Result<u64> {
+    smaps_kb(&Procfs, pid, memory_source(), "Private_Dirty")
+}
+
+pub fn read_shared_dirty_kb(pid: u32) -> io::Result<u64> {
+    smaps_kb(&Procfs, pid, memory_source(), "Shared_Dirty")
+}
+
+pub fn read_shared_clean_kb(pid: u32) -> io::Result<u64> {
+    smaps_kb(&Procfs, pid, memory_source(), "Shared_Clean")
+}
+
+/// Proportional set size: each resident page divided by the number of
+/// processes mapping it.
+pub fn read_pss_kb(pid: u32) -> io::Result<u64> {
+    smaps_kb(&Procfs, pid, memory_source(), "Pss")
+}
+
+/// Pages marked with `MADV_FREE` that the kernel has not reclaimed yet;
+/// they still count in RSS.
+pub fn read_lazy_free_kb(pid: u32) -> io::Result<u64> {
+    smaps_kb(&Procfs, pid, memory_source(), "LazyFree")
+}
+
+pub fn read_anon_huge_kb(pid: u32) -> io::Result<u64> {
+    smaps_kb(&Procfs, pid, memory_source(), "AnonHugePages")
+}
+
+pub fn read_buffer_usage(pid: u32, buffer: &[u8]) -> io::Result<BufferUsage> {
+    let start = buffer.as_ptr() as usize;
+    buffer_usage(&Procfs, pid, start, start + buffer.len())
+}
+
+/// Where the buffer's pages are, per NUMA node; fails on macOS.
+pub fn read_buffer_nodes(pid: u32, buffer: &[u8]) -> io::Result<NodeUsage> {
+    let start = buffer.as_ptr() as usize;
+    buffer_nodes(&Procfs, pid, start, start + buffer.len())
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_stat_faults(pid: u32) -> io::Result<StatFaults> {
+    stat_faults(&Procfs, pid)
+}
+
+/// Mach counts every fault and, separately, those that paged in from disk;
+/// it keeps no totals for reaped children.
+#[cfg(target_os = "macos")]
+pub fn read_stat_faults(pid: u32) -> io::Result<StatFaults> {
+    let info = sys::task_info(pid)?;
+    Ok(StatFaults {
+        min_flt: info.faults.saturating_sub(info.pageins),
+        cmin_flt: 0,
+        maj_flt: info.pageins,
+        cmaj_flt: 0,
+    })
+}
+
+/// The system-wide counters of `/proc/vmstat`; fails on macOS.
+pub fn read_vm_faults() -> io::Result<VmFaults> {
+    parse_vm_faults(&std::fs::read_to_string("/proc/vmstat")?)
+}
+
+/// RSS from the file `rss` names.
+pub fn rss_kb(source: &impl ProcSource, pid: u32, rss: RssSource) -> io::Result<u64> {
+    match rss {
+        RssSource::Status => status_kb(source, pid, "VmRSS"),
+        RssSource::Statm => {
+            let pages = parse_statm_rss_pages(&source.read(pid, "statm")?)?;
+            Ok(pages * source.page_size() as u64 / 1024)
+        }
+        RssSource::TaskInfo => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "task_info is not a /proc file",
+        )),
+    }
+}
+
+pub fn status_kb(source: &impl ProcSource, pid: u32, field: &str) -> io::Result<u64> {
+    parse_status_kb(&source.read(pid, "status")?, field)
+}
+
+/// Sums `field` over `smaps_rollup` or every mapping in `smaps`, as
+/// `memory` says.
+pub fn smaps_kb(
+    source: &impl ProcSource,
+    pid: u32,
+    memory: MemorySource,
+    field: &str,
+) -> io::Result<u64> {
+    let file = match memory {
+        MemorySource::SmapsRollup => "smaps_rollup",
+        MemorySource::Smaps => "smaps",
+        MemorySource::RssOnly => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{field} needs smaps_rollup or smaps"),
+            ))
+        }
+    };
+    parse_smaps_kb(&source.read(pid, file)?, field, file)
+}
+
+/// The mappings of `smaps` that overlap the addresses `start..end`.
+pub fn buffer_usage(
+    source: &impl ProcSource,
+    pid: u32,
+    start: usize,
+    end: usize,
+) -> io::Result<BufferUsage> {
+    parse_buffer_usage(&source.read(pid, "smaps")?, start, end)
+}
+
+/// The mappings of `numa_maps` that overlap the addresses `start..end`.
+/// `numa_maps` gives only where each mapping starts, so `maps` is read too.
+pub fn buffer_nodes(
+    source: &impl ProcSource,
+    pid: u32,
+    start: usize,
+    end: usize,
+) -> io::Result<NodeUsage> {
+    let maps = source.read(pid, "maps")?;
+    parse_buffer_nodes(&maps, &source.read(pid, "numa_maps")?, start, end)
+}
+
+#[cfg(target_os = "linux")]
+pub fn stat_faults(source: &impl ProcSource, pid: u32) -> io::Result<StatFaults> {
+    parse_stat_faults(&source.read(pid, "stat")?)
+}
+
+/// The number before ` kB` on the `field:` line of a `status` file.
+pub fn parse_status_kb(text: &str, field: &str) -> io::Result<u64> {
+    let prefix = format!("{field}:");
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix(prefix.as_str()) {
+            if let Some(number) = rest.split_whitespace().next() {
+                return number.parse::<u64>().map_err(invalid);
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{field} not found in /proc status"),
+    ))
+}
+
+/// Resident pages from the second column of a `statm` file.
+pub fn parse_statm_rss_pages(text: &str) -> io::Result<u64> {
+    text.split_whitespace()
+        .nth(1)
+        .ok_or_else(|| invalid("malformed /proc statm"))?
+        .parse()
+        .map_err(invalid)
+}
+
+/// The total of every `field:` line, so it works on `smaps_rollup` (one
+/// line) and `smaps` (one per mapping) alike. `file` names the source in
+/// the error when there is none.
+pub fn parse_smaps_kb(text: &str, field: &str, file: &str) -> io::Result<u64> {
+    let prefix = format!("{field}:");
+    let mut total = None;
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix(prefix.as_str()) {
+            if let Some(number) = rest.split_whitespace().next() {
+                *total.get_or_insert(0) += number.parse::<u64>().map_err(invalid)?;
+            }
+        }
+    }
+    total.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{field} not found in {file}"),
+        )
+    })
+}
+
+/// Sums the figures of the `smaps` mappings that overlap `start..end`.
+pub fn parse_buffer_usage(text: &str, start: usize, end: usize) -> io::Result<BufferUsage> {
+    let mut usage = BufferUsage::default();
+    let mut inside = false;
+    let mut found = false;
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        // Each mapping starts with a `start-end perms ...` header line.
+        if let Some((from, to)) = first.split_once('-') {
+            if let (Ok(from), Ok(to)) = (
+                usize::from_str_radix(from, 16),
+                usize::from_str_radix(to, 16),
+            ) {
+                inside = from < end && start < to;
+                found |= inside;
+                continue;
+            }
+        }
+        if !inside {
+            continue;
+        }
+        let Some(kb) = words.next().and_then(|word| word.parse::<u64>().ok()) else {
+            continue;
+        };
+        match first {
+            "Rss:" => usage.rss_kb += kb,
+            "Private_Dirty:" => usage.private_dirty_kb += kb,
+            "Shared_Clean:" => usage.shared_clean_kb += kb,
+            "Shared_Dirty:" => usage.shared_dirty_kb += kb,
+            "Private_Clean:" => usage.private_clean_kb += kb,
+            "Swap:" => usage.swap_kb += kb,
+            _ => {}
+        }
+    }
+    if !found {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "buffer not found in smaps",
+        ));
+    }
+    Ok(usage)
+}
+
+/// The `N<node>=<pages>` counts of every `numa_maps` line for a mapping
+/// that `maps` shows overlapping `start..end`, in kB.
+pub fn parse_buffer_nodes(
+    maps: &str,
+    numa_maps: &str,
+    start: usize,
+    end: usize,
+) -> io::Result<NodeUsage> {
+    let overlapping: Vec<usize> = maps
+        .lines()
+        .filter_map(|line| {
+            let (from, to) = line.split_whitespace().next()?.split_once('-')?;
+            let from = usize::from_str_radix(from, 16).ok()?;
+            let to = usize::from_str_radix(to, 16).ok()?;
+            (from < end && start < to).then_some(from)
+        })
+        .collect();
+    let mut nodes: Vec<(u32, u64)> = Vec::new();
+    let mut found = false;
+    for line in numa_maps.lines() {
+        let mut words = line.split_whitespace();
+        let Some(from) = words
+            .next()
+            .and_then(|word| usize::from_str_radix(word, 16).ok())
+        else {
+            continue;
+        };
+        if !overlapping.contains(&from) {
+            continue;
+        }
+        found = true;
+        let words: Vec<&str> = words.collect();
+        let page_kb = words
+            .iter()
+            .find_map(|word| word.strip_prefix("kernelpagesize_kB="))
+            .map_or(Ok(4), str::parse::<u64>)
+            .map_err(invalid)?;
+        for word in words {
+            let Some((node, pages)) = word
+                .strip_prefix('N')
+                .and_then(|count| count.split_once('='))
+            else {
+                continue;
+            };
+            let (Ok(node), Ok(pages)) = (node.parse::<u32>(), pages.parse::<u64>()) else {
+                continue;
+            };
+            match nodes.iter_mut().find(|(seen, _)| *seen == node) {
+                Some((_, kb)) => *kb += pages * page_kb,
+                None => nodes.push((node, pages * page_kb)),
+            }
+        }
+    }
+    if !found {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "buffer not found in numa_maps",
+        ));
+    }
+    nodes.sort_unstable();
+    Ok(NodeUsage(nodes))
+}
+
+/// Fields 10-13 of a `stat` file.
+#[cfg(target_os = "linux")]
+pub fn parse_stat_faults(text: &str) -> io::Result<StatFaults> {
+    // The command name (field 2) may contain spaces and parentheses, so start
+    // after its closing parenthesis; the remainder begins at field 3.
+    let rest = text
+        .rfind(')')
+        .map(|end| &text[end + 1..])
+        .ok_or_else(|| invalid("malformed /proc stat"))?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |number: usize| -> io::Result<u64> {
+        fields
+            .get(number - 3)
+            .ok_or_else(|| invalid(format!("field {number} missing in /proc stat")))?
+            .parse::<u64>()
+            .map_err(invalid)
+    };
+    Ok(StatFaults {
+        min_flt: field(10)?,
+        cmin_flt: field(11)?,
+        maj_flt: field(12)?,
+        cmaj_flt: field(13)?,
+    })
+}
+
+/// The fault counters of a `vmstat` file. `thp_fault_alloc` is missing from
+/// kernels built without THP and reads as zero.
+pub fn parse_vm_faults(text: &str) -> io::Result<VmFaults> {
+    let counter = |name: &str| -> Option<io::Result<u64>> {
+        text.lines().find_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            (key == name).then(|| value.trim().parse().map_err(invalid))
+        })
+    };
+    let required = |name: &str| {
+        counter(name).unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{name} not found in /proc/vmstat"),
+            ))
+        })
+    };
+    Ok(VmFaults {
+        pgfault: required("pgfault")?,
+        pgmajfault: required("pgmajfault")?,
+        thp_fault_alloc: counter("thp_fault_alloc").transpose()?.unwrap_or(0),
+    })
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed `/proc` files for one process, with a page size of its own.
+    struct FakeProc {
+        files: Vec<(&'static str, &'static str)>,
+        page_size: usize,
+    }
+
+    impl ProcSource for FakeProc {
+        fn read(&self, _pid: u32, file: &str) -> io::Result<String> {
+            self.files
+                .iter()
+                .find(|(name, _)| *name == file)
+                .map(|(_, text)| text.to_string())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn page_size(&self) -> usize {
+            self.page_size
+        }
+    }
+
+    const STATUS: &str =
+        "Name:\tcow\nVmSize:\t  10240 kB\nVmRSS:\t   2048 kB\nVmLck:\t      0 kB\n";
+
+    const SMAPS: &str = "\
+7f0000000000-7f0000004000 rw-p 00000000 00:00 0
+Rss:                  16 kB
+Private_Dirty:        12 kB
+Shared_Clean:          4 kB
+Swap:                  0 kB
+7f0000010000-7f0000012000 rw-p 00000000 00:00 0
+Rss:                   8 kB
+Private_Dirty:         8 kB
+Shared_Dirty:        bad kB
+";
+
+    fn kind<T: std::fmt::Debug>(result: io::Result<T>) -> io::ErrorKind {
+        result.unwrap_err().kind()
+    }
+
+    #[test]
+    fn status_fields() {
+        let proc = FakeProc {
+            files: vec![("status", STATUS)],
+            page_size: 4096,
+        };
+        assert_eq!(status_kb(&proc, 1, "VmRSS").unwrap(), 2048);
+        assert_eq!(parse_status_kb(STATUS, "VmSize").unwrap(), 10240);
+        assert_eq!(kind(status_kb(&proc, 1, "VmSwap")), io::ErrorKind::NotFound);
+        assert_eq!(
+            kind(parse_status_kb("VmRSS:\t12x kB\n", "VmRSS")),
+            io::ErrorKind::InvalidData
+        );
+        // A field name that only prefixes another must not match it.
+        assert_eq!(
+            kind(parse_status_kb("VmRSSx:\t1 kB\n", "VmRSS")),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn rss_falls_back_to_statm_pages() {
+        let proc = FakeProc {
+            files: vec![("statm", "2500 300 40 5 0 120 0\n")],
+            page_size: 16384,
+        };
+        assert_eq!(RssSource::probe(&proc, 1), RssSource::Statm);
+        assert_eq!(rss_kb(&proc, 1, RssSource::Statm).unwrap(), 300 * 16);
+        assert_eq!(
+            kind(parse_statm_rss_pages("2500\n")),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn smaps_totals() {
+        let rollup = FakeProc {
+            files: vec![
+                (
+                    "smaps_rollup",
+                    "Rss: 64 kB\nPss: 40 kB\nPrivate_Dirty: 32 kB\n",
+                ),
+                ("smaps", SMAPS),
+            ],
+            page_size: 4096,
+        };
+        assert_eq!(MemorySource::probe(&rollup, 1), MemorySource::SmapsRollup);
+        assert_eq!(
+            smaps_kb(&rollup, 1, MemorySource::SmapsRollup, "Pss").unwrap(),
+            40
+        );
+
+        let smaps_only = FakeProc {
+            files: vec![("smaps", SMAPS)],
+            page_size: 4096,
+        };
+        assert_eq!(MemorySource::probe(&smaps_only, 1), MemorySource::Smaps);
+        assert_eq!(
+            smaps_kb(&smaps_only, 1, MemorySource::Smaps, "Private_Dirty").unwrap(),
+            20
+        );
+        assert_eq!(
+            kind(smaps_kb(&smaps_only, 1, MemorySource::Smaps, "Pss")),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind(parse_smaps_kb(SMAPS, "Shared_Dirty", "smaps")),
+            io::ErrorKind::InvalidData
+        );
+
+        let bare = FakeProc {
+            files: vec![],
+            page_size: 4096,
+        };
+        assert_eq!(MemorySource::probe(&bare, 1), MemorySource::RssOnly);
+        assert_eq!(
+            kind(smaps_kb(&bare, 1, MemorySource::RssOnly, "Pss")),
+            io::ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn buffer_usage_counts_overlapping_mappings() {
+        let proc = FakeProc {
+            files: vec![("smaps", SMAPS)],
+            page_size: 4096,
+        };
+        let first = buffer_usage(&proc, 1, 0x7f00_0000_1000, 0x7f00_0000_2000).unwrap();
+        assert_eq!(first.rss_kb, 16);
+        assert_eq!(first.private_dirty_kb, 12);
+        assert_eq!(first.shared_clean_kb, 4);
+
+        // The malformed Shared_Dirty line of the second mapping is skipped.
+        let both = parse_buffer_usage(SMAPS, 0x7f00_0000_0000, 0x7f00_0001_1000).unwrap();
+        assert_eq!(both.rss_kb, 24);
+        assert_eq!(both.private_dirty_kb, 20);
+        assert_eq!(both.shared_dirty_kb, 0);
+
+        assert_eq!(
+            kind(parse_buffer_usage(SMAPS, 0x1000, 0x2000)),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn buffer_nodes_from_numa_maps() {
+        let maps = "\
+7f0000000000-7f0000400000 rw-p 00000000 00:00 0
+7f0000600000-7f0000800000 rw-p 00000000 00:00 0
+";
+        let numa_maps = "\
+7f0000000000 default anon=1024 dirty=1024 N0=512 N1=512 kernelpagesize_kB=4
+7f0000600000 default anon=1 N1=1 Nx=3 N0=oops kernelpagesize_kB=2048
+";
+        let proc = FakeProc {
+            files: vec![("maps", maps), ("numa_maps", numa_maps)],
+            page_size: 4096,
+        };
+        let nodes = buffer_nodes(&proc, 1, 0x7f00_0000_0000, 0x7f00_0070_0000).unwrap();
+        assert_eq!(nodes.0, vec![(0, 2048), (1, 2048 + 2048)]);
+
+        let first = parse_buffer_nodes(maps, numa_maps, 0x7f00_0000_0000, 0x7f00_0000_1000);
+        assert_eq!(first.unwrap().0, vec![(0, 2048), (1, 2048)]);
+        assert_eq!(
+            kind(parse_buffer_nodes(maps, numa_maps, 0x1000, 0x2000)),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind(parse_buffer_nodes(
+                maps,
+                "7f0000000000 default N0=1 kernelpagesize_kB=big\n",
+                0x7f00_0000_0000,
+                0x7f00_0000_1000
+            )),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stat_faults_skip_the_command_name() {
+        let stat = "42 (a (b) c) S 1 42 42 0 -1 4194560 120 30 4 1 0 0";
+        let proc = FakeProc {
+            files: vec![("stat", stat)],
+            page_size: 4096,
+        };
+        let faults = stat_faults(&proc, 42).unwrap();
+        assert_eq!(
+            (
+                faults.min_flt,
+                faults.cmin_flt,
+                faults.maj_flt,
+                faults.cmaj_flt
+            ),
+            (120, 30, 4, 1)
+        );
+        assert_eq!(
+            kind(parse_stat_faults("42 (cow) S 1 42 42 0 -1 4194560 120")),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(parse_stat_faults(
+                "42 (cow) S 1 42 42 0 -1 4194560 x 30 4 1"
+            )),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(parse_stat_faults("42 cow S")),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn vm_faults() {
+        let faults = parse_vm_faults("pgfault 900\npgmajfault 7\nthp_fault_alloc 3\n").unwrap();
+        assert_eq!(
+            (faults.pgfault, faults.pgmajfault, faults.thp_fault_alloc),
+            (900, 7, 3)
+        );
+        let without_thp = parse_vm_faults("pgmajfault 7\npgfault 900\n").unwrap();
+        assert_eq!(without_thp.thp_fault_alloc, 0);
+        assert_eq!(
+            kind(parse_vm_faults("pgfault 900\n")),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind(parse_vm_faults("pgfault 900\npgmajfault seven\n")),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Minimal SVG line charts for `--plot`, styled after the matplotlib figures
+//! in `graphs/` so the tool can produce report figures without a plotting
+//! crate.
+
+use std::fmt::Write as _;
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 420.0;
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 190.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 60.0;
+const COLORS: &[&str] = &[
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b",
+];
+
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+pub struct Chart {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+}
+
+/// Renders `charts` stacked vertically in a single SVG document.
+pub fn render(charts: &[Chart]) -> String {
+    let mut svg = String::new();
+    let total_height = HEIGHT * charts.len() as f64;
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{total_height}\" \
+viewBox=\"0 0 {WIDTH} {total_height}\" font-family=\"sans-serif\" font-size=\"12\">"
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{WIDTH}\" height=\"{total_height}\" fill=\"#ffffff\"/>"
+    );
+    for (index, chart) in charts.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            "<g transform=\"translate(0 {})\">",
+            HEIGHT * index as f64
+        );
+        render_chart(&mut svg, chart);
+        let _ = writeln!(svg, "</g>");
+    }
+    let _ = writeln!(svg, "</svg>");
+    svg
+}
+
+fn render_chart(svg: &mut String, chart: &Chart) {
+    let points = chart.series.iter().flat_map(|series| &series.points);
+    let (x_min, x_max) = points
+        .clone()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &(x, _)| {
+            (lo.min(x), hi.max(x))
+        });
+    let y_top = points.fold(0.0f64, |hi, &(_, y)| hi.max(y));
+    if !x_min.is_finite() {
+        return;
+    }
+    // A single size still needs a non-empty x range.
+    let (x_min, x_max) = if x_max > x_min {
+        (x_min, x_max)
+    } else {
+        (x_min - 1.0, x_max + 1.0)
+    };
+    let y_step = tick_step(y_top.max(1.0));
+    let y_max = (y_top / y_step).ceil().max(1.0) * y_step;
+
+    let plot_w = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_h = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let sx = |x: f64| MARGIN_LEFT + (x - x_min) / (x_max - x_min) * plot_w;
+    let sy = |y: f64| MARGIN_TOP + plot_h - y / y_max * plot_h;
+
+    let _ = writeln!(
+        svg,
+        "<rect x=\"{MARGIN_LEFT}\" y=\"{MARGIN_TOP}\" width=\"{plot_w}\" height=\"{plot_h}\" fill=\"#eaeaf2\"/>"
+    );
+    let mut y = 0.0;
+    while y <= y_max + y_step / 2.0 {
+        let py = sy(y);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{MARGIN_LEFT}\" y1=\"{py:.1}\" x2=\"{:.1}\" y2=\"{py:.1}\" stroke=\"#ffffff\"/>\
+<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            MARGIN_LEFT + plot_w,
+            MARGIN_LEFT - 6.0,
+            py + 4.0,
+            format_tick(y)
+        );
+        y += y_step;
+    }
+    let x_step = tick_step(x_max - x_min);
+    let mut x = (x_min / x_step).ceil() * x_step;
+    while x <= x_max + x_step / 1000.0 {
+        let px = sx(x);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{px:.1}\" y1=\"{MARGIN_TOP}\" x2=\"{px:.1}\" y2=\"{:.1}\" stroke=\"#ffffff\"/>\
+<text x=\"{px:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            MARGIN_TOP + plot_h,
+            MARGIN_TOP + plot_h + 18.0,
+            format_tick(x)
+        );
+        x += x_step;
+    }
+
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"24\" text-anchor=\"middle\" font-size=\"15\">{}</text>",
+        MARGIN_LEFT + plot_w / 2.0,
+        escape(&chart.title)
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+        MARGIN_LEFT + plot_w / 2.0,
+        HEIGHT - 18.0,
+        escape(&chart.x_label)
+    );
+    let _ = writeln!(
+        svg,
+        "<text transform=\"translate(20 {:.1}) rotate(-90)\" text-anchor=\"middle\">{}</text>",
+        MARGIN_TOP + plot_h / 2.0,
+        escape(&chart.y_label)
+    );
+
+    for (index, series) in chart.series.iter().enumerate() {
+        let color = COLORS[index % COLORS.len()];
+        let path: Vec<String> = series
+            .points
+            .iter()
+            .map(|&(x, y)| format!("{:.1},{:.1}", sx(x), sy(y)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"2\"/>",
+            path.join(" ")
+        );
+        for &(x, y) in &series.points {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3.5\" fill=\"{color}\"/>",
+                sx(x),
+                sy(y)
+            );
+        }
+        let legend_y = MARGIN_TOP + 10.0 + 20.0 * index as f64;
+        let legend_x = MARGIN_LEFT + plot_w + 14.0;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{legend_x:.1}\" y1=\"{legend_y:.1}\" x2=\"{:.1}\" y2=\"{legend_y:.1}\" \
+stroke=\"{color}\" stroke-width=\"2\"/><text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+            legend_x + 20.0,
+            legend_x + 26.0,
+            legend_y + 4.0,
+            escape(&series.label)
+        );
+    }
+}
+
+/// A 1, 2 or 5 times power-of-ten step giving about five ticks over `range`.
+fn tick_step(range: f64) -> f64 {
+    let rough = range / 5.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    if step > 0.0 {
+        step
+    } else {
+        1.0
+    }
+}
+
+fn format_tick(value: f64) -> String {
+    if value.fract().abs() < 1e-9 {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.2}")
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
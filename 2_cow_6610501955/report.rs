@@ -0,0 +1,632 @@
+//! The binary messages of the checkpoint protocol. Every message is a frame:
+//! a little-endian `u32` payload length, then the payload, which starts with
+//! [`VERSION`]. A request from the parent carries one checkpoint byte. A
+//! child's reply carries a record count and then its records, the last of
+//! which is the stage for the checkpoint it answers.
+//!
+//! Fields are written in declaration order at fixed widths: a new field is
+//! written in `write_stage`, read back in `read_stage`, and `VERSION` goes
+//! up by one.
+
+use crate::error::Error;
+use crate::measure::{BufferUsage, NodeUsage};
+use crate::{
+    Checkpoint, ChildStage, DiscardAdvice, DiscardReport, FirstWrite, FrameSharing, LatencyStats,
+    Round, Timestamp, WriteLatency, LATENCY_BUCKETS,
+};
+
+/// Bumped whenever a record changes, so a parent and a spawned helper built
+/// from different sources refuse each other's messages instead of misreading
+/// them.
+pub const VERSION: u8 = 13;
+
+const STAGE: u8 = 1;
+const ROUND: u8 = 2;
+const ERROR: u8 = 3;
+const LATENCY: u8 = 4;
+const FORKED: u8 = 5;
+const CHECKSUM: u8 = 6;
+const DISCARD: u8 = 7;
+
+/// One entry of a child's reply.
+#[derive(Debug)]
+pub enum Record {
+    Stage(Box<ChildStage>),
+    Round(Round),
+    /// A measurement that failed, prefixed with its stage.
+    Error(String),
+    Latency(Box<WriteLatency>),
+    /// Microseconds the child spent forking the next generation (`--depth`).
+    Forked(f64),
+    /// Checksum of the buffer as the child saw it before writing (`--verify`).
+    Checksum(u64),
+    /// What `--discard` did and what reading the pages back showed.
+    Discard(Box<DiscardReport>),
+}
+
+pub fn encode_request(checkpoint: Checkpoint) -> Vec<u8> {
+    frame(vec![VERSION, checkpoint as u8])
+}
+
+pub fn decode_request(payload: &[u8]) -> Result<Checkpoint, Error> {
+    let mut reader = Reader::new(payload)?;
+    let index = reader.u8()?;
+    reader.finish()?;
+    Checkpoint::ALL
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| invalid(format!("unknown checkpoint {index}")))
+}
+
+pub fn encode_reply(records: &[Record]) -> Vec<u8> {
+    let mut writer = Writer(vec![VERSION]);
+    writer.u64(records.len() as u64);
+    for record in records {
+        match record {
+            Record::Stage(stage) => {
+                writer.u8(STAGE);
+                write_stage(&mut writer, stage);
+            }
+            Record::Round(round) => {
+                writer.u8(ROUND);
+                writer.u64(round.pages_touched as u64);
+                writer.u64(round.rss_kb);
+                writer.u64(round.private_dirty_kb);
+                writer.u64(round.buffer_private_dirty_kb);
+                writer.f64(round.touch_ms);
+            }
+            Record::Error(message) => {
+                writer.u8(ERROR);
+                writer.str(message);
+            }
+            Record::Latency(latency) => {
+                writer.u8(LATENCY);
+                for stats in [latency.first, latency.rewrite] {
+                    for value in stats.buckets {
+                        writer.u64(value);
+                    }
+                    for value in [stats.p50_ns, stats.p90_ns, stats.p99_ns, stats.max_ns] {
+                        writer.u64(value);
+                    }
+                }
+            }
+            Record::Forked(fork_us) => {
+                writer.u8(FORKED);
+                writer.f64(*fork_us);
+            }
+            Record::Checksum(sum) => {
+                writer.u8(CHECKSUM);
+                writer.u64(*sum);
+            }
+            Record::Discard(report) => {
+                writer.u8(DISCARD);
+                writer.u8(match report.advice {
+                    DiscardAdvice::DontNeed => 0,
+                    DiscardAdvice::Free => 1,
+                });
+                for value in [
+                    report.discarded_bytes,
+                    report.lazy_free_kb,
+                    report.kept_pages,
+                    report.zeroed_pages,
+                    report.other_pages,
+                    report.reread_faults,
+                    report.reread_rss_kb,
+                    report.reread_private_dirty_kb,
+                ] {
+                    writer.u64(value);
+                }
+            }
+        }
+    }
+    frame(writer.0)
+}
+
+/// Decodes a child's reply. The error carries no stage; the caller knows
+/// which checkpoint the reply answers.
+pub fn decode_reply(payload: &[u8]) -> Result<Vec<Record>, Error> {
+    let mut reader = Reader::new(payload)?;
+    let count = reader.u64()?;
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let record = match reader.u8()? {
+            STAGE => Record::Stage(Box::new(read_stage(&mut reader)?)),
+            ROUND => Record::Round(Round {
+                pages_touched: reader.u64()? as usize,
+                rss_kb: reader.u64()?,
+                private_dirty_kb: reader.u64()?,
+                buffer_private_dirty_kb: reader.u64()?,
+                touch_ms: reader.f64()?,
+            }),
+            ERROR => Record::Error(reader.str()?),
+            LATENCY => Record::Latency(Box::new(WriteLatency {
+                first: read_latency(&mut reader)?,
+                rewrite: read_latency(&mut reader)?,
+            })),
+            FORKED => Record::Forked(reader.f64()?),
+            CHECKSUM => Record::Checksum(reader.u64()?),
+            DISCARD => Record::Discard(Box::new(DiscardReport {
+                advice: match reader.u8()? {
+                    0 => DiscardAdvice::DontNeed,
+                    1 => DiscardAdvice::Free,
+                    other => return Err(invalid(format!("unknown discard advice {other}"))),
+                },
+                discarded_bytes: reader.u64()?,
+                lazy_free_kb: reader.u64()?,
+                kept_pages: reader.u64()?,
+                zeroed_pages: reader.u64()?,
+                other_pages: reader.u64()?,
+                reread_faults: reader.u64()?,
+                reread_rss_kb: reader.u64()?,
+                reread_private_dirty_kb: reader.u64()?,
+            })),
+            tag => return Err(invalid(format!("unknown record type {tag}"))),
+        };
+        records.push(record);
+    }
+    reader.finish()?;
+    Ok(records)
+}
+
+/// Removes the first whole frame from the front of `pending` and returns its
+/// payload, or `None` if the frame has not fully arrived yet.
+pub fn take_frame(pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let header: [u8; 4] = pending.get(..4)?.try_into().ok()?;
+    let len = u32::from_le_bytes(header) as usize;
+    if pending.len() < 4 + len {
+        return None;
+    }
+    let payload = pending[4..4 + len].to_vec();
+    pending.drain(..4 + len);
+    Some(payload)
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::Report {
+        stage: None,
+        message: message.into(),
+    }
+}
+
+fn frame(payload: Vec<u8>) -> Vec<u8> {
+    let mut bytes = (payload.len() as u32).to_le_bytes().to_vec();
+    bytes.extend(payload);
+    bytes
+}
+
+fn write_stage(writer: &mut Writer, stage: &ChildStage) {
+    writer.str(&stage.stage);
+    for value in [
+        stage.rss_kb,
+        stage.private_dirty_kb,
+        stage.shared_dirty_kb,
+        stage.shared_clean_kb,
+        stage.pss_kb,
+        stage.missing_pages,
+        stage.zeroed_pages,
+        stage.parent_private_dirty_kb,
+        stage.parent_shared_dirty_kb,
+        stage.parent_shared_clean_kb,
+        stage.parent_pss_kb,
+        stage.anon_huge_kb,
+        stage.hugetlb_kb,
+        stage.locked_kb,
+        stage.swap_kb,
+        stage.buffer.rss_kb,
+        stage.buffer.private_dirty_kb,
+        stage.buffer.shared_clean_kb,
+        stage.buffer.shared_dirty_kb,
+        stage.buffer.swap_kb,
+        stage.buffer.private_clean_kb,
+    ] {
+        writer.u64(value);
+    }
+    match stage.frames {
+        Some(frames) => {
+            writer.u8(1);
+            writer.u64(frames.shared);
+            writer.u64(frames.copied);
+        }
+        None => writer.u8(0),
+    }
+    match stage.soft_dirty_pages {
+        Some(pages) => {
+            writer.u8(1);
+            writer.u64(pages);
+        }
+        None => writer.u8(0),
+    }
+    for value in [
+        stage.minor_faults,
+        stage.major_faults,
+        stage.stat_min_flt,
+        stage.stat_maj_flt,
+    ] {
+        writer.u64(value);
+    }
+    writer.f64(stage.touch_ms);
+    writer.u64(stage.touched_bytes);
+    match stage.first_write {
+        Some(first) => {
+            writer.u8(1);
+            writer.u64(first.ns);
+            writer.u64(first.minor_faults);
+            writer.u64(first.major_faults);
+        }
+        None => writer.u8(0),
+    }
+    writer.u64(stage.nodes.0.len() as u64);
+    for &(node, kb) in &stage.nodes.0 {
+        writer.u64(node.into());
+        writer.u64(kb);
+    }
+    write_timestamp(writer, stage.measured_at);
+    for at in [stage.touch_started_at, stage.touch_ended_at] {
+        match at {
+            Some(at) => {
+                writer.u8(1);
+                write_timestamp(writer, at);
+            }
+            None => writer.u8(0),
+        }
+    }
+}
+
+fn write_timestamp(writer: &mut Writer, at: Timestamp) {
+    writer.u64(at.monotonic_ns);
+    writer.u64(at.unix_ns);
+}
+
+fn read_stage(reader: &mut Reader) -> Result<ChildStage, Error> {
+    Ok(ChildStage {
+        stage: reader.str()?,
+        rss_kb: reader.u64()?,
+        private_dirty_kb: reader.u64()?,
+        shared_dirty_kb: reader.u64()?,
+        shared_clean_kb: reader.u64()?,
+        pss_kb: reader.u64()?,
+        missing_pages: reader.u64()?,
+        zeroed_pages: reader.u64()?,
+        parent_private_dirty_kb: reader.u64()?,
+        parent_shared_dirty_kb: reader.u64()?,
+        parent_shared_clean_kb: reader.u64()?,
+        parent_pss_kb: reader.u64()?,
+        anon_huge_kb: reader.u64()?,
+        hugetlb_kb: reader.u64()?,
+        locked_kb: reader.u64()?,
+        swap_kb: reader.u64()?,
+        buffer: BufferUsage {
+            rss_kb: reader.u64()?,
+            private_dirty_kb: reader.u64()?,
+            shared_clean_kb: reader.u64()?,
+            shared_dirty_kb: reader.u64()?,
+            swap_kb: reader.u64()?,
+            private_clean_kb: reader.u64()?,
+        },
+        frames: match reader.u8()? {
+            0 => None,
+            _ => Some(FrameSharing {
+                shared: reader.u64()?,
+                copied: reader.u64()?,
+            }),
+        },
+        soft_dirty_pages: match reader.u8()? {
+            0 => None,
+            _ => Some(reader.u64()?),
+        },
+        minor_faults: reader.u64()?,
+        major_faults: reader.u64()?,
+        stat_min_flt: reader.u64()?,
+        stat_maj_flt: reader.u64()?,
+        touch_ms: reader.f64()?,
+        touched_bytes: reader.u64()?,
+        first_write: match reader.u8()? {
+            0 => None,
+            _ => Some(FirstWrite {
+                ns: reader.u64()?,
+                minor_faults: reader.u64()?,
+                major_faults: reader.u64()?,
+            }),
+        },
+        nodes: read_nodes(reader)?,
+        measured_at: read_timestamp(reader)?,
+        touch_started_at: read_optional_timestamp(reader)?,
+        touch_ended_at: read_optional_timestamp(reader)?,
+        // Stamped by the parent when the reply arrives.
+        collected_at: None,
+    })
+}
+
+fn read_nodes(reader: &mut Reader) -> Result<NodeUsage, Error> {
+    let count = reader.u64()?;
+    let mut nodes = Vec::new();
+    for _ in 0..count {
+        let node = u32::try_from(reader.u64()?).map_err(|_| invalid("NUMA node out of range"))?;
+        nodes.push((node, reader.u64()?));
+    }
+    Ok(NodeUsage(nodes))
+}
+
+fn read_timestamp(reader: &mut Reader) -> Result<Timestamp, Error> {
+    Ok(Timestamp {
+        monotonic_ns: reader.u64()?,
+        unix_ns: reader.u64()?,
+    })
+}
+
+fn read_optional_timestamp(reader: &mut Reader) -> Result<Option<Timestamp>, Error> {
+    match reader.u8()? {
+        0 => Ok(None),
+        _ => read_timestamp(reader).map(Some),
+    }
+}
+
+fn read_latency(reader: &mut Reader) -> Result<LatencyStats, Error> {
+    let mut buckets = [0; LATENCY_BUCKETS];
+    for bucket in &mut buckets {
+        *bucket = reader.u64()?;
+    }
+    Ok(LatencyStats {
+        buckets,
+        p50_ns: reader.u64()?,
+        p90_ns: reader.u64()?,
+        p99_ns: reader.u64()?,
+        max_ns: reader.u64()?,
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u64(value.len() as u64);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Starts reading a payload after checking its version byte.
+    fn new(payload: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes: payload };
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "report format version {version}, expected {VERSION}"
+            )));
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated report"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().expect("eight bytes")))
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    fn str(&mut self) -> Result<String, Error> {
+        let len = self.u64()?;
+        let len = usize::try_from(len).map_err(|_| invalid("oversized string in report"))?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| invalid(format!("invalid string in report: {e}")))
+    }
+
+    /// Fails if anything is left over.
+    fn finish(&self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "{} unexpected bytes after report",
+                self.bytes.len()
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends `record` alone through a frame and back, and checks it reads
+    /// as it was written.
+    fn round_trip(record: Record) {
+        let expected = format!("{record:?}");
+        let mut pending = encode_reply(&[record]);
+        let payload = take_frame(&mut pending).unwrap();
+        assert!(pending.is_empty());
+        let records = decode_reply(&payload).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(format!("{:?}", records[0]), expected);
+    }
+
+    fn stage_reply() -> Vec<u8> {
+        let mut pending = encode_reply(&[Record::Stage(Box::default())]);
+        take_frame(&mut pending).unwrap()
+    }
+
+    fn message(result: Result<Vec<Record>, Error>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        for checkpoint in Checkpoint::ALL {
+            let mut pending = encode_request(checkpoint);
+            let payload = take_frame(&mut pending).unwrap();
+            assert_eq!(decode_request(&payload).unwrap(), checkpoint);
+        }
+    }
+
+    #[test]
+    fn stage_round_trips() {
+        let at = |ns| Timestamp {
+            monotonic_ns: ns,
+            unix_ns: ns + 1,
+        };
+        round_trip(Record::Stage(Box::new(ChildStage {
+            stage: "post_write".into(),
+            rss_kb: 1,
+            private_dirty_kb: 2,
+            shared_dirty_kb: 3,
+            shared_clean_kb: 4,
+            pss_kb: 5,
+            missing_pages: 6,
+            zeroed_pages: 7,
+            parent_private_dirty_kb: 8,
+            parent_shared_dirty_kb: 9,
+            parent_shared_clean_kb: 10,
+            parent_pss_kb: 11,
+            anon_huge_kb: 12,
+            hugetlb_kb: 13,
+            locked_kb: 14,
+            swap_kb: 15,
+            buffer: BufferUsage {
+                rss_kb: 16,
+                private_dirty_kb: 17,
+                shared_clean_kb: 18,
+                shared_dirty_kb: 19,
+                swap_kb: 20,
+                private_clean_kb: 21,
+            },
+            frames: Some(FrameSharing {
+                shared: 22,
+                copied: 23,
+            }),
+            soft_dirty_pages: Some(24),
+            minor_faults: 25,
+            major_faults: 26,
+            stat_min_flt: 27,
+            stat_maj_flt: 28,
+            touch_ms: 29.5,
+            touched_bytes: 30,
+            first_write: Some(FirstWrite {
+                ns: 31,
+                minor_faults: 32,
+                major_faults: 33,
+            }),
+            nodes: NodeUsage(vec![(0, 34), (3, 35)]),
+            measured_at: at(36),
+            touch_started_at: Some(at(38)),
+            touch_ended_at: None,
+            collected_at: None,
+        })));
+        round_trip(Record::Stage(Box::default()));
+    }
+
+    #[test]
+    fn other_records_round_trip() {
+        round_trip(Record::Round(Round {
+            pages_touched: 1,
+            rss_kb: 2,
+            private_dirty_kb: 3,
+            buffer_private_dirty_kb: 4,
+            touch_ms: 5.25,
+        }));
+        round_trip(Record::Error("post_fork: cannot read smaps".into()));
+        let mut first = LatencyStats {
+            p50_ns: 1,
+            p90_ns: 2,
+            p99_ns: 3,
+            max_ns: 4,
+            ..LatencyStats::default()
+        };
+        for (bucket, value) in first.buckets.iter_mut().zip(10..) {
+            *bucket = value;
+        }
+        round_trip(Record::Latency(Box::new(WriteLatency {
+            first,
+            rewrite: LatencyStats::default(),
+        })));
+        round_trip(Record::Forked(123.5));
+        round_trip(Record::Checksum(0xdead_beef_0bad_f00d));
+        for advice in [DiscardAdvice::DontNeed, DiscardAdvice::Free] {
+            round_trip(Record::Discard(Box::new(DiscardReport {
+                advice,
+                discarded_bytes: 1,
+                lazy_free_kb: 2,
+                kept_pages: 3,
+                zeroed_pages: 4,
+                other_pages: 5,
+                reread_faults: 6,
+                reread_rss_kb: 7,
+                reread_private_dirty_kb: 8,
+            })));
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let payload = stage_reply();
+        let mut pending = frame(payload.clone());
+        pending.pop();
+        assert!(take_frame(&mut pending).is_none());
+        assert_eq!(
+            message(decode_reply(&payload[..payload.len() - 1])),
+            "invalid report: truncated report"
+        );
+        assert_eq!(
+            message(decode_reply(&[])),
+            "invalid report: truncated report"
+        );
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut payload = stage_reply();
+        payload[0] = VERSION + 1;
+        assert_eq!(
+            message(decode_reply(&payload)),
+            format!(
+                "invalid report: report format version {}, expected {VERSION}",
+                VERSION + 1
+            )
+        );
+        let mut request = encode_request(Checkpoint::PostFork).split_off(4);
+        request[0] = VERSION - 1;
+        assert!(decode_request(&request).is_err());
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        let mut payload = vec![VERSION];
+        payload.extend(1u64.to_le_bytes());
+        payload.push(99);
+        assert_eq!(
+            message(decode_reply(&payload)),
+            "invalid report: unknown record type 99"
+        );
+    }
+}
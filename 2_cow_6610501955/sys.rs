@@ -0,0 +1,737 @@
+//! Thin wrappers over the libc calls the experiments need. Each one checks
+//! the return value and reports failures as `io::Error`, so the rest of the
+//! program never touches a raw return code or `errno`.
+//!
+//! Linux and macOS are supported. Constants whose values differ are chosen
+//! with `cfg`; the Linux-only mapping flags and `madvise` advice keep their
+//! Linux values everywhere, and `cow` rejects the options that use them on
+//! other platforms.
+
+use std::ffi::{c_char, CString};
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+
+pub const ENOMEM: i32 = 12;
+pub const SIGKILL: i32 = 9;
+pub const PROT_READ: i32 = 0x1;
+pub const PROT_WRITE: i32 = 0x2;
+pub const MAP_SHARED: i32 = 0x01;
+pub const MAP_PRIVATE: i32 = 0x02;
+#[cfg(target_os = "linux")]
+pub const MAP_ANONYMOUS: i32 = 0x20;
+#[cfg(target_os = "macos")]
+pub const MAP_ANONYMOUS: i32 = 0x1000;
+pub const MAP_HUGETLB: i32 = 0x40000;
+pub const MADV_DONTNEED: i32 = 4;
+#[cfg(target_os = "linux")]
+pub const MADV_FREE: i32 = 8;
+#[cfg(target_os = "macos")]
+pub const MADV_FREE: i32 = 5;
+pub const MADV_DONTFORK: i32 = 10;
+pub const MADV_MERGEABLE: i32 = 12;
+pub const MADV_HUGEPAGE: i32 = 14;
+pub const MADV_NOHUGEPAGE: i32 = 15;
+pub const MADV_WIPEONFORK: i32 = 18;
+#[cfg(target_os = "linux")]
+pub const RLIMIT_MEMLOCK: i32 = 8;
+#[cfg(target_os = "macos")]
+pub const RLIMIT_MEMLOCK: i32 = 6;
+#[cfg(target_os = "linux")]
+pub const RLIMIT_AS: i32 = 9;
+#[cfg(target_os = "macos")]
+pub const RLIMIT_AS: i32 = 5;
+pub const RLIMIT_DATA: i32 = 2;
+#[cfg(target_os = "linux")]
+pub const RLIM_INFINITY: u64 = u64::MAX;
+#[cfg(target_os = "macos")]
+pub const RLIM_INFINITY: u64 = (1 << 63) - 1;
+
+#[cfg(target_os = "linux")]
+const SC_PAGESIZE: i32 = 30;
+#[cfg(target_os = "macos")]
+const SC_PAGESIZE: i32 = 29;
+const RUSAGE_SELF: i32 = 0;
+const F_SETFD: i32 = 2;
+#[cfg(target_os = "linux")]
+const F_DUPFD_CLOEXEC: i32 = 1030;
+#[cfg(target_os = "macos")]
+const F_DUPFD_CLOEXEC: i32 = 67;
+const STDOUT_FD: i32 = 1;
+const WNOHANG: i32 = 1;
+const POLLIN: i16 = 0x1;
+#[cfg(not(target_os = "linux"))]
+const FD_CLOEXEC: i32 = 1;
+#[cfg(target_os = "linux")]
+const O_CLOEXEC: i32 = 0o2_000_000;
+#[cfg(target_os = "linux")]
+const MFD_CLOEXEC: u32 = 0x1;
+#[cfg(target_os = "macos")]
+const PROC_PIDTASKINFO: i32 = 4;
+#[cfg(target_os = "linux")]
+const FICLONE: u64 = 0x4004_9409;
+#[cfg(target_os = "linux")]
+const FS_IOC_FIEMAP: u64 = 0xC020_660B;
+#[cfg(target_os = "linux")]
+const FIEMAP_FLAG_SYNC: u32 = 0x1;
+#[cfg(target_os = "linux")]
+const FIEMAP_EXTENT_LAST: u32 = 0x1;
+pub const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+#[cfg(target_os = "linux")]
+const CLOCK_MONOTONIC: i32 = 1;
+#[cfg(target_os = "macos")]
+const CLOCK_MONOTONIC: i32 = 6;
+#[cfg(target_os = "linux")]
+const TIOCGWINSZ: u64 = 0x5413;
+#[cfg(target_os = "macos")]
+const TIOCGWINSZ: u64 = 0x4008_7468;
+#[cfg(target_os = "linux")]
+const MPOL_BIND: i64 = 2;
+/// Also migrate pages already faulted in, if only this process maps them.
+#[cfg(target_os = "linux")]
+const MPOL_MF_MOVE: i64 = 0x2;
+/// `mbind` and `set_mempolicy` have no glibc wrappers. x86_64 numbers them
+/// apart; aarch64, riscv64 and loongarch64 share the generic table.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const SYS_MBIND: i64 = 237;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const SYS_SET_MEMPOLICY: i64 = 238;
+#[cfg(all(target_os = "linux", not(target_arch = "x86_64")))]
+const SYS_MBIND: i64 = 235;
+#[cfg(all(target_os = "linux", not(target_arch = "x86_64")))]
+const SYS_SET_MEMPOLICY: i64 = 237;
+/// Words of the node and CPU masks: 1024 bits, the kernel's usual maximum.
+#[cfg(target_os = "linux")]
+const MASK_WORDS: usize = 16;
+/// Extents fetched per `FS_IOC_FIEMAP` call.
+#[cfg(target_os = "linux")]
+const FIEMAP_BATCH: usize = 256;
+
+unsafe extern "C" {
+    fn fork() -> i32;
+    fn waitpid(pid: i32, status: *mut i32, options: i32) -> i32;
+    fn kill(pid: i32, signal: i32) -> i32;
+    fn poll(fds: *mut PollFd, nfds: NfdsT, timeout_ms: i32) -> i32;
+    #[cfg(not(target_os = "linux"))]
+    fn pipe(fds: *mut i32) -> i32;
+    fn dup2(old: i32, new: i32) -> i32;
+    fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+    fn read(fd: i32, buf: *mut u8, count: usize) -> isize;
+    fn write(fd: i32, buf: *const u8, count: usize) -> isize;
+    fn sysconf(name: i32) -> isize;
+    fn gethostname(name: *mut c_char, len: usize) -> i32;
+    fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+    fn munmap(addr: *mut u8, len: usize) -> i32;
+    fn madvise(addr: *mut u8, len: usize, advice: i32) -> i32;
+    fn getrusage(who: i32, usage: *mut RUsage) -> i32;
+    fn getrlimit(resource: i32, rlim: *mut [u64; 2]) -> i32;
+    fn setrlimit(resource: i32, rlim: *const [u64; 2]) -> i32;
+    fn mlock(addr: *const u8, len: usize) -> i32;
+    fn posix_spawn(
+        pid: *mut i32,
+        path: *const c_char,
+        file_actions: *const u8,
+        attrp: *const u8,
+        argv: *const *const c_char,
+        envp: *const *const c_char,
+    ) -> i32;
+    static environ: *const *const c_char;
+    fn execv(path: *const c_char, argv: *const *const c_char) -> i32;
+    fn ioctl(fd: i32, request: u64, ...) -> i32;
+    fn clock_gettime(clock: i32, time: *mut [i64; 2]) -> i32;
+    fn _exit(status: i32) -> !;
+}
+
+#[cfg(target_os = "linux")]
+unsafe extern "C" {
+    fn memfd_create(name: *const c_char, flags: u32) -> i32;
+    fn pipe2(fds: *mut i32, flags: i32) -> i32;
+    fn ftruncate(fd: i32, length: i64) -> i32;
+    fn syscall(number: i64, ...) -> i64;
+    fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn proc_pidinfo(pid: i32, flavor: i32, arg: u64, buffer: *mut u8, size: i32) -> i32;
+    fn sysctlbyname(
+        name: *const c_char,
+        old: *mut u8,
+        old_len: *mut usize,
+        new: *const u8,
+        new_len: usize,
+    ) -> i32;
+}
+
+/// `struct rusage`: two timevals followed by fourteen longs, of which only the
+/// fault counters are read here. Linux and 64-bit macOS share this layout.
+#[repr(C)]
+#[derive(Default)]
+struct RUsage {
+    ru_utime: [i64; 2],
+    ru_stime: [i64; 2],
+    ru_maxrss: i64,
+    ru_ixrss: i64,
+    ru_idrss: i64,
+    ru_isrss: i64,
+    ru_minflt: i64,
+    ru_majflt: i64,
+    ru_rest: [i64; 8],
+}
+
+/// `struct winsize`.
+#[repr(C)]
+#[derive(Default)]
+struct WinSize {
+    rows: u16,
+    cols: u16,
+    x_pixels: u16,
+    y_pixels: u16,
+}
+
+/// `struct pollfd`.
+#[repr(C)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+/// `struct fiemap` from `<linux/fiemap.h>` with room for a batch of extents.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct Fiemap {
+    fm_start: u64,
+    fm_length: u64,
+    fm_flags: u32,
+    fm_mapped_extents: u32,
+    fm_extent_count: u32,
+    fm_reserved: u32,
+    fm_extents: [FiemapExtent; FIEMAP_BATCH],
+}
+
+/// `struct fiemap_extent`.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    fe_logical: u64,
+    fe_physical: u64,
+    fe_length: u64,
+    fe_reserved64: [u64; 2],
+    fe_flags: u32,
+    fe_reserved: [u32; 3],
+}
+
+/// Where a run of a file's bytes lives on disk, in bytes.
+pub struct Extent {
+    pub logical: u64,
+    pub physical: u64,
+    pub length: u64,
+    /// `FIEMAP_EXTENT_*` flags, such as [`FIEMAP_EXTENT_SHARED`].
+    pub flags: u32,
+}
+
+#[cfg(target_os = "linux")]
+type NfdsT = u64;
+#[cfg(target_os = "macos")]
+type NfdsT = u32;
+
+/// Which side of a successful `fork` the caller is on.
+pub enum Fork {
+    Child,
+    Parent(i32),
+}
+
+fn check(rc: i32) -> io::Result<()> {
+    if rc < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn fork_process() -> io::Result<Fork> {
+    match unsafe { fork() } {
+        pid if pid < 0 => Err(io::Error::last_os_error()),
+        0 => Ok(Fork::Child),
+        pid => Ok(Fork::Parent(pid)),
+    }
+}
+
+/// Takes ownership of `fd`, just returned by a call that created it, so it
+/// closes when dropped.
+fn owned(fd: i32) -> io::Result<OwnedFd> {
+    check(fd)?;
+    // SAFETY: `fd` is open and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Creates a pipe and returns its `(read, write)` ends. Both are
+/// close-on-exec, so no program started by [`spawn`] or [`exec`] inherits
+/// them unless [`set_inheritable`] says so, and each closes when dropped.
+pub fn create_pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [-1i32; 2];
+    #[cfg(target_os = "linux")]
+    check(unsafe { pipe2(fds.as_mut_ptr(), O_CLOEXEC) })?;
+    #[cfg(not(target_os = "linux"))]
+    check(unsafe { pipe(fds.as_mut_ptr()) })?;
+    let ends = (owned(fds[0])?, owned(fds[1])?);
+    // macOS has no pipe2; nothing else runs between the calls to exec them.
+    #[cfg(not(target_os = "linux"))]
+    for end in [&ends.0, &ends.1] {
+        check(unsafe { fcntl(end.as_raw_fd(), F_SETFD, FD_CLOEXEC) })?;
+    }
+    Ok(ends)
+}
+
+/// Clears close-on-exec on `fd`, for a descriptor whose number is handed to
+/// a program started by [`spawn`] or [`exec`].
+pub fn set_inheritable(fd: BorrowedFd<'_>) -> io::Result<()> {
+    check(unsafe { fcntl(fd.as_raw_fd(), F_SETFD, 0) })
+}
+
+/// Points standard output at `fd` and returns a close-on-exec copy of what
+/// it was, so forked children and spawned programs inherit `fd` while the
+/// caller can still write to the original.
+pub fn redirect_stdout(fd: BorrowedFd<'_>) -> io::Result<OwnedFd> {
+    let saved = owned(unsafe { fcntl(STDOUT_FD, F_DUPFD_CLOEXEC, 3) })?;
+    check(unsafe { dup2(fd.as_raw_fd(), STDOUT_FD) })?;
+    Ok(saved)
+}
+
+pub fn read_fd(fd: BorrowedFd<'_>, buf: &mut [u8]) -> io::Result<usize> {
+    let count = unsafe { read(fd.as_raw_fd(), buf.as_mut_ptr(), buf.len()) };
+    if count < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
+pub fn write_fd(fd: BorrowedFd<'_>, buf: &[u8]) -> io::Result<usize> {
+    let count = unsafe { write(fd.as_raw_fd(), buf.as_ptr(), buf.len()) };
+    if count < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
+/// Waits for `pid` to exit, retrying when interrupted by a signal, and
+/// returns its raw wait status.
+pub fn wait_pid(pid: i32) -> io::Result<i32> {
+    let mut status = 0;
+    loop {
+        if unsafe { waitpid(pid, &mut status, 0) } >= 0 {
+            return Ok(status);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Like [`wait_pid`], but returns `None` at once if `pid` has not exited yet.
+pub fn try_wait_pid(pid: i32) -> io::Result<Option<i32>> {
+    let mut status = 0;
+    match unsafe { waitpid(pid, &mut status, WNOHANG) } {
+        rc if rc < 0 => Err(io::Error::last_os_error()),
+        0 => Ok(None),
+        _ => Ok(Some(status)),
+    }
+}
+
+pub fn send_signal(pid: i32, signal: i32) -> io::Result<()> {
+    check(unsafe { kill(pid, signal) })
+}
+
+/// Waits up to `timeout_ms` for `fd` to have data or reach end of file, and
+/// returns whether it did.
+pub fn wait_readable(fd: BorrowedFd<'_>, timeout_ms: i32) -> io::Result<bool> {
+    let mut pollfd = PollFd {
+        fd: fd.as_raw_fd(),
+        events: POLLIN,
+        revents: 0,
+    };
+    match unsafe { poll(&mut pollfd, 1, timeout_ms) } {
+        rc if rc < 0 => Err(io::Error::last_os_error()),
+        rc => Ok(rc > 0),
+    }
+}
+
+pub fn page_size() -> io::Result<usize> {
+    let size = unsafe { sysconf(SC_PAGESIZE) };
+    if size <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(size as usize)
+}
+
+/// Nanoseconds on `CLOCK_MONOTONIC`, the clock perf, ftrace and bpftrace
+/// stamp their events with, so the two timelines line up.
+pub fn monotonic_ns() -> io::Result<u64> {
+    let mut time = [0i64; 2];
+    check(unsafe { clock_gettime(CLOCK_MONOTONIC, &mut time) })?;
+    Ok(time[0] as u64 * 1_000_000_000 + time[1] as u64)
+}
+
+/// The host's name, as `uname -n` prints it.
+pub fn hostname() -> io::Result<String> {
+    let mut name = [0u8; 256];
+    check(unsafe { gethostname(name.as_mut_ptr().cast(), name.len()) })?;
+    let end = name
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(name.len());
+    Ok(String::from_utf8_lossy(&name[..end]).into_owned())
+}
+
+/// Rows and columns of the terminal on `fd`.
+pub fn terminal_size(fd: BorrowedFd<'_>) -> io::Result<(u16, u16)> {
+    let mut size = WinSize::default();
+    check(unsafe { ioctl(fd.as_raw_fd(), TIOCGWINSZ, &mut size as *mut WinSize) })?;
+    Ok((size.rows, size.cols))
+}
+
+/// Ends the process at once, skipping destructors, atexit handlers, and stdio
+/// flushes that would otherwise run twice in a forked child.
+pub fn exit_now(status: i32) -> ! {
+    unsafe { _exit(status) }
+}
+
+/// Maps `len` bytes readable and writable, of `fd` or of anonymous memory.
+pub fn map_memory(len: usize, flags: i32, fd: Option<BorrowedFd<'_>>) -> io::Result<*mut u8> {
+    let fd = fd.map_or(-1, |fd| fd.as_raw_fd());
+    let ptr = unsafe {
+        mmap(
+            std::ptr::null_mut(),
+            len,
+            PROT_READ | PROT_WRITE,
+            flags,
+            fd,
+            0,
+        )
+    };
+    // MAP_FAILED is (void *)-1.
+    if ptr as isize == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr)
+}
+
+/// # Safety
+///
+/// `ptr..ptr + len` must be a mapping returned by [`map_memory`] that nothing
+/// refers to any more.
+pub unsafe fn unmap_memory(ptr: *mut u8, len: usize) -> io::Result<()> {
+    check(unsafe { munmap(ptr, len) })
+}
+
+/// Advises the kernel about `region`, which must be page-aligned.
+pub fn advise(region: &mut [u8], advice: i32) -> io::Result<()> {
+    check(unsafe { madvise(region.as_mut_ptr(), region.len(), advice) })
+}
+
+pub fn lock_memory(region: &[u8]) -> io::Result<()> {
+    check(unsafe { mlock(region.as_ptr(), region.len()) })
+}
+
+/// The `(soft, hard)` limits of `resource`.
+pub fn resource_limit(resource: i32) -> io::Result<(u64, u64)> {
+    let mut limit = [0u64; 2];
+    check(unsafe { getrlimit(resource, &mut limit) })?;
+    Ok((limit[0], limit[1]))
+}
+
+/// Sets the `(soft, hard)` limits of `resource`; inherited across fork and
+/// exec.
+pub fn set_resource_limit(resource: i32, soft: u64, hard: u64) -> io::Result<()> {
+    check(unsafe { setrlimit(resource, &[soft, hard]) })
+}
+
+/// Minor and major page faults of the calling process so far.
+pub fn fault_counts() -> io::Result<(u64, u64)> {
+    let mut usage = RUsage::default();
+    check(unsafe { getrusage(RUSAGE_SELF, &mut usage) })?;
+    Ok((usage.ru_minflt as u64, usage.ru_majflt as u64))
+}
+
+/// Creates an anonymous memory-backed file of `len` bytes.
+#[cfg(target_os = "linux")]
+pub fn create_memfd(name: &str, len: usize) -> io::Result<OwnedFd> {
+    let name = CString::new(name).map_err(io::Error::other)?;
+    let fd = owned(unsafe { memfd_create(name.as_ptr(), MFD_CLOEXEC) })?;
+    check(unsafe { ftruncate(fd.as_raw_fd(), len as i64) })?;
+    Ok(fd)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn create_memfd(_name: &str, _len: usize) -> io::Result<OwnedFd> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "memfd_create is Linux-only",
+    ))
+}
+
+/// A node or CPU mask with bit `index` set for each of `indices`.
+#[cfg(target_os = "linux")]
+fn bit_mask(indices: &[usize]) -> io::Result<[u64; MASK_WORDS]> {
+    let mut mask = [0u64; MASK_WORDS];
+    for &index in indices {
+        let word = mask.get_mut(index / 64).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{index} is out of range"),
+            )
+        })?;
+        *word |= 1 << (index % 64);
+    }
+    Ok(mask)
+}
+
+/// Allocates the pages of `region`, which must be page-aligned, on NUMA
+/// `node` only, moving those already there that no other process maps.
+#[cfg(target_os = "linux")]
+pub fn bind_memory(region: &mut [u8], node: u32) -> io::Result<()> {
+    let mask = bit_mask(&[node as usize])?;
+    let rc = unsafe {
+        syscall(
+            SYS_MBIND,
+            region.as_mut_ptr(),
+            region.len(),
+            MPOL_BIND,
+            mask.as_ptr(),
+            (MASK_WORDS * 64) as u64,
+            MPOL_MF_MOVE,
+        )
+    };
+    check(rc as i32)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_memory(_region: &mut [u8], _node: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "mbind is Linux-only",
+    ))
+}
+
+/// Allocates every later page of the calling process on NUMA `node` only,
+/// except in mappings with a policy of their own.
+#[cfg(target_os = "linux")]
+pub fn set_memory_policy(node: u32) -> io::Result<()> {
+    let mask = bit_mask(&[node as usize])?;
+    let rc = unsafe {
+        syscall(
+            SYS_SET_MEMPOLICY,
+            MPOL_BIND,
+            mask.as_ptr(),
+            (MASK_WORDS * 64) as u64,
+        )
+    };
+    check(rc as i32)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_memory_policy(_node: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "set_mempolicy is Linux-only",
+    ))
+}
+
+/// Keeps the calling thread on `cpus`.
+#[cfg(target_os = "linux")]
+pub fn set_cpu_affinity(cpus: &[usize]) -> io::Result<()> {
+    let mask = bit_mask(cpus)?;
+    check(unsafe { sched_setaffinity(0, MASK_WORDS * 8, mask.as_ptr()) })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_cpu_affinity(_cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "sched_setaffinity is Linux-only",
+    ))
+}
+
+/// Makes the file `dest` share every block of `src` (a reflink). Only
+/// filesystems with copy-on-write extents, such as btrfs and XFS, support it.
+#[cfg(target_os = "linux")]
+pub fn clone_file(dest: BorrowedFd<'_>, src: BorrowedFd<'_>) -> io::Result<()> {
+    check(unsafe { ioctl(dest.as_raw_fd(), FICLONE, src.as_raw_fd()) })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn clone_file(_dest: BorrowedFd<'_>, _src: BorrowedFd<'_>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "FICLONE is Linux-only",
+    ))
+}
+
+/// Every extent of the file `fd`, after flushing its dirty data so that
+/// delayed allocations have a place on disk.
+#[cfg(target_os = "linux")]
+pub fn file_extents(fd: BorrowedFd<'_>) -> io::Result<Vec<Extent>> {
+    let mut extents = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut map = Box::new(Fiemap {
+            fm_start: start,
+            fm_length: u64::MAX - start,
+            fm_flags: FIEMAP_FLAG_SYNC,
+            fm_mapped_extents: 0,
+            fm_extent_count: FIEMAP_BATCH as u32,
+            fm_reserved: 0,
+            fm_extents: [FiemapExtent::default(); FIEMAP_BATCH],
+        });
+        check(unsafe { ioctl(fd.as_raw_fd(), FS_IOC_FIEMAP, &mut *map as *mut Fiemap) })?;
+        let mapped = &map.fm_extents[..map.fm_mapped_extents as usize];
+        extents.extend(mapped.iter().map(|extent| Extent {
+            logical: extent.fe_logical,
+            physical: extent.fe_physical,
+            length: extent.fe_length,
+            flags: extent.fe_flags,
+        }));
+        match mapped.last() {
+            Some(last) if last.fe_flags & FIEMAP_EXTENT_LAST == 0 => {
+                start = last.fe_logical + last.fe_length;
+            }
+            _ => return Ok(extents),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn file_extents(_fd: BorrowedFd<'_>) -> io::Result<Vec<Extent>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "FIEMAP is Linux-only",
+    ))
+}
+
+/// Replaces the current process image with `argv[0]`, keeping this
+/// process's environment and every descriptor not marked close-on-exec.
+/// Returns only on failure, with the error `execv` set.
+pub fn exec(argv: &[CString]) -> io::Error {
+    if argv.is_empty() {
+        return io::Error::new(io::ErrorKind::InvalidInput, "execv needs a program");
+    }
+    let mut pointers: Vec<*const c_char> = argv.iter().map(|arg| arg.as_ptr()).collect();
+    pointers.push(std::ptr::null());
+    unsafe { execv(pointers[0], pointers.as_ptr()) };
+    io::Error::last_os_error()
+}
+
+/// Starts `argv[0]` with the given arguments and this process's environment,
+/// returning the new process id.
+pub fn spawn(argv: &[CString]) -> io::Result<i32> {
+    if argv.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "posix_spawn needs a program",
+        ));
+    }
+    let mut pointers: Vec<*const c_char> = argv.iter().map(|arg| arg.as_ptr()).collect();
+    pointers.push(std::ptr::null());
+    let mut pid = 0;
+    let rc = unsafe {
+        posix_spawn(
+            &mut pid,
+            pointers[0],
+            std::ptr::null(),
+            std::ptr::null(),
+            pointers.as_ptr(),
+            environ,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::from_raw_os_error(rc));
+    }
+    Ok(pid)
+}
+
+/// The raw value of sysctl `name`, such as `hw.memsize`.
+#[cfg(target_os = "macos")]
+pub fn sysctl(name: &str) -> io::Result<Vec<u8>> {
+    let name =
+        CString::new(name).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let mut len = 0usize;
+    check(unsafe {
+        sysctlbyname(
+            name.as_ptr(),
+            std::ptr::null_mut(),
+            &mut len,
+            std::ptr::null(),
+            0,
+        )
+    })?;
+    let mut value = vec![0u8; len];
+    check(unsafe {
+        sysctlbyname(
+            name.as_ptr(),
+            value.as_mut_ptr(),
+            &mut len,
+            std::ptr::null(),
+            0,
+        )
+    })?;
+    value.truncate(len);
+    Ok(value)
+}
+
+/// `struct proc_taskinfo` from `<sys/proc_info.h>`.
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Default)]
+struct ProcTaskInfo {
+    pti_virtual_size: u64,
+    pti_resident_size: u64,
+    pti_times: [u64; 4],
+    pti_policy: i32,
+    pti_faults: i32,
+    pti_pageins: i32,
+    pti_cow_faults: i32,
+    pti_rest: [i32; 8],
+}
+
+/// Memory and fault counters Mach keeps for a task, as read by
+/// `proc_pidinfo(PROC_PIDTASKINFO)`. macOS has no per-mapping dirty-page
+/// accounting, so this is all `cow` can report there.
+#[cfg(target_os = "macos")]
+pub struct TaskInfo {
+    pub resident_bytes: u64,
+    /// Every page fault, copy-on-write or not.
+    pub faults: u64,
+    /// Faults that had to read the page in from disk.
+    pub pageins: u64,
+}
+
+#[cfg(target_os = "macos")]
+pub fn task_info(pid: u32) -> io::Result<TaskInfo> {
+    let mut info = ProcTaskInfo::default();
+    let size = std::mem::size_of::<ProcTaskInfo>() as i32;
+    let written = unsafe {
+        proc_pidinfo(
+            pid as i32,
+            PROC_PIDTASKINFO,
+            0,
+            (&mut info as *mut ProcTaskInfo).cast(),
+            size,
+        )
+    };
+    if written <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if written < size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "short proc_pidinfo result",
+        ));
+    }
+    Ok(TaskInfo {
+        resident_bytes: info.pti_resident_size,
+        faults: info.pti_faults as u64,
+        pageins: info.pti_pageins as u64,
+    })
+}